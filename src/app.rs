@@ -1,18 +1,18 @@
-use wgpu::util::DeviceExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use winit::application::ApplicationHandler;
 use winit::event::{DeviceEvent, DeviceId, WindowEvent};
-use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::event_loop::ActiveEventLoop;
+use winit::keyboard::KeyCode;
 use winit::window::{Window, WindowId};
 
-use crate::camera::Camera;
-use crate::camera_uniform::CameraUniform;
 use crate::input_context::InputContext;
 use crate::render_context::RenderContext;
 use crate::renderables::cube::Cube;
 use crate::renderables::skybox::Skybox;
 use crate::renderables::ui::UI;
 use crate::state::State;
-use crate::my_texture::TextureSource;
+use crate::my_texture::{TextureFiltering, TextureSource};
 
 #[derive(Default)]
 pub struct App {
@@ -30,17 +30,22 @@ impl App {
     }
 }
 
-impl<'a> ApplicationHandler for App {
+impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let window = event_loop
             .create_window(Window::default_attributes())
             .unwrap();
-        self.render_context = Some(RenderContext::new(window));
+        let mut render_context = RenderContext::new(window);
+        // smooths the edges of the 3d geometry if the adapter supports 4 samples, M switches between the sample counts
+        let _ = render_context.set_sample_count(4);
+        // keeps the textures sharp when they are seen at a grazing angle
+        render_context.texture_filtering = TextureFiltering::Anisotropic(16);
+        self.render_context = Some(render_context);
         // RENDERABLES.lock().unwrap().push(Box::new(Polygon));
-        self.state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
-        self.state.renderables.push(Box::new(Skybox::new("assets/skybox".to_string())));
+        self.state.add_renderable(Cube::new("assets/grass.jpg".to_string())).unwrap();
+        self.state.add_renderable(Skybox::new("assets/skybox".to_string())).unwrap();
         // self.state.renderables.push(Box::new(UI::new(TextureSource::FilePath("assets/grass.jpg".to_string()))));
-        self.state.renderables.push(Box::new(UI::new(TextureSource::TextCharacter{character: '啊', font_file_path: "assets/KaiTi.ttf".to_string()})));
+        self.state.add_renderable(UI::new(TextureSource::TextCharacter{character: '啊', font_file_path: "assets/KaiTi.ttf".to_string()})).unwrap();
     }
     fn device_event(
         &mut self,
@@ -75,16 +80,26 @@ impl<'a> ApplicationHandler for App {
                 // You only need to call this if you've determined that you need to redraw in
                 // applications which do not always need to. Applications that redraw continuously
                 // can render here instead.
-                self.get_context().window.request_redraw();
+                self.get_context().window.as_ref().unwrap().request_redraw();
 
                 // if !surface_configured {
                 //     return;
                 // }
 
-                let window = self.get_context().window.clone();
+                let window = self.get_context().window.clone().unwrap();
                 self.state.update(&mut self.input_context, window);
                 // take out the render context from self
                 let mut render_context = self.render_context.take().unwrap();
+                if self.input_context.get_key_down(KeyCode::F12) {
+                    render_context.request_screenshot();
+                }
+                if self.input_context.get_key_down(KeyCode::KeyM) {
+                    let sample_counts = render_context.supported_sample_counts();
+                    let index = sample_counts.iter().position(|count| *count == render_context.sample_count()).unwrap();
+                    let sample_count = sample_counts[(index + 1) % sample_counts.len()];
+                    render_context.set_sample_count(sample_count).unwrap();
+                    println!("MSAA: {}x", sample_count);
+                }
 
                 match render_context.render(&mut self.state) {
                     Ok(_) => {}
@@ -103,6 +118,14 @@ impl<'a> ApplicationHandler for App {
                         log::warn!("Surface timeout")
                     }
                 }
+                if let Some(screenshot) = render_context.take_screenshot() {
+                    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+                    let file_path = format!("screenshot_{}.png", time);
+                    match screenshot.save(&file_path) {
+                        Ok(_) => println!("Saved screenshot to {}", file_path),
+                        Err(e) => log::error!("Failed to save screenshot: {}", e),
+                    }
+                }
                 self.render_context = Some(render_context);
             }
             _ => (),
@@ -0,0 +1,353 @@
+// everything that is loaded once and shared, like textures, cube textures, meshes, fonts and shaders.
+// Loading hands out a Handle, handles to the same key share one asset and it is dropped with the last of them.
+// Assets can be loaded asynchronously, their files are then read on worker threads and the asset is made on the
+// render thread in AssetServer::update
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    panic::AssertUnwindSafe,
+    sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, mpsc, Arc, Condvar, Mutex, OnceLock, Weak},
+};
+
+use tokio::runtime::Runtime;
+
+use crate::render_context::RenderContext;
+
+pub type AssetError = Box<dyn std::error::Error + Send + Sync>;
+
+pub trait Asset: Sized + Send + Sync + 'static {
+    // tells the assets of a type apart, like the path of a file. Loading a key again gives the asset that is already loaded
+    type Key: Clone + Eq + Hash + Debug + Send + Sync + 'static;
+    // what read returns, it is made into the asset by create
+    type Data: Send + 'static;
+    // reads and decodes the files of the key, on a worker thread when the asset is loaded asynchronously
+    fn read(key: &Self::Key, assets: &AssetServer) -> Result<Self::Data, AssetError>;
+    // uploads the data to the gpu, always on the thread that renders
+    fn create(key: &Self::Key, data: Self::Data, render_context: &RenderContext) -> Result<Self, AssetError>;
+    // keys that read no files are loaded right away, even when they are asked for asynchronously
+    fn reads_files(key: &Self::Key) -> bool {
+        let _ = key;
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    // the error the asset failed with, loading the key again will not retry while a handle to it exists
+    Failed(String),
+}
+
+enum AssetState<T> {
+    Loading,
+    Loaded(Arc<T>),
+    Failed(String),
+}
+
+struct Slot<T: Asset> {
+    id: u64,
+    key: T::Key,
+    state: Mutex<AssetState<T>>,
+    // notified when the asset stops loading
+    loaded: Condvar,
+}
+
+impl<T: Asset> Slot<T> {
+    fn finish(&self, result: Result<T, AssetError>) {
+        let state = match result {
+            Ok(asset) => AssetState::Loaded(Arc::new(asset)),
+            Err(error) => {
+                log::warn!("Failed to load {:?}: {}", self.key, error);
+                AssetState::Failed(error.to_string())
+            }
+        };
+        *self.state.lock().unwrap() = state;
+        self.loaded.notify_all();
+    }
+}
+
+pub struct Handle<T: Asset> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T: Asset> Handle<T> {
+    // unique for as long as the server lives, even after the asset was dropped
+    pub fn id(&self) -> u64 {
+        self.slot.id
+    }
+    pub fn key(&self) -> &T::Key {
+        &self.slot.key
+    }
+    pub fn load_state(&self) -> LoadState {
+        match &*self.slot.state.lock().unwrap() {
+            AssetState::Loading => LoadState::Loading,
+            AssetState::Loaded(_) => LoadState::Loaded,
+            AssetState::Failed(error) => LoadState::Failed(error.clone()),
+        }
+    }
+    pub fn is_loading(&self) -> bool {
+        matches!(*self.slot.state.lock().unwrap(), AssetState::Loading)
+    }
+    // None while the asset is loading or if it failed
+    pub fn get(&self) -> Option<Arc<T>> {
+        match &*self.slot.state.lock().unwrap() {
+            AssetState::Loaded(asset) => Some(asset.clone()),
+            _ => None,
+        }
+    }
+    // panics with the error of an asset that failed
+    pub fn expect_loaded(&self) -> Arc<T> {
+        self.get().unwrap_or_else(|| panic!("{:?} is not loaded: {:?}", self.key(), self.load_state()))
+    }
+    // the number of handles sharing the asset, including this one
+    pub fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.slot)
+    }
+    // does not keep the asset alive
+    pub fn downgrade(&self) -> WeakHandle<T> {
+        WeakHandle {
+            id: self.slot.id,
+            slot: Arc::downgrade(&self.slot),
+        }
+    }
+}
+
+impl<T: Asset> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self { slot: self.slot.clone() }
+    }
+}
+
+impl<T: Asset> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl<T: Asset> Eq for Handle<T> {}
+
+impl<T: Asset> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+impl<T: Asset> Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Handle").field("id", &self.id()).field("key", self.key()).field("state", &self.load_state()).finish()
+    }
+}
+
+pub struct WeakHandle<T: Asset> {
+    id: u64,
+    slot: Weak<Slot<T>>,
+}
+
+impl<T: Asset> WeakHandle<T> {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    // None once every handle was dropped
+    pub fn upgrade(&self) -> Option<Handle<T>> {
+        self.slot.upgrade().map(|slot| Handle { slot })
+    }
+    pub fn is_alive(&self) -> bool {
+        self.slot.strong_count() > 0
+    }
+}
+
+impl<T: Asset> Clone for WeakHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+type Slots<T> = HashMap<<T as Asset>::Key, Weak<Slot<T>>>;
+
+// makes an asset from the data a worker read, on the render thread
+type Completion = Box<dyn FnOnce(&RenderContext) + Send>;
+
+struct Inner {
+    // the slots of every asset type, keyed by the type of the asset
+    storages: Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
+    next_id: AtomicU64,
+    // the workers, started with the first asynchronous load
+    runtime: OnceLock<Runtime>,
+    completions: (mpsc::Sender<Completion>, Mutex<mpsc::Receiver<Completion>>),
+    // asynchronous loads whose completion has not run yet
+    pending: AtomicUsize,
+}
+
+impl Drop for Inner {
+    // the last clone of the server can be dropped on a worker, where the runtime cannot wait for its threads
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+// owned by the render context, the assets belong to its device. Clones share the assets
+#[derive(Clone)]
+pub struct AssetServer {
+    inner: Arc<Inner>,
+}
+
+impl Default for AssetServer {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            inner: Arc::new(Inner {
+                storages: Mutex::new(HashMap::new()),
+                next_id: AtomicU64::new(0),
+                runtime: OnceLock::new(),
+                completions: (sender, Mutex::new(receiver)),
+                pending: AtomicUsize::new(0),
+            }),
+        }
+    }
+}
+
+impl AssetServer {
+    fn with_slots<T: Asset, R>(&self, f: impl FnOnce(&mut Slots<T>) -> R) -> R {
+        let mut storages = self.inner.storages.lock().unwrap();
+        let slots = storages.entry(TypeId::of::<T>()).or_insert_with(|| Box::new(Slots::<T>::new()));
+        f(slots.downcast_mut().unwrap())
+    }
+
+    // the handle of the key and whether it is new, in which case the caller loads the asset
+    fn get_or_add_slot<T: Asset>(&self, key: T::Key) -> (Handle<T>, bool) {
+        self.with_slots::<T, _>(|slots| {
+            if let Some(slot) = slots.get(&key).and_then(Weak::upgrade) {
+                return (Handle { slot }, false);
+            }
+            // forget the assets that were dropped
+            slots.retain(|_, slot| slot.strong_count() > 0);
+            let slot = Arc::new(Slot {
+                id: self.inner.next_id.fetch_add(1, Ordering::Relaxed),
+                key: key.clone(),
+                state: Mutex::new(AssetState::Loading),
+                loaded: Condvar::new(),
+            });
+            slots.insert(key, Arc::downgrade(&slot));
+            (Handle { slot }, true)
+        })
+    }
+
+    // the asset is loaded unless a handle to it exists already, an asset that is loading asynchronously is waited for.
+    // Failures end up in the load state of the handle
+    pub fn load<T: Asset>(&self, key: T::Key, render_context: &RenderContext) -> Handle<T> {
+        let (handle, is_new) = self.get_or_add_slot::<T>(key);
+        if is_new {
+            // outside of the lock, so that assets can load the assets they are made from
+            let result = T::read(handle.key(), self).and_then(|data| T::create(handle.key(), data, render_context));
+            handle.slot.finish(result);
+        }
+        while handle.is_loading() {
+            self.complete_one(render_context);
+        }
+        handle
+    }
+
+    // the files are read on a worker thread, the handle is loading until update made the asset
+    pub fn load_async<T: Asset>(&self, key: T::Key, render_context: &RenderContext) -> Handle<T> {
+        if !T::reads_files(&key) {
+            return self.load(key, render_context);
+        }
+        let (handle, is_new) = self.get_or_add_slot::<T>(key);
+        if is_new {
+            self.inner.pending.fetch_add(1, Ordering::Relaxed);
+            let runtime = self.inner.runtime.get_or_init(|| {
+                tokio::runtime::Builder::new_multi_thread()
+                    .thread_name("asset loader")
+                    .build()
+                    .unwrap()
+            });
+            let assets = self.clone();
+            let worker_handle = handle.clone();
+            runtime.spawn_blocking(move || {
+                // a panic while reading fails the asset instead of leaving it loading forever
+                let data = std::panic::catch_unwind(AssertUnwindSafe(|| T::read(worker_handle.key(), &assets)))
+                    .unwrap_or_else(|panic| Err(panic_message(panic).into()));
+                let completion: Completion = Box::new(move |render_context| {
+                    let result = data.and_then(|data| T::create(worker_handle.key(), data, render_context));
+                    worker_handle.slot.finish(result);
+                });
+                // the server is gone if the receiver is
+                let _ = assets.inner.completions.0.send(completion);
+            });
+        }
+        handle
+    }
+
+    // loads assets that need no gpu on the calling thread, like fonts on the workers that read glyphs.
+    // An asset that is loading on another thread is waited for
+    pub fn read<T: Asset<Data = T>>(&self, key: T::Key) -> Handle<T> {
+        let (handle, is_new) = self.get_or_add_slot::<T>(key);
+        if is_new {
+            handle.slot.finish(T::read(handle.key(), self));
+        }
+        let state = handle.slot.state.lock().unwrap();
+        drop(handle.slot.loaded.wait_while(state, |state| matches!(state, AssetState::Loading)).unwrap());
+        handle
+    }
+
+    fn run_completion(&self, completion: Completion, render_context: &RenderContext) {
+        completion(render_context);
+        self.inner.pending.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    // waits for the next asynchronous load to finish and makes its asset
+    fn complete_one(&self, render_context: &RenderContext) {
+        // the receiver is not locked while the asset is made, it may load others
+        let completion = self.inner.completions.1.lock().unwrap().recv().unwrap();
+        self.run_completion(completion, render_context);
+    }
+
+    // makes the assets whose files were read since the last call, called after every frame
+    pub fn update(&self, render_context: &RenderContext) {
+        loop {
+            let completion = self.inner.completions.1.lock().unwrap().try_recv();
+            match completion {
+                Ok(completion) => self.run_completion(completion, render_context),
+                Err(_) => break,
+            }
+        }
+    }
+
+    // blocks until every asynchronous load has finished
+    pub fn finish_loading(&self, render_context: &RenderContext) {
+        while self.pending() > 0 {
+            self.complete_one(render_context);
+        }
+    }
+
+    // the number of asynchronous loads that have not finished
+    pub fn pending(&self) -> usize {
+        self.inner.pending.load(Ordering::Relaxed)
+    }
+
+    // the handle of an asset that is loaded or loading, None if it was never loaded or every handle to it was dropped
+    pub fn get_handle<T: Asset>(&self, key: &T::Key) -> Option<Handle<T>> {
+        self.with_slots::<T, _>(|slots| slots.get(key).and_then(Weak::upgrade)).map(|slot| Handle { slot })
+    }
+
+    // the number of assets of a type that still have handles
+    pub fn count<T: Asset>(&self) -> usize {
+        self.with_slots::<T, _>(|slots| slots.values().filter(|slot| slot.strong_count() > 0).count())
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => panic.downcast_ref::<&str>().map_or("panicked".to_string(), |message| message.to_string()),
+    }
+}
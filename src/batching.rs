@@ -0,0 +1,251 @@
+// merges renderables that are drawn the same way into one instanced draw
+
+use std::{any::TypeId, collections::HashMap, sync::Mutex};
+
+use crate::{
+    instance::InstanceRaw,
+    render_context::RenderContext,
+    renderable::{get_pipeline_from_cache, unpack_pipeline, IndexFormat, Renderable},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BatchStats {
+    // renderables with at least one instance
+    pub renderables: u32,
+    pub draws: u32,
+    // pipeline, bind group and buffer bindings that were skipped because they were already bound
+    pub skipped_state_changes: u32,
+}
+
+impl BatchStats {
+    pub fn saved_draws(&self) -> u32 {
+        self.renderables - self.draws
+    }
+    pub fn add(&mut self, other: BatchStats) {
+        self.renderables += other.renderables;
+        self.draws += other.draws;
+        self.skipped_state_changes += other.skipped_state_changes;
+    }
+}
+
+// wgpu objects compare by identity, so equal keys mean the exact same gpu state
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct BatchKey {
+    pipeline_type: TypeId,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: Option<wgpu::Buffer>,
+    index_format: IndexFormat,
+    num_indices: u32,
+    num_vertices: u32,
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+struct Batch {
+    // None for renderables that draw themselves
+    key: Option<BatchKey>,
+    members: Vec<usize>,
+    // only gathered once a second member joins, a single renderable draws from its own instance buffer
+    instances: Vec<InstanceRaw>,
+}
+
+fn get_instances(renderable: &dyn Renderable) -> Vec<InstanceRaw> {
+    renderable.get_instances().expect("Batched renderable has no instances")
+}
+
+// the instance buffers of batches with several members, kept between frames and rewritten in place.
+// Every batch drawn in a frame gets a buffer of its own, the writes only land when the frame is submitted
+#[derive(Default)]
+pub struct BatchBuffers {
+    buffers: Mutex<HashMap<BatchKey, Vec<wgpu::Buffer>>>,
+    // the buffers of each key handed out in the current frame
+    used: Mutex<HashMap<BatchKey, usize>>,
+}
+
+// the keys hash by object identity, which the interior mutability of wgpu objects does not affect
+#[allow(clippy::mutable_key_type)]
+impl BatchBuffers {
+    fn write(&self, render_context: &RenderContext, key: &BatchKey, instances: &[InstanceRaw]) -> wgpu::Buffer {
+        let mut used = self.used.lock().unwrap();
+        let index = used.entry(key.clone()).or_insert(0);
+        let mut buffers = self.buffers.lock().unwrap();
+        let buffers = buffers.entry(key.clone()).or_default();
+        let contents: &[u8] = bytemuck::cast_slice(instances);
+        if buffers.get(*index).is_none_or(|buffer| buffer.size() < contents.len() as wgpu::BufferAddress) {
+            // grown to the next power of two, like InstanceBuffer
+            let buffer = render_context.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Batch Instance Buffer"),
+                size: (contents.len() as wgpu::BufferAddress).next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            if *index < buffers.len() {
+                buffers[*index] = buffer;
+            } else {
+                buffers.push(buffer);
+            }
+        }
+        let buffer = buffers[*index].clone();
+        render_context.queue.write_buffer(&buffer, 0, contents);
+        *index += 1;
+        buffer
+    }
+
+    /// Drops the buffers that were not used in the frame, called after it was submitted.
+    pub fn end_frame(&self) {
+        let mut used = self.used.lock().unwrap();
+        let mut buffers = self.buffers.lock().unwrap();
+        buffers.retain(|key, buffers| {
+            buffers.truncate(used.get(key).copied().unwrap_or(0));
+            !buffers.is_empty()
+        });
+        used.clear();
+    }
+}
+
+#[derive(Default)]
+struct BoundState {
+    pipeline_type: Option<TypeId>,
+    bind_groups: Vec<Option<wgpu::BindGroup>>,
+    vertex_buffer: Option<wgpu::Buffer>,
+    instance_buffer: Option<wgpu::Buffer>,
+    index_buffer: Option<wgpu::Buffer>,
+}
+
+// the keys hash by object identity, which the interior mutability of wgpu objects does not affect
+#[allow(clippy::mutable_key_type)]
+fn create_batches(renderables: &mut [&mut dyn Renderable], render_context: &RenderContext, keep_order: bool) -> Vec<Batch> {
+    let mut batches: Vec<Batch> = Vec::new();
+    let mut batch_indices: HashMap<BatchKey, usize> = HashMap::new();
+    for i in 0..renderables.len() {
+        let renderable = &mut renderables[i];
+        if renderable.get_num_instances() == 0 {
+            continue;
+        }
+        if !renderable.is_batched() {
+            batches.push(Batch {
+                key: None,
+                members: vec![i],
+                instances: Vec::new(),
+            });
+            continue;
+        }
+        let key = BatchKey {
+            pipeline_type: renderable.choose_pipeline(),
+            vertex_buffer: (*renderable.get_vertex_buffer(render_context)).clone(),
+            index_buffer: renderable.get_index_buffer(render_context).map(|buffer| (*buffer).clone()),
+            index_format: renderable.get_index_format(),
+            num_indices: renderable.get_num_indices(),
+            num_vertices: renderable.get_num_vertices(),
+            bind_groups: renderable
+                .get_bind_groups(render_context)
+                .into_iter()
+                .cloned()
+                .collect(),
+        };
+        // sorted renderables may only merge with their direct neighbour, otherwise the order would break
+        let existing = if keep_order {
+            batches
+                .last()
+                .filter(|batch| batch.key.as_ref() == Some(&key))
+                .map(|_| batches.len() - 1)
+        } else {
+            batch_indices.get(&key).copied()
+        };
+        match existing {
+            Some(batch_index) => {
+                let batch = &mut batches[batch_index];
+                if batch.members.len() == 1 {
+                    batch.instances = get_instances(&*renderables[batch.members[0]]);
+                }
+                batch.members.push(i);
+                batch.instances.extend(get_instances(&*renderables[i]));
+            }
+            None => {
+                batch_indices.insert(key.clone(), batches.len());
+                batches.push(Batch {
+                    key: Some(key),
+                    members: vec![i],
+                    instances: Vec::new(),
+                });
+            }
+        }
+    }
+    batches
+}
+
+// returns whether the binding changed
+fn update_binding<T: PartialEq + Clone>(bound: &mut Option<T>, value: &T, stats: &mut BatchStats) -> bool {
+    if bound.as_ref() == Some(value) {
+        stats.skipped_state_changes += 1;
+        false
+    } else {
+        *bound = Some(value.clone());
+        true
+    }
+}
+
+/// Draws the renderables of one render pass, merging the ones that share pipeline, buffers and bind groups.
+/// With keep_order only neighbouring renderables are merged, for passes that depend on the draw order.
+pub fn draw_batched(
+    render_pass: &mut wgpu::RenderPass,
+    renderables: &mut [&mut dyn Renderable],
+    render_context: &RenderContext,
+    keep_order: bool,
+) -> BatchStats {
+    let batches = create_batches(renderables, render_context, keep_order);
+    let mut stats = BatchStats {
+        renderables: batches.iter().map(|batch| batch.members.len() as u32).sum(),
+        draws: batches.len() as u32,
+        skipped_state_changes: 0,
+    };
+    let mut bound = BoundState::default();
+    for batch in batches {
+        let Some(key) = batch.key else {
+            renderables[batch.members[0]].render(render_pass, render_context);
+            // the renderable bound its own state
+            bound = BoundState::default();
+            continue;
+        };
+        // a single renderable already has its instances on the gpu
+        let (instance_buffer, num_instances) = if batch.members.len() == 1 {
+            let renderable = &mut renderables[batch.members[0]];
+            let instance_buffer = renderable.get_instance_buffer(render_context).expect("Batched renderable has no instance buffer");
+            ((*instance_buffer).clone(), renderable.get_num_instances())
+        } else {
+            // written every frame, the members may have moved
+            let instance_buffer = render_context.batch_buffers.write(render_context, &key, &batch.instances);
+            (instance_buffer, batch.instances.len() as u32)
+        };
+        if update_binding(&mut bound.pipeline_type, &key.pipeline_type, &mut stats) {
+            let pipeline = get_pipeline_from_cache(key.pipeline_type, render_context);
+            render_pass.set_pipeline(&unpack_pipeline(&pipeline).pipeline);
+            // a new pipeline may have a different layout
+            bound.bind_groups.clear();
+        }
+        if bound.bind_groups.len() < key.bind_groups.len() {
+            bound.bind_groups.resize(key.bind_groups.len(), None);
+        }
+        for (i, bind_group) in key.bind_groups.iter().enumerate() {
+            if update_binding(&mut bound.bind_groups[i], bind_group, &mut stats) {
+                render_pass.set_bind_group(i as u32, bind_group, &[]);
+            }
+        }
+        if update_binding(&mut bound.vertex_buffer, &key.vertex_buffer, &mut stats) {
+            render_pass.set_vertex_buffer(0, key.vertex_buffer.slice(..));
+        }
+        if update_binding(&mut bound.instance_buffer, &instance_buffer, &mut stats) {
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        }
+        match key.index_format.to_wgpu() {
+            Some(index_format) => {
+                let index_buffer = key.index_buffer.as_ref().expect("Indexed renderable has no index buffer");
+                if update_binding(&mut bound.index_buffer, index_buffer, &mut stats) {
+                    render_pass.set_index_buffer(index_buffer.slice(..), index_format);
+                }
+                render_pass.draw_indexed(0..key.num_indices, 0, 0..num_instances);
+            }
+            None => render_pass.draw(0..key.num_vertices, 0..num_instances),
+        }
+    }
+    stats
+}
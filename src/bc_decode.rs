@@ -0,0 +1,570 @@
+// cpu decoders for the bc formats, for devices that cannot sample them. Blocks are 4x4 texels,
+// bc1 and bc4 take 8 bytes and the others 16
+
+// the format the texels of a bc format are decoded to, None for the formats that are not bc
+pub fn decoded_format(format: wgpu::TextureFormat) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat::*;
+    match format {
+        Bc1RgbaUnorm | Bc2RgbaUnorm | Bc3RgbaUnorm | Bc4RUnorm | Bc5RgUnorm | Bc7RgbaUnorm => Some(Rgba8Unorm),
+        Bc1RgbaUnormSrgb | Bc2RgbaUnormSrgb | Bc3RgbaUnormSrgb | Bc7RgbaUnormSrgb => Some(Rgba8UnormSrgb),
+        Bc4RSnorm | Bc5RgSnorm => Some(Rgba8Snorm),
+        Bc6hRgbUfloat | Bc6hRgbFloat => Some(Rgba16Float),
+        _ => None,
+    }
+}
+
+/// Decodes an image of the bc format into decoded_format(format). The width and height are the ones of the image,
+/// the data covers whole blocks.
+pub fn decode_bc(format: wgpu::TextureFormat, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+    use wgpu::TextureFormat::*;
+    let texel_size = decoded_format(format)
+        .unwrap_or_else(|| panic!("{:?} is not a bc format", format))
+        .block_copy_size(None)
+        .unwrap() as usize;
+    let block_size = format.block_copy_size(None).unwrap() as usize;
+    let blocks_wide = width.div_ceil(4) as usize;
+    let blocks_high = height.div_ceil(4) as usize;
+    assert!(
+        data.len() >= blocks_wide * blocks_high * block_size,
+        "{} bytes are not enough for a {}x{} {:?} image",
+        data.len(), width, height, format
+    );
+    let mut texels = vec![0u8; width as usize * height as usize * texel_size];
+    for block_y in 0..blocks_high {
+        for block_x in 0..blocks_wide {
+            let offset = (block_y * blocks_wide + block_x) * block_size;
+            let block = &data[offset..offset + block_size];
+            // every texel of the block, row by row
+            let decoded: [Vec<u8>; 16] = match format {
+                Bc1RgbaUnorm | Bc1RgbaUnormSrgb => decode_bc1(block, true).map(|texel| texel.to_vec()),
+                Bc2RgbaUnorm | Bc2RgbaUnormSrgb => decode_bc2(block).map(|texel| texel.to_vec()),
+                Bc3RgbaUnorm | Bc3RgbaUnormSrgb => decode_bc3(block).map(|texel| texel.to_vec()),
+                Bc4RUnorm => decode_bc4(block, false).map(|r| vec![r as u8, 0, 0, 255]),
+                Bc4RSnorm => decode_bc4(block, true).map(|r| vec![r as i8 as u8, 0, 0, 127]),
+                Bc5RgUnorm => {
+                    let (r, g) = (decode_bc4(&block[..8], false), decode_bc4(&block[8..], false));
+                    std::array::from_fn(|i| vec![r[i] as u8, g[i] as u8, 0, 255])
+                }
+                Bc5RgSnorm => {
+                    let (r, g) = (decode_bc4(&block[..8], true), decode_bc4(&block[8..], true));
+                    std::array::from_fn(|i| vec![r[i] as i8 as u8, g[i] as i8 as u8, 0, 127])
+                }
+                Bc6hRgbUfloat => decode_bc6h(block, false).map(|texel| texel.iter().flat_map(|c| c.to_le_bytes()).collect()),
+                Bc6hRgbFloat => decode_bc6h(block, true).map(|texel| texel.iter().flat_map(|c| c.to_le_bytes()).collect()),
+                Bc7RgbaUnorm | Bc7RgbaUnormSrgb => decode_bc7(block).map(|texel| texel.to_vec()),
+                _ => unreachable!(),
+            };
+            for (i, texel) in decoded.iter().enumerate() {
+                let x = block_x * 4 + i % 4;
+                let y = block_y * 4 + i / 4;
+                // blocks at the right and bottom edge can reach past the image
+                if x < width as usize && y < height as usize {
+                    let start = (y * width as usize + x) * texel_size;
+                    texels[start..start + texel_size].copy_from_slice(texel);
+                }
+            }
+        }
+    }
+    texels
+}
+
+// reads the bits of a block from the lowest one up
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+    fn read(&mut self, count: usize) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let bit = (self.data[(self.position + i) / 8] >> ((self.position + i) % 8)) & 1;
+            value |= (bit as u32) << i;
+        }
+        self.position += count;
+        value
+    }
+}
+
+fn expand_565(color: u16) -> [u32; 3] {
+    let r = (color >> 11) as u32 & 31;
+    let g = (color >> 5) as u32 & 63;
+    let b = color as u32 & 31;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+// with alpha the block switches to three colors and transparent black when the first color is not the larger one,
+// bc2 and bc3 always use four colors
+fn decode_bc1(block: &[u8], alpha: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (expand_565(c0), expand_565(c1));
+    let mut palette = [[0u8; 4]; 4];
+    palette[0] = [e0[0] as u8, e0[1] as u8, e0[2] as u8, 255];
+    palette[1] = [e1[0] as u8, e1[1] as u8, e1[2] as u8, 255];
+    if c0 > c1 || !alpha {
+        for channel in 0..3 {
+            palette[2][channel] = ((2 * e0[channel] + e1[channel]) / 3) as u8;
+            palette[3][channel] = ((e0[channel] + 2 * e1[channel]) / 3) as u8;
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for channel in 0..3 {
+            palette[2][channel] = ((e0[channel] + e1[channel]) / 2) as u8;
+        }
+        palette[2][3] = 255;
+        palette[3] = [0, 0, 0, 0];
+    }
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[(indices >> (2 * i)) as usize & 3])
+}
+
+fn decode_bc2(block: &[u8]) -> [[u8; 4]; 16] {
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    let mut texels = decode_bc1(&block[8..], false);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (4 * i)) & 15) as u8 * 17;
+    }
+    texels
+}
+
+fn decode_bc3(block: &[u8]) -> [[u8; 4]; 16] {
+    let alpha = decode_bc4(&block[..8], false);
+    let mut texels = decode_bc1(&block[8..], false);
+    for (texel, alpha) in texels.iter_mut().zip(alpha) {
+        texel[3] = alpha as u8;
+    }
+    texels
+}
+
+// one channel with two endpoints and six values between them, or four between them and the extremes.
+// Signed blocks return values from -127 to 127
+fn decode_bc4(block: &[u8], signed: bool) -> [i32; 16] {
+    let (e0, e1, min, max) = if signed {
+        ((block[0] as i8 as i32).max(-127), (block[1] as i8 as i32).max(-127), -127, 127)
+    } else {
+        (block[0] as i32, block[1] as i32, 0, 255)
+    };
+    let mut palette = [e0, e1, 0, 0, 0, 0, 0, 0];
+    if e0 > e1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as i32) * e0 + i as i32 * e1 + 3) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as i32) * e0 + i as i32 * e1 + 2) / 5;
+        }
+        palette[6] = min;
+        palette[7] = max;
+    }
+    let indices = u64::from_le_bytes([block[2], block[3], block[4], block[5], block[6], block[7], 0, 0]);
+    std::array::from_fn(|i| palette[((indices >> (3 * i)) & 7) as usize])
+}
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weights(index_bits: usize) -> &'static [u32] {
+    match index_bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+// the subset of every texel for the 64 partitions of two subsets, as one bit per texel
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce, 0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+#[rustfmt::skip]
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0,0,1,1,0,0,1,1,0,2,2,1,2,2,2,2], [0,0,0,1,0,0,1,1,2,2,1,1,2,2,2,1], [0,0,0,0,2,0,0,1,2,2,1,1,2,2,1,1], [0,2,2,2,0,0,2,2,0,0,1,1,0,1,1,1],
+    [0,0,0,0,0,0,0,0,1,1,2,2,1,1,2,2], [0,0,1,1,0,0,1,1,0,0,2,2,0,0,2,2], [0,0,2,2,0,0,2,2,1,1,1,1,1,1,1,1], [0,0,1,1,0,0,1,1,2,2,1,1,2,2,1,1],
+    [0,0,0,0,0,0,0,0,1,1,1,1,2,2,2,2], [0,0,0,0,1,1,1,1,1,1,1,1,2,2,2,2], [0,0,0,0,1,1,1,1,2,2,2,2,2,2,2,2], [0,0,1,2,0,0,1,2,0,0,1,2,0,0,1,2],
+    [0,1,1,2,0,1,1,2,0,1,1,2,0,1,1,2], [0,1,2,2,0,1,2,2,0,1,2,2,0,1,2,2], [0,0,1,1,0,1,1,2,1,1,2,2,1,2,2,2], [0,0,1,1,2,0,0,1,2,2,0,0,2,2,2,0],
+    [0,0,0,1,0,0,1,1,0,1,1,2,1,1,2,2], [0,1,1,1,0,0,1,1,2,0,0,1,2,2,0,0], [0,0,0,0,1,1,2,2,1,1,2,2,1,1,2,2], [0,0,2,2,0,0,2,2,0,0,2,2,1,1,1,1],
+    [0,1,1,1,0,1,1,1,0,2,2,2,0,2,2,2], [0,0,0,1,0,0,0,1,2,2,2,1,2,2,2,1], [0,0,0,0,0,0,1,1,0,1,2,2,0,1,2,2], [0,0,0,0,1,1,0,0,2,2,1,0,2,2,1,0],
+    [0,1,2,2,0,1,2,2,0,0,1,1,0,0,0,0], [0,0,1,2,0,0,1,2,1,1,2,2,2,2,2,2], [0,1,1,0,1,2,2,1,1,2,2,1,0,1,1,0], [0,0,0,0,0,1,1,0,1,2,2,1,1,2,2,1],
+    [0,0,2,2,1,1,0,2,1,1,0,2,0,0,2,2], [0,1,1,0,0,1,1,0,2,0,0,2,2,2,2,2], [0,0,1,1,0,1,2,2,0,1,2,2,0,0,1,1], [0,0,0,0,2,0,0,0,2,2,1,1,2,2,2,1],
+    [0,0,0,0,0,0,0,2,1,1,2,2,1,2,2,2], [0,2,2,2,0,0,2,2,0,0,1,2,0,0,1,1], [0,0,1,1,0,0,1,2,0,0,2,2,0,2,2,2], [0,1,2,0,0,1,2,0,0,1,2,0,0,1,2,0],
+    [0,0,0,0,1,1,1,1,2,2,2,2,0,0,0,0], [0,1,2,0,1,2,0,1,2,0,1,2,0,1,2,0], [0,1,2,0,2,0,1,2,1,2,0,1,0,1,2,0], [0,0,1,1,2,2,0,0,1,1,2,2,0,0,1,1],
+    [0,0,1,1,1,1,2,2,2,2,0,0,0,0,1,1], [0,1,0,1,0,1,0,1,2,2,2,2,2,2,2,2], [0,0,0,0,0,0,0,0,2,1,2,1,2,1,2,1], [0,0,2,2,1,1,2,2,0,0,2,2,1,1,2,2],
+    [0,0,2,2,0,0,1,1,0,0,2,2,0,0,1,1], [0,2,2,0,1,2,2,1,0,2,2,0,1,2,2,1], [0,1,0,1,2,2,2,2,2,2,2,2,0,1,0,1], [0,0,0,0,2,1,2,1,2,1,2,1,2,1,2,1],
+    [0,1,0,1,0,1,0,1,0,1,0,1,2,2,2,2], [0,2,2,2,0,1,1,1,0,2,2,2,0,1,1,1], [0,0,0,2,1,1,1,2,0,0,0,2,1,1,1,2], [0,0,0,0,2,1,1,2,2,1,1,2,2,1,1,2],
+    [0,2,2,2,0,1,1,1,0,1,1,1,0,2,2,2], [0,0,0,2,1,1,1,2,1,1,1,2,0,0,0,2], [0,1,1,0,0,1,1,0,0,1,1,0,2,2,2,2], [0,0,0,0,0,0,0,0,2,1,1,2,2,1,1,2],
+    [0,1,1,0,0,1,1,0,2,2,2,2,2,2,2,2], [0,0,2,2,0,0,1,1,0,0,1,1,0,0,2,2], [0,0,2,2,1,1,2,2,1,1,2,2,0,0,2,2], [0,0,0,0,0,0,0,0,0,0,0,0,2,1,1,2],
+    [0,0,0,2,0,0,0,1,0,0,0,2,0,0,0,1], [0,2,2,2,1,2,2,2,0,2,2,2,1,2,2,2], [0,1,0,1,2,2,2,2,2,2,2,2,2,2,2,2], [0,1,1,1,2,0,1,1,2,2,0,1,2,2,2,0],
+];
+
+// the texel of the second subset whose index has one bit less
+#[rustfmt::skip]
+const ANCHORS_2: [usize; 64] = [
+    15,15,15,15,15,15,15,15, 15,15,15,15,15,15,15,15, 15, 2, 8, 2, 2, 8, 8,15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15,15, 6, 8, 2, 8,15,15, 2, 8, 2, 2, 2,15,15, 6, 6, 2, 6, 8,15,15, 2, 2, 15,15,15,15,15, 2, 2,15,
+];
+
+// the anchors of the second and third subset
+#[rustfmt::skip]
+const ANCHORS_3: [[usize; 64]; 2] = [
+    [
+        3, 3,15,15, 8, 3,15,15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8,15, 3, 3, 6,10, 5, 8, 8, 6, 8, 5,15,15,
+        8,15, 3, 5, 6,10, 8,15,15, 3,15, 5,15,15,15,15, 3,15, 5, 5, 5, 8, 5,10, 5,10, 8,13,15,12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3,15,15, 3, 8,15,15,15,15,15,15,15, 8,15, 8,15, 3,15, 8,15, 8, 3,15, 6,10,15,15,10, 8,
+        15, 3,15,10,10, 8, 9,10, 6,15, 8,15, 3, 6, 6, 8,15, 3,15,15,15,15,15,15,15,15,15,15, 3,15,15, 8,
+    ],
+];
+
+fn subset(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => ((PARTITIONS_2[partition] >> texel) & 1) as usize,
+        _ => PARTITIONS_3[partition][texel] as usize,
+    }
+}
+
+fn is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    texel == 0
+        || match subsets {
+            1 => false,
+            2 => texel == ANCHORS_2[partition],
+            _ => texel == ANCHORS_3[0][partition] || texel == ANCHORS_3[1][partition],
+        }
+}
+
+fn read_indices(reader: &mut BitReader, subsets: usize, partition: usize, index_bits: usize) -> [usize; 16] {
+    std::array::from_fn(|texel| {
+        let bits = if is_anchor(subsets, partition, texel) { index_bits - 1 } else { index_bits };
+        reader.read(bits) as usize
+    })
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    index_selection_bits: usize,
+    color_bits: usize,
+    alpha_bits: usize,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: usize,
+    secondary_index_bits: usize,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u32 {
+    ((64 - weight) * e0 + weight * e1 + 32) >> 6
+}
+
+fn decode_bc7(block: &[u8]) -> [[u8; 4]; 16] {
+    // the mode is the number of zeros before the first set bit
+    let Some(mode_index) = (0..8).find(|bit| block[0] & (1 << bit) != 0) else {
+        // reserved, decodes to transparent black
+        return [[0; 4]; 16];
+    };
+    let mode = &BC7_MODES[mode_index];
+    let mut reader = BitReader::new(block);
+    reader.read(mode_index + 1);
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    // endpoints[subset * 2 + endpoint][channel]
+    let mut endpoints = [[0u32; 4]; 6];
+    let endpoint_count = mode.subsets * 2;
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        endpoint[3] = if mode.alpha_bits > 0 { reader.read(mode.alpha_bits) } else { 255 };
+    }
+    let (mut color_bits, mut alpha_bits) = (mode.color_bits, mode.alpha_bits);
+    if mode.endpoint_p_bits || mode.shared_p_bits {
+        let p_bits: Vec<u32> = if mode.endpoint_p_bits {
+            (0..endpoint_count).map(|_| reader.read(1)).collect()
+        } else {
+            (0..mode.subsets).flat_map(|_| {
+                let p_bit = reader.read(1);
+                [p_bit, p_bit]
+            }).collect()
+        };
+        for (endpoint, p_bit) in endpoints.iter_mut().zip(p_bits) {
+            for value in &mut endpoint[..3] {
+                *value = (*value << 1) | p_bit;
+            }
+            if mode.alpha_bits > 0 {
+                endpoint[3] = (endpoint[3] << 1) | p_bit;
+            }
+        }
+        color_bits += 1;
+        if mode.alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+    let expand = |value: u32, bits: usize| (value << (8 - bits)) | (value >> (2 * bits - 8));
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in &mut endpoint[..3] {
+            *value = expand(*value, color_bits);
+        }
+        if alpha_bits > 0 {
+            endpoint[3] = expand(endpoint[3], alpha_bits);
+        }
+    }
+
+    let indices = read_indices(&mut reader, mode.subsets, partition, mode.index_bits);
+    let secondary_indices = if mode.secondary_index_bits > 0 {
+        Some(read_indices(&mut reader, 1, 0, mode.secondary_index_bits))
+    } else {
+        None
+    };
+
+    std::array::from_fn(|texel| {
+        let subset = subset(mode.subsets, partition, texel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        // the color and the alpha can have their own indices, the selection bit swaps which one they use
+        let (color_index, color_weights, alpha_index, alpha_weights) = match secondary_indices {
+            Some(secondary) if index_selection == 1 => {
+                (secondary[texel], weights(mode.secondary_index_bits), indices[texel], weights(mode.index_bits))
+            }
+            Some(secondary) => (indices[texel], weights(mode.index_bits), secondary[texel], weights(mode.secondary_index_bits)),
+            None => (indices[texel], weights(mode.index_bits), indices[texel], weights(mode.index_bits)),
+        };
+        let mut texel = [0u8; 4];
+        for channel in 0..3 {
+            texel[channel] = interpolate(e0[channel], e1[channel], color_weights[color_index]) as u8;
+        }
+        texel[3] = interpolate(e0[3], e1[3], alpha_weights[alpha_index]) as u8;
+        // the rotation swaps alpha with one of the colors
+        if rotation > 0 {
+            texel.swap(3, rotation as usize - 1);
+        }
+        texel
+    })
+}
+
+// a field of a bc6h mode: the endpoint value it belongs to, and the bits of it in the order they are stored
+#[derive(Clone, Copy)]
+enum Field {
+    // the endpoint value, its first bit and the number of bits, lowest first
+    Bits(usize, u32, u32),
+    // the bits from the first one down to the last one, stored highest first
+    Reversed(usize, u32, u32),
+}
+
+// the endpoint values r0 g0 b0 r1 g1 b1 r2 g2 b2 r3 g3 b3
+const R0: usize = 0;
+const G0: usize = 1;
+const B0: usize = 2;
+const R1: usize = 3;
+const G1: usize = 4;
+const B1: usize = 5;
+const R2: usize = 6;
+const G2: usize = 7;
+const B2: usize = 8;
+const R3: usize = 9;
+const G3: usize = 10;
+const B3: usize = 11;
+
+struct Bc6hMode {
+    // two regions with 3 bit indices, or one with 4 bit indices
+    two_regions: bool,
+    // the other endpoints are stored as differences to the first one
+    transformed: bool,
+    // bits of the first endpoint and of the others, for red, green and blue
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    fields: &'static [Field],
+}
+
+use Field::{Bits as B, Reversed as Rev};
+
+#[rustfmt::skip]
+const BC6H_MODES: [(u32, Bc6hMode); 14] = [
+    (0b00, Bc6hMode { two_regions: true, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], fields: &[
+        B(G2, 4, 1), B(B2, 4, 1), B(B3, 4, 1), B(R0, 0, 10), B(G0, 0, 10), B(B0, 0, 10), B(R1, 0, 5), B(G3, 4, 1), B(G2, 0, 4),
+        B(G1, 0, 5), B(B3, 0, 1), B(G3, 0, 4), B(B1, 0, 5), B(B3, 1, 1), B(B2, 0, 4), B(R2, 0, 5), B(B3, 2, 1), B(R3, 0, 5), B(B3, 3, 1),
+    ] }),
+    (0b01, Bc6hMode { two_regions: true, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], fields: &[
+        B(G2, 5, 1), B(G3, 4, 1), B(G3, 5, 1), B(R0, 0, 7), B(B3, 0, 1), B(B3, 1, 1), B(B2, 4, 1), B(G0, 0, 7), B(B2, 5, 1), B(B3, 2, 1),
+        B(G2, 4, 1), B(B0, 0, 7), B(B3, 3, 1), B(B3, 5, 1), B(B3, 4, 1), B(R1, 0, 6), B(G2, 0, 4), B(G1, 0, 6), B(G3, 0, 4), B(B1, 0, 6),
+        B(B2, 0, 4), B(R2, 0, 6), B(R3, 0, 6),
+    ] }),
+    (0b00010, Bc6hMode { two_regions: true, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], fields: &[
+        B(R0, 0, 10), B(G0, 0, 10), B(B0, 0, 10), B(R1, 0, 5), B(R0, 10, 1), B(G2, 0, 4), B(G1, 0, 4), B(G0, 10, 1), B(B3, 0, 1),
+        B(G3, 0, 4), B(B1, 0, 4), B(B0, 10, 1), B(B3, 1, 1), B(B2, 0, 4), B(R2, 0, 5), B(B3, 2, 1), B(R3, 0, 5), B(B3, 3, 1),
+    ] }),
+    (0b00110, Bc6hMode { two_regions: true, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], fields: &[
+        B(R0, 0, 10), B(G0, 0, 10), B(B0, 0, 10), B(R1, 0, 4), B(R0, 10, 1), B(G3, 4, 1), B(G2, 0, 4), B(G1, 0, 5), B(G0, 10, 1),
+        B(G3, 0, 4), B(B1, 0, 4), B(B0, 10, 1), B(B3, 1, 1), B(B2, 0, 4), B(R2, 0, 4), B(B3, 0, 1), B(B3, 2, 1), B(R3, 0, 4),
+        B(G2, 4, 1), B(B3, 3, 1),
+    ] }),
+    (0b01010, Bc6hMode { two_regions: true, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], fields: &[
+        B(R0, 0, 10), B(G0, 0, 10), B(B0, 0, 10), B(R1, 0, 4), B(R0, 10, 1), B(B2, 4, 1), B(G2, 0, 4), B(G1, 0, 4), B(G0, 10, 1),
+        B(B3, 0, 1), B(G3, 0, 4), B(B1, 0, 5), B(B0, 10, 1), B(B2, 0, 4), B(R2, 0, 4), B(B3, 1, 1), B(B3, 2, 1), B(R3, 0, 4),
+        B(B3, 4, 1), B(B3, 3, 1),
+    ] }),
+    (0b01110, Bc6hMode { two_regions: true, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], fields: &[
+        B(R0, 0, 9), B(B2, 4, 1), B(G0, 0, 9), B(G2, 4, 1), B(B0, 0, 9), B(B3, 4, 1), B(R1, 0, 5), B(G3, 4, 1), B(G2, 0, 4),
+        B(G1, 0, 5), B(B3, 0, 1), B(G3, 0, 4), B(B1, 0, 5), B(B3, 1, 1), B(B2, 0, 4), B(R2, 0, 5), B(B3, 2, 1), B(R3, 0, 5), B(B3, 3, 1),
+    ] }),
+    (0b10010, Bc6hMode { two_regions: true, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], fields: &[
+        B(R0, 0, 8), B(G3, 4, 1), B(B2, 4, 1), B(G0, 0, 8), B(B3, 2, 1), B(G2, 4, 1), B(B0, 0, 8), B(B3, 3, 1), B(B3, 4, 1),
+        B(R1, 0, 6), B(G2, 0, 4), B(G1, 0, 5), B(B3, 0, 1), B(G3, 0, 4), B(B1, 0, 5), B(B3, 1, 1), B(B2, 0, 4), B(R2, 0, 6), B(R3, 0, 6),
+    ] }),
+    (0b10110, Bc6hMode { two_regions: true, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], fields: &[
+        B(R0, 0, 8), B(B3, 0, 1), B(B2, 4, 1), B(G0, 0, 8), B(G2, 5, 1), B(G2, 4, 1), B(B0, 0, 8), B(G3, 5, 1), B(B3, 4, 1),
+        B(R1, 0, 5), B(G3, 4, 1), B(G2, 0, 4), B(G1, 0, 6), B(G3, 0, 4), B(B1, 0, 5), B(B3, 1, 1), B(B2, 0, 4), B(R2, 0, 5),
+        B(B3, 2, 1), B(R3, 0, 5), B(B3, 3, 1),
+    ] }),
+    (0b11010, Bc6hMode { two_regions: true, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], fields: &[
+        B(R0, 0, 8), B(B3, 1, 1), B(B2, 4, 1), B(G0, 0, 8), B(B2, 5, 1), B(G2, 4, 1), B(B0, 0, 8), B(B3, 5, 1), B(B3, 4, 1),
+        B(R1, 0, 5), B(G3, 4, 1), B(G2, 0, 4), B(G1, 0, 5), B(B3, 0, 1), B(G3, 0, 4), B(B1, 0, 6), B(B2, 0, 4), B(R2, 0, 5),
+        B(B3, 2, 1), B(R3, 0, 5), B(B3, 3, 1),
+    ] }),
+    (0b11110, Bc6hMode { two_regions: true, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], fields: &[
+        B(R0, 0, 6), B(G3, 4, 1), B(B3, 0, 1), B(B3, 1, 1), B(B2, 4, 1), B(G0, 0, 6), B(G2, 5, 1), B(B2, 5, 1), B(B3, 2, 1),
+        B(G2, 4, 1), B(B0, 0, 6), B(G3, 5, 1), B(B3, 3, 1), B(B3, 5, 1), B(B3, 4, 1), B(R1, 0, 6), B(G2, 0, 4), B(G1, 0, 6),
+        B(G3, 0, 4), B(B1, 0, 6), B(B2, 0, 4), B(R2, 0, 6), B(R3, 0, 6),
+    ] }),
+    (0b00011, Bc6hMode { two_regions: false, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], fields: &[
+        B(R0, 0, 10), B(G0, 0, 10), B(B0, 0, 10), B(R1, 0, 10), B(G1, 0, 10), B(B1, 0, 10),
+    ] }),
+    (0b00111, Bc6hMode { two_regions: false, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], fields: &[
+        B(R0, 0, 10), B(G0, 0, 10), B(B0, 0, 10), B(R1, 0, 9), B(R0, 10, 1), B(G1, 0, 9), B(G0, 10, 1), B(B1, 0, 9), B(B0, 10, 1),
+    ] }),
+    (0b01011, Bc6hMode { two_regions: false, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], fields: &[
+        B(R0, 0, 10), B(G0, 0, 10), B(B0, 0, 10), B(R1, 0, 8), Rev(R0, 11, 10), B(G1, 0, 8), Rev(G0, 11, 10), B(B1, 0, 8), Rev(B0, 11, 10),
+    ] }),
+    (0b01111, Bc6hMode { two_regions: false, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], fields: &[
+        B(R0, 0, 10), B(G0, 0, 10), B(B0, 0, 10), B(R1, 0, 4), Rev(R0, 15, 10), B(G1, 0, 4), Rev(G0, 15, 10), B(B1, 0, 4), Rev(B0, 15, 10),
+    ] }),
+];
+
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+// scales a quantized endpoint to 16 bits, keeping the extremes
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || value == 0 {
+            value
+        } else if value == (1 << bits) - 1 {
+            0xffff
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else {
+        if bits >= 16 || value == 0 {
+            return value;
+        }
+        let magnitude = value.abs();
+        let unquantized = if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 { -unquantized } else { unquantized }
+    }
+}
+
+// scales an interpolated value to the largest finite half float, as the bits of one
+fn finish_unquantize(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        0x8000 | (((-value) * 31) >> 5) as u16
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+// rgb half floats, alpha is always one
+fn decode_bc6h(block: &[u8], signed: bool) -> [[u16; 4]; 16] {
+    const ONE: u16 = 0x3c00;
+    let mut reader = BitReader::new(block);
+    let mut mode_bits = reader.read(2);
+    if mode_bits > 1 {
+        mode_bits |= reader.read(3) << 2;
+    }
+    let Some((_, mode)) = BC6H_MODES.iter().find(|(bits, _)| *bits == mode_bits) else {
+        // reserved modes decode to black
+        return [[0, 0, 0, ONE]; 16];
+    };
+    let mut values = [0u32; 12];
+    for field in mode.fields {
+        match *field {
+            B(value, first, count) => values[value] |= reader.read(count as usize) << first,
+            Rev(value, first, last) => {
+                for bit in (last..=first).rev() {
+                    values[value] |= reader.read(1) << bit;
+                }
+            }
+        }
+    }
+    let partition = if mode.two_regions { reader.read(5) as usize } else { 0 };
+    let subsets = if mode.two_regions { 2 } else { 1 };
+    let endpoint_count = subsets * 2;
+
+    // the endpoints as signed values of endpoint_bits bits, per endpoint and channel
+    let mut endpoints = [[0i32; 3]; 4];
+    for channel in 0..3 {
+        let base = values[channel];
+        endpoints[0][channel] = if signed { sign_extend(base, mode.endpoint_bits) } else { base as i32 };
+        for (endpoint, values) in endpoints.iter_mut().zip(values.chunks_exact(3)).skip(1).take(endpoint_count - 1) {
+            endpoint[channel] = if mode.transformed {
+                let delta = sign_extend(values[channel], mode.delta_bits[channel]);
+                let sum = (base as i32 + delta) as u32 & ((1 << mode.endpoint_bits) - 1);
+                if signed { sign_extend(sum, mode.endpoint_bits) } else { sum as i32 }
+            } else if signed {
+                sign_extend(values[channel], mode.endpoint_bits)
+            } else {
+                values[channel] as i32
+            };
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut() {
+            *value = unquantize(*value, mode.endpoint_bits, signed);
+        }
+    }
+
+    let index_bits = if mode.two_regions { 3 } else { 4 };
+    let indices = read_indices(&mut reader, subsets, partition, index_bits);
+    std::array::from_fn(|texel| {
+        let subset = subset(subsets, partition, texel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        let weight = weights(index_bits)[indices[texel]] as i32;
+        let mut texel = [0, 0, 0, ONE];
+        for channel in 0..3 {
+            let value = ((64 - weight) * e0[channel] + weight * e1[channel] + 32) >> 6;
+            texel[channel] = finish_unquantize(value, signed);
+        }
+        texel
+    })
+}
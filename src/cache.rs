@@ -1,6 +1,5 @@
-use std::{any::TypeId, sync::Arc};
+use std::{any::{Any, TypeId}, collections::HashMap, sync::{Arc, Mutex}};
 
-use lazy_static::lazy_static;
 use moka::sync::Cache;
 
 use crate::my_pipeline::MyPipeline;
@@ -18,14 +17,29 @@ pub enum CacheValue{
 }
 
 
-lazy_static!{
-    // This is a simple in-memory cache to store the last computed value of a function.
-    // In a real-world application, you might want to use a more sophisticated caching mechanism.
-    pub static ref CACHE: Cache<CacheKey, Arc<CacheValue>> = {
-        // Create a cache with a maximum size of 100 items and an expiration time of 60 seconds.
-        Cache::builder()
-            .max_capacity(100) // Maximum number of items in the cache
-            .time_to_live(std::time::Duration::from_secs(60)) // Time to live for each item in the cache
-            .build()
-    };
-}
\ No newline at end of file
+// the pipelines of a render context, they belong to its device so every context has its own
+pub type PipelineCache = Cache<CacheKey, Arc<CacheValue>>;
+
+pub fn create_pipeline_cache() -> PipelineCache {
+    // Create a cache with a maximum size of 100 items and an expiration time of 60 seconds.
+    Cache::builder()
+        .max_capacity(100) // Maximum number of items in the cache
+        .time_to_live(std::time::Duration::from_secs(60)) // Time to live for each item in the cache
+        .build()
+}
+// objects made for the device of one render context, like pipelines and the bind groups of textures.
+// Every context has its own, so they are freed with it and never used with another device
+#[derive(Default)]
+pub struct ContextResources {
+    resources: Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+}
+
+impl ContextResources {
+    // the resource of a type, made with its default the first time. Resources are told apart by their type,
+    // and each is locked on its own so that one can be made while another one is locked
+    pub fn get<T: Default + Send + 'static>(&self) -> Arc<Mutex<T>> {
+        let mut resources = self.resources.lock().unwrap();
+        let resource = resources.entry(TypeId::of::<T>()).or_insert_with(|| Arc::new(Mutex::new(T::default())));
+        resource.clone().downcast::<Mutex<T>>().unwrap()
+    }
+}
@@ -41,8 +41,7 @@ impl Camera {
         // Calculate the actual up vector (perpendicular to forward and right)
         let up = right.cross(forward).normalize();
         let target = self.pos + forward;
-        let view = cgmath::Matrix4::look_at_rh(self.pos, target, up);
-        view
+        cgmath::Matrix4::look_at_rh(self.pos, target, up)
     }
     pub fn build_projection_matrix(&self, aspect: f32) -> cgmath::Matrix4<f32> {
         cgmath::perspective(cgmath::Deg(self.fovy), aspect, self.znear, self.zfar)
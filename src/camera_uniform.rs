@@ -9,6 +9,8 @@ pub struct CameraUniform {
     // to convert the Matrix4 into a 4x4 f32 array
     view: [[f32; 4]; 4],
     proj: [[f32; 4]; 4],
+    // world space, for the specular highlights of lit pipelines
+    position: [f32; 4],
 }
 
 impl CameraUniform {
@@ -22,6 +24,7 @@ impl CameraUniform {
         Self {
             view: view.into(),
             proj: camera.build_projection_matrix(aspect).into(),
+            position: camera.pos.to_homogeneous().into(),
         }
     }
 }
@@ -0,0 +1,323 @@
+// textures that are stored in a format the gpu samples as it is, read from ktx2 and dds files.
+// These files carry their own mip levels and can hold the six faces of a cube
+
+use std::borrow::Cow;
+
+use image::error::{DecodingError, ImageError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+
+use crate::{
+    bc_decode::{decode_bc, decoded_format},
+    my_texture::{ColorSpace, TextureOptions},
+    render_context::RenderContext,
+};
+
+// the compression features the device is requested with when the adapter has them
+pub const COMPRESSION_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_COMPRESSION_BC
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC);
+
+const ASTC_BLOCKS: [wgpu::AstcBlock; 14] = {
+    use wgpu::AstcBlock::*;
+    [B4x4, B5x4, B5x5, B6x5, B6x6, B8x5, B8x6, B8x8, B10x5, B10x6, B10x8, B10x10, B12x10, B12x12]
+};
+
+pub(crate) fn decoding_error(container: &str, message: String) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormatHint::Name(container.to_string()), message))
+}
+
+fn unsupported_error(container: &str, message: String) -> ImageError {
+    ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+        ImageFormatHint::Name(container.to_string()),
+        UnsupportedErrorKind::GenericFeature(message),
+    ))
+}
+
+// ktx2 files name their formats by the vulkan ones
+fn ktx2_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::TextureFormat::*;
+    Some(match format {
+        K::R8G8B8A8_UNORM => Rgba8Unorm,
+        K::R8G8B8A8_SRGB => Rgba8UnormSrgb,
+        K::B8G8R8A8_UNORM => Bgra8Unorm,
+        K::B8G8R8A8_SRGB => Bgra8UnormSrgb,
+        K::R16G16B16A16_SFLOAT => Rgba16Float,
+        // wgpu has no bc1 without alpha. The blocks of such files can still use the mode with three colors,
+        // its fourth color is opaque black for them: decoded on the cpu it is, see CompressedImage::opaque,
+        // sampled on the gpu it is transparent black
+        K::BC1_RGB_UNORM_BLOCK | K::BC1_RGBA_UNORM_BLOCK => Bc1RgbaUnorm,
+        K::BC1_RGB_SRGB_BLOCK | K::BC1_RGBA_SRGB_BLOCK => Bc1RgbaUnormSrgb,
+        K::BC2_UNORM_BLOCK => Bc2RgbaUnorm,
+        K::BC2_SRGB_BLOCK => Bc2RgbaUnormSrgb,
+        K::BC3_UNORM_BLOCK => Bc3RgbaUnorm,
+        K::BC3_SRGB_BLOCK => Bc3RgbaUnormSrgb,
+        K::BC4_UNORM_BLOCK => Bc4RUnorm,
+        K::BC4_SNORM_BLOCK => Bc4RSnorm,
+        K::BC5_UNORM_BLOCK => Bc5RgUnorm,
+        K::BC5_SNORM_BLOCK => Bc5RgSnorm,
+        K::BC6H_UFLOAT_BLOCK => Bc6hRgbUfloat,
+        K::BC6H_SFLOAT_BLOCK => Bc6hRgbFloat,
+        K::BC7_UNORM_BLOCK => Bc7RgbaUnorm,
+        K::BC7_SRGB_BLOCK => Bc7RgbaUnormSrgb,
+        K::ETC2_R8G8B8_UNORM_BLOCK => Etc2Rgb8Unorm,
+        K::ETC2_R8G8B8_SRGB_BLOCK => Etc2Rgb8UnormSrgb,
+        K::ETC2_R8G8B8A1_UNORM_BLOCK => Etc2Rgb8A1Unorm,
+        K::ETC2_R8G8B8A1_SRGB_BLOCK => Etc2Rgb8A1UnormSrgb,
+        K::ETC2_R8G8B8A8_UNORM_BLOCK => Etc2Rgba8Unorm,
+        K::ETC2_R8G8B8A8_SRGB_BLOCK => Etc2Rgba8UnormSrgb,
+        K::EAC_R11_UNORM_BLOCK => EacR11Unorm,
+        K::EAC_R11_SNORM_BLOCK => EacR11Snorm,
+        K::EAC_R11G11_UNORM_BLOCK => EacRg11Unorm,
+        K::EAC_R11G11_SNORM_BLOCK => EacRg11Snorm,
+        // the ldr astc formats come in pairs of unorm and srgb for every block size, the hdr ones follow each other
+        _ => {
+            let value = format.value();
+            let (index, channel) = match value {
+                157..=184 => (
+                    (value - 157) / 2,
+                    if (value - 157).is_multiple_of(2) { wgpu::AstcChannel::Unorm } else { wgpu::AstcChannel::UnormSrgb },
+                ),
+                1000066000..=1000066013 => (value - 1000066000, wgpu::AstcChannel::Hdr),
+                _ => return None,
+            };
+            Astc { block: ASTC_BLOCKS[index as usize], channel }
+        }
+    })
+}
+
+fn dds_format(format: ddsfile::DxgiFormat) -> Option<wgpu::TextureFormat> {
+    use ddsfile::DxgiFormat as D;
+    use wgpu::TextureFormat::*;
+    Some(match format {
+        D::R8G8B8A8_UNorm => Rgba8Unorm,
+        D::R8G8B8A8_UNorm_sRGB => Rgba8UnormSrgb,
+        D::R8G8B8A8_SNorm => Rgba8Snorm,
+        D::B8G8R8A8_UNorm => Bgra8Unorm,
+        D::B8G8R8A8_UNorm_sRGB => Bgra8UnormSrgb,
+        D::R16G16B16A16_Float => Rgba16Float,
+        D::BC1_UNorm => Bc1RgbaUnorm,
+        D::BC1_UNorm_sRGB => Bc1RgbaUnormSrgb,
+        D::BC2_UNorm => Bc2RgbaUnorm,
+        D::BC2_UNorm_sRGB => Bc2RgbaUnormSrgb,
+        D::BC3_UNorm => Bc3RgbaUnorm,
+        D::BC3_UNorm_sRGB => Bc3RgbaUnormSrgb,
+        D::BC4_UNorm => Bc4RUnorm,
+        D::BC4_SNorm => Bc4RSnorm,
+        D::BC5_UNorm => Bc5RgUnorm,
+        D::BC5_SNorm => Bc5RgSnorm,
+        D::BC6H_UF16 => Bc6hRgbUfloat,
+        D::BC6H_SF16 => Bc6hRgbFloat,
+        D::BC7_UNorm => Bc7RgbaUnorm,
+        D::BC7_UNorm_sRGB => Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+#[derive(Clone)]
+pub struct CompressedImage {
+    pub format: wgpu::TextureFormat,
+    // depth_or_array_layers is 6 for cubes
+    pub size: wgpu::Extent3d,
+    // one entry per mip level, with the level of every layer after each other
+    pub levels: Vec<Vec<u8>>,
+    // the file declares its bc1 blocks without alpha, the cpu decoder makes every texel opaque
+    pub opaque: bool,
+}
+
+impl CompressedImage {
+    // the files are told apart by their extension
+    pub fn is_container(file_path: &str) -> bool {
+        let file_path = file_path.to_lowercase();
+        file_path.ends_with(".ktx2") || file_path.ends_with(".dds")
+    }
+
+    pub fn open(file_path: &str) -> Result<Self, ImageError> {
+        let bytes = std::fs::read(file_path).map_err(ImageError::IoError)?;
+        if file_path.to_lowercase().ends_with(".ktx2") {
+            Self::from_ktx2(&bytes)
+        } else {
+            Self::from_dds(&bytes)
+        }
+    }
+
+    // the size of one layer of a level, rounded up to whole blocks
+    pub fn level_size(&self, level: u32) -> wgpu::Extent3d {
+        wgpu::Extent3d { depth_or_array_layers: 1, ..self.size }
+            .mip_level_size(level, wgpu::TextureDimension::D2)
+            .physical_size(self.format)
+    }
+
+    fn layer_byte_count(&self, level: u32) -> usize {
+        let size = self.level_size(level);
+        let (block_width, block_height) = self.format.block_dimensions();
+        let block_size = self.format.block_copy_size(None).unwrap();
+        ((size.width / block_width) * (size.height / block_height) * block_size) as usize
+    }
+
+    fn check_levels(self, container: &str) -> Result<Self, ImageError> {
+        for (level, data) in self.levels.iter().enumerate() {
+            let expected = self.layer_byte_count(level as u32) * self.size.depth_or_array_layers as usize;
+            if data.len() != expected {
+                return Err(decoding_error(
+                    container,
+                    format!("mip level {} has {} bytes instead of {}", level, data.len(), expected),
+                ));
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self, ImageError> {
+        let reader = ktx2::Reader::new(bytes).map_err(|error| decoding_error("KTX2", error.to_string()))?;
+        let header = reader.header();
+        if let Some(scheme) = header.supercompression_scheme {
+            return Err(unsupported_error("KTX2", format!("supercompression {:?}", scheme)));
+        }
+        if header.pixel_depth > 1 {
+            return Err(unsupported_error("KTX2", "3d textures".to_string()));
+        }
+        let format = header
+            .format
+            .and_then(ktx2_format)
+            .ok_or_else(|| unsupported_error("KTX2", format!("the format {:?}", header.format)))?;
+        // the data of a level holds every face of every layer
+        let layers = header.layer_count.max(1) * header.face_count;
+        let image = Self {
+            format,
+            size: wgpu::Extent3d {
+                width: header.pixel_width,
+                height: header.pixel_height.max(1),
+                depth_or_array_layers: layers,
+            },
+            levels: reader.levels().map(|level| level.data.to_vec()).collect(),
+            opaque: matches!(header.format, Some(ktx2::Format::BC1_RGB_UNORM_BLOCK | ktx2::Format::BC1_RGB_SRGB_BLOCK)),
+        };
+        image.check_levels("KTX2")
+    }
+
+    pub fn from_dds(bytes: &[u8]) -> Result<Self, ImageError> {
+        let dds = ddsfile::Dds::read(bytes).map_err(|error| decoding_error("DDS", error.to_string()))?;
+        if dds.get_depth() > 1 {
+            return Err(unsupported_error("DDS", "3d textures".to_string()));
+        }
+        let format = dds
+            .get_dxgi_format()
+            .and_then(dds_format)
+            .ok_or_else(|| unsupported_error("DDS", format!("the format {:?}", dds.get_dxgi_format().ok_or(dds.get_d3d_format()))))?;
+        // dx10 headers count cubes, the faces are the layers
+        let layers = match &dds.header10 {
+            Some(header10) if header10.misc_flag.contains(ddsfile::MiscFlag::TEXTURECUBE) => header10.array_size * 6,
+            _ => dds.get_num_array_layers(),
+        };
+        let mut image = Self {
+            format,
+            size: wgpu::Extent3d {
+                width: dds.get_width(),
+                height: dds.get_height(),
+                depth_or_array_layers: layers,
+            },
+            levels: vec![Vec::new(); dds.get_num_mipmap_levels().max(1) as usize],
+            opaque: false,
+        };
+        // dds files store every mip level of a layer before the next layer
+        let mut offset = 0;
+        for _ in 0..layers {
+            for level in 0..image.levels.len() {
+                let count = image.layer_byte_count(level as u32);
+                let data = dds
+                    .data
+                    .get(offset..offset + count)
+                    .ok_or_else(|| decoding_error("DDS", format!("the data ends in mip level {}", level)))?;
+                image.levels[level].extend_from_slice(data);
+                offset += count;
+            }
+        }
+        image.check_levels("DDS")
+    }
+
+    /// Decodes a bc image on the cpu, the mip levels and layers stay as they are.
+    pub fn decompress(&self) -> Self {
+        self.decompress_as(self.format)
+    }
+
+    // the format can be the srgb or plain variant of the one of the image
+    fn decompress_as(&self, compressed_format: wgpu::TextureFormat) -> Self {
+        let format = decoded_format(compressed_format).unwrap_or_else(|| panic!("{:?} cannot be decoded on the cpu", compressed_format));
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let size = wgpu::Extent3d { depth_or_array_layers: 1, ..self.size }.mip_level_size(level as u32, wgpu::TextureDimension::D2);
+                let mut texels: Vec<u8> = data
+                    .chunks_exact(self.layer_byte_count(level as u32))
+                    .flat_map(|layer| decode_bc(compressed_format, size.width, size.height, layer))
+                    .collect();
+                if self.opaque {
+                    texels.chunks_exact_mut(4).for_each(|texel| texel[3] = 255);
+                }
+                texels
+            })
+            .collect();
+        Self { format, size: self.size, levels, opaque: self.opaque }
+    }
+
+    /// Creates a texture with the levels of the image, decoding it on the cpu when the device cannot sample its format.
+    /// The color space of the options picks the srgb or plain variant of the format, the format of the options is not used.
+    pub fn create_texture(&self, render_context: &RenderContext, options: &TextureOptions, label: Option<&str>) -> Result<wgpu::Texture, ImageError> {
+        let device = &render_context.device;
+        let format = match options.color_space {
+            ColorSpace::Srgb => self.format.add_srgb_suffix(),
+            ColorSpace::Linear => self.format.remove_srgb_suffix(),
+        };
+        let (image, format) = if device.features().contains(format.required_features()) {
+            (Cow::Borrowed(self), format)
+        } else if decoded_format(format).is_some() {
+            let decoded = self.decompress_as(format);
+            let decoded_format = decoded.format;
+            (Cow::Owned(decoded), decoded_format)
+        } else {
+            return Err(unsupported_error(
+                &format!("{:?}", format),
+                format!("sampling it needs {:?}, which the device does not have", format.required_features()),
+            ));
+        };
+        let (block_width, block_height) = format.block_dimensions();
+        if image.size.width % block_width != 0 || image.size.height % block_height != 0 {
+            return Err(unsupported_error(
+                &format!("{:?}", format),
+                format!("a size of {}x{} that is not made of whole blocks", image.size.width, image.size.height),
+            ));
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: image.size,
+            mip_level_count: image.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let block_size = format.block_copy_size(None).unwrap();
+        for (level, data) in image.levels.iter().enumerate() {
+            let level_size = image.level_size(level as u32);
+            render_context.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(level_size.width / block_width * block_size),
+                    rows_per_image: Some(level_size.height / block_height),
+                },
+                wgpu::Extent3d { depth_or_array_layers: image.size.depth_or_array_layers, ..level_size },
+            );
+        }
+        Ok(texture)
+    }
+}
@@ -1,6 +1,6 @@
-use image::GenericImageView;
+use wgpu::util::DeviceExt;
 
-use crate::render_context::RenderContext;
+use crate::{assets::{Asset, AssetError, AssetServer}, compressed_texture::{decoding_error, CompressedImage}, environment_map::{equirect_to_cube, ENVIRONMENT_FORMAT}, mipmaps::{generate_mipmaps, mip_level_count, MIPMAP_USAGES}, my_texture::{TextureOptions, PLACEHOLDER_COLOR}, render_context::RenderContext};
 
 
 
@@ -10,66 +10,231 @@ pub struct CubeTexture {
     pub view: wgpu::TextureView,
 }
 
+// the faces of a cube texture once its files are read, made into a texture on the gpu by CubeTexture::from_data
+pub enum CubeData {
+    // a panorama as half floats, its faces are rendered from it
+    Equirect { width: u32, height: u32, texels: Vec<u8> },
+    // a ktx2 or dds file with the six faces
+    Compressed(CompressedImage),
+    // the six faces of every mip level, only the first level if the others are generated
+    Faces(Vec<[image::RgbaImage; 6]>),
+}
+
 impl CubeTexture {
 
+    // the directory holds an image per face, or it is a ktx2 or dds file with the six faces, or an hdr or exr panorama
     pub fn from_files(render_context: &RenderContext,
-        directory: &str, label: &str) -> Result<Self, image::ImageError> {
+        directory: &str, options: &TextureOptions, label: &str) -> Result<Self, AssetError> {
+            Self::from_data(render_context, Self::read(directory)?, options, label)
+    }
+
+    // reads and decodes the files, without the gpu so it can run on a worker thread
+    pub fn read(directory: &str) -> Result<CubeData, image::ImageError> {
+            if Self::is_equirect(directory) {
+                let panorama = image::open(directory)?.to_rgba32f();
+                // larger values do not fit into a half float
+                let texels = panorama
+                    .as_raw()
+                    .iter()
+                    .flat_map(|value| half::f16::from_f32(value.min(65504.0)).to_le_bytes())
+                    .collect();
+                return Ok(CubeData::Equirect { width: panorama.width(), height: panorama.height(), texels });
+            }
+            if CompressedImage::is_container(directory) {
+                let image = CompressedImage::open(directory)?;
+                if image.size.depth_or_array_layers != 6 {
+                    return Err(decoding_error(directory, format!("{} has {} faces instead of the 6 of a cube", directory, image.size.depth_or_array_layers)));
+                }
+                return Ok(CubeData::Compressed(image));
+            }
+            let face_names = ["px.png", "nx.png", "py.png", "ny.png", "pz.png", "nz.png"];
+            // precomputed mip levels are optional, level n is read from the subdirectory mip<n>
+            let mut level_directories = vec![directory.to_string()];
+            while std::path::Path::new(&format!("{}/mip{}/{}", directory, level_directories.len(), face_names[0])).exists() {
+                level_directories.push(format!("{}/mip{}", directory, level_directories.len()));
+            }
+            let mut levels: Vec<[image::RgbaImage; 6]> = Vec::new();
+            for (level, level_directory) in level_directories.iter().enumerate() {
+                let mut faces = Vec::new();
+                for face_name in face_names {
+                    let file_name = format!("{}/{}", level_directory, face_name);
+                    let rgba = image::open(&file_name)?.to_rgba8();
+                    // the first face of the first level gives the size of the others, it has to be square
+                    let first = levels.first().map(|faces| &faces[0]).or(faces.first());
+                    let (width, height) = first.map_or(rgba.dimensions(), |face| face.dimensions());
+                    if width != height {
+                        return Err(decoding_error(directory, format!("The faces of {} are {}x{} instead of square", directory, width, height)));
+                    }
+                    let expected = ((width >> level).max(1), (height >> level).max(1));
+                    if rgba.dimensions() != expected {
+                        return Err(decoding_error(
+                            directory,
+                            format!("{} is {:?} instead of {:?} for mip level {}", file_name, rgba.dimensions(), expected, level),
+                        ));
+                    }
+                    faces.push(rgba);
+                }
+                levels.push(faces.try_into().unwrap());
+            }
+            Ok(CubeData::Faces(levels))
+    }
+
+    // uploads what read returned
+    pub fn from_data(render_context: &RenderContext,
+        data: CubeData, options: &TextureOptions, label: &str) -> Result<Self, AssetError> {
+            let levels = match data {
+                CubeData::Equirect { width, height, texels } => {
+                    return Self::from_equirect_texels(render_context, width, height, &texels, options, label);
+                }
+                CubeData::Compressed(image) => {
+                    let texture = image.create_texture(render_context, options, Some(label))?;
+                    return Self::from_texture(render_context, texture, options);
+                }
+                CubeData::Faces(levels) => levels,
+            };
             let device = &render_context.device;
             let queue = &render_context.queue;
-            let mut dimensions: Option<(u32, u32)> = None;
-            let mut texture: Option<wgpu::Texture> = None;
-            let file_names = ["px.png", "nx.png", "py.png", "ny.png", "pz.png", "nz.png"]
-                .iter()
-                .map(|file_name| format!("{}/{}", directory, file_name))
-                .collect::<Vec<_>>();
-            for (i, file_name) in file_names.iter().enumerate() {
-                let img = image::open(file_name)?;
-                let rgba = img.to_rgba8();
-                if dimensions.is_none() {
-                    dimensions = Some(rgba.dimensions());
-                    assert!(texture.is_none());
-                    texture = Some(device.create_texture(&wgpu::TextureDescriptor {
-                        label: Some(label),
-                        size: wgpu::Extent3d {
-                            width: dimensions.unwrap().0,
-                            height: dimensions.unwrap().1,
-                            depth_or_array_layers: 6,
+            let generate = levels.len() == 1;
+            let (width, height) = levels[0][0].dimensions();
+            let size = wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 6,
+            };
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: if generate { mip_level_count(width, height) } else { levels.len() as u32 },
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: options.texture_format(),
+                usage: if generate {
+                    MIPMAP_USAGES | wgpu::TextureUsages::COPY_DST
+                } else {
+                    wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+                },
+                view_formats: &[],
+            });
+            for (level, faces) in levels.iter().enumerate() {
+                let level_size = size.mip_level_size(level as u32, wgpu::TextureDimension::D2);
+                for (i, rgba) in faces.iter().enumerate() {
+                    queue.write_texture(
+                        wgpu::TexelCopyTextureInfo {
+                            aspect: wgpu::TextureAspect::All,
+                            texture: &texture,
+                            mip_level: level as u32,
+                            origin: wgpu::Origin3d {
+                                x: 0,
+                                y: 0,
+                                z: i as u32,
+                            },
                         },
-                        mip_level_count: 1,
-                        sample_count: 1,
-                        dimension: wgpu::TextureDimension::D2,
-                        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                        view_formats: &[],
-                    }));
-                }
-                let dimensions = dimensions.unwrap();
-                println!("Writing texture data for face {}", i);
-                queue.write_texture(
-                    wgpu::TexelCopyTextureInfo {
-                        aspect: wgpu::TextureAspect::All,
-                        texture: texture.as_ref().unwrap(),
-                        mip_level: 0,
-                        origin: wgpu::Origin3d {
-                            x: 0,
-                            y: 0,
-                            z: i as u32,
+                        &options.convert_pixels(rgba)?,
+                        wgpu::TexelCopyBufferLayout {
+                            offset: 0,
+                            bytes_per_row: Some(options.texture_format().block_copy_size(None).unwrap() * level_size.width),
+                            rows_per_image: Some(level_size.height),
                         },
+                        wgpu::Extent3d {
+                            width: level_size.width,
+                            height: level_size.height,
+                            depth_or_array_layers: 1, // Only write one layer at a time
+                        },
+                    );
+                }
+            }
+            if generate {
+                // every face gets its own chain
+                generate_mipmaps(render_context, &texture);
+            }
+            Self::from_texture(render_context, texture, options)
+    }
+
+    pub fn is_equirect(file_path: &str) -> bool {
+        let file_path = file_path.to_lowercase();
+        file_path.ends_with(".hdr") || file_path.ends_with(".exr")
+    }
+
+    // an equirectangular panorama, its faces are a quarter as wide as it and keep the values above 1.
+    // The color space of the options does not apply, the values are linear
+    pub fn from_equirect(render_context: &RenderContext,
+        file_path: &str, options: &TextureOptions, label: &str) -> Result<Self, AssetError> {
+            assert!(Self::is_equirect(file_path), "{} is not a panorama", file_path);
+            Self::from_files(render_context, file_path, options, label)
+    }
+
+    fn from_equirect_texels(render_context: &RenderContext,
+        width: u32, height: u32, texels: &[u8], options: &TextureOptions, label: &str) -> Result<Self, AssetError> {
+            let panorama_texture = render_context.device.create_texture_with_data(
+                &render_context.queue,
+                &wgpu::TextureDescriptor {
+                    label: Some("Panorama"),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
                     },
-                    &rgba,
-                    wgpu::TexelCopyBufferLayout {
-                        offset: 0,
-                        bytes_per_row: Some(4 * dimensions.0),
-                        rows_per_image: Some(dimensions.1),
-                    },
-                    wgpu::Extent3d {
-                        width: dimensions.0,
-                        height: dimensions.1,
-                        depth_or_array_layers: 1, // Only write one layer at a time
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: ENVIRONMENT_FORMAT,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                },
+                wgpu::util::TextureDataOrder::LayerMajor,
+                texels,
+            );
+            let face_size = (width / 4).max(1);
+            let texture = render_context.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: face_size,
+                    height: face_size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: mip_level_count(face_size, face_size),
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: ENVIRONMENT_FORMAT,
+                usage: MIPMAP_USAGES,
+                view_formats: &[],
+            });
+            equirect_to_cube(render_context, &panorama_texture, &texture);
+            generate_mipmaps(render_context, &texture);
+            Self::from_texture(render_context, texture, options)
+    }
+
+    // a gray cube, drawn in place of cube textures that are still loading or failed to load
+    pub fn placeholder(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+            let texture = device.create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    label: Some("Placeholder Cube Texture"),
+                    size: wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 6,
                     },
-                );
-            }
-            let texture = texture.unwrap();    
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                },
+                wgpu::util::TextureDataOrder::LayerMajor,
+                &PLACEHOLDER_COLOR.repeat(6),
+            );
+            let view = texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Placeholder Cube Texture View"),
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                ..Default::default()
+            });
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+            Self { texture, sampler, view }
+    }
+
+    fn from_texture(render_context: &RenderContext, texture: wgpu::Texture, options: &TextureOptions) -> Result<Self, AssetError> {
             let view = texture.create_view(&wgpu::TextureViewDescriptor {
                 label: Some("Cube Texture View"),
                 dimension: Some(wgpu::TextureViewDimension::Cube),
@@ -77,20 +242,23 @@ impl CubeTexture {
                 ..Default::default()
             });
     
-            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-                label: Some("Cube Texture Sampler"),
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Nearest,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                ..Default::default()
-            });
+            let sampler = options.create_sampler(render_context, Some("Cube Texture Sampler"))?;
             Ok(Self {
                 texture,
                 sampler,
                 view,
             })
     }
-}
\ No newline at end of file
+}
+
+// keyed by the directory or file and the options, like Skybox::new takes them
+impl Asset for CubeTexture {
+    type Key = (String, TextureOptions);
+    type Data = CubeData;
+    fn read((directory, _options): &Self::Key, _assets: &AssetServer) -> Result<CubeData, AssetError> {
+        Ok(Self::read(directory)?)
+    }
+    fn create((_directory, options): &Self::Key, data: CubeData, render_context: &RenderContext) -> Result<Self, AssetError> {
+        Self::from_data(render_context, data, options, "Cube Texture")
+    }
+}
@@ -0,0 +1,361 @@
+// image based lighting: the light that arrives from every direction of a sky is convolved once,
+// so the lit pipelines can look up the diffuse and specular ambient light instead of integrating the sky per pixel
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    assets::{Asset, AssetError, AssetServer, Handle},
+    cube_texture::{CubeData, CubeTexture},
+    my_texture::TextureOptions,
+    post_process::{create_uniform_buffer, draw_fullscreen, FULLSCREEN_SHADER},
+    render_context::RenderContext,
+};
+
+pub const IRRADIANCE_SIZE: u32 = 32;
+pub const PREFILTERED_SIZE: u32 = 128;
+// roughness 0 to 1 is spread evenly over the levels, the pbr shader has the same constant
+pub const PREFILTERED_LEVELS: u32 = 5;
+pub const BRDF_LUT_SIZE: u32 = 128;
+pub const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+pub const ENVIRONMENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// a sky that lights the scene, the source is anything Skybox accepts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Environment {
+    pub source: String,
+    // scales the light of the sky, it replaces the ambient light of the state
+    pub intensity: f32,
+}
+
+impl Environment {
+    pub fn new(source: String) -> Self {
+        Self { source, intensity: 1.0 }
+    }
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+}
+
+pub struct EnvironmentMap {
+    // the light a white diffuse surface reflects, by normal
+    pub irradiance: wgpu::Texture,
+    pub irradiance_view: wgpu::TextureView,
+    // the sky blurred by the specular lobe, rougher in every level
+    pub prefiltered: wgpu::Texture,
+    pub prefiltered_view: wgpu::TextureView,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum IblPass {
+    Equirect,
+    Irradiance,
+    Prefilter,
+    Brdf,
+}
+
+impl IblPass {
+    fn entry_point(self) -> &'static str {
+        match self {
+            IblPass::Equirect => "fs_equirect",
+            IblPass::Irradiance => "fs_irradiance",
+            IblPass::Prefilter => "fs_prefilter",
+            IblPass::Brdf => "fs_brdf",
+        }
+    }
+    fn format(self) -> wgpu::TextureFormat {
+        match self {
+            IblPass::Brdf => BRDF_LUT_FORMAT,
+            _ => ENVIRONMENT_FORMAT,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FaceUniform {
+    face: u32,
+    roughness: f32,
+    source_size: f32,
+    target_size: f32,
+}
+
+// one per pass and render context
+type IblPipelines = HashMap<IblPass, (wgpu::BindGroupLayout, wgpu::RenderPipeline)>;
+
+fn get_ibl_pipeline(render_context: &RenderContext, pass: IblPass) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+    let pipelines = render_context.resources.get::<IblPipelines>();
+    let mut pipelines = pipelines.lock().unwrap();
+    pipelines.entry(pass).or_insert_with(|| create_ibl_pipeline(&render_context.device, pass)).clone()
+}
+
+fn create_ibl_pipeline(device: &wgpu::Device, pass: IblPass) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+    let fragment_entry = |binding: u32, ty: wgpu::BindingType| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty,
+        count: None,
+    };
+    // the panorama or the cube it was turned into, then the sampler and the face. The brdf only depends on the uv
+    let mut entries = Vec::new();
+    let source_dimension = match pass {
+        IblPass::Equirect => Some((0, wgpu::TextureViewDimension::D2)),
+        IblPass::Irradiance | IblPass::Prefilter => Some((3, wgpu::TextureViewDimension::Cube)),
+        IblPass::Brdf => None,
+    };
+    if let Some((binding, view_dimension)) = source_dimension {
+        entries.push(fragment_entry(binding, wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        }));
+        entries.push(fragment_entry(1, wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)));
+        entries.push(fragment_entry(2, wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        }));
+    }
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &entries,
+        label: Some("ibl_bind_group_layout"),
+    });
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("IBL Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("IBL Shader"),
+        source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", FULLSCREEN_SHADER, include_str!("pipelines/ibl.wgsl")).into()),
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("IBL Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some(pass.entry_point()),
+            targets: &[Some(wgpu::ColorTargetState {
+                format: pass.format(),
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    });
+    (bind_group_layout, pipeline)
+}
+
+// trilinear and clamped, for the maps of the environment and the brdf lookup table
+pub fn create_environment_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Environment Sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+fn create_cube(device: &wgpu::Device, label: &str, size: u32, mip_level_count: u32, usage: wgpu::TextureUsages) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: ENVIRONMENT_FORMAT,
+        usage,
+        view_formats: &[],
+    })
+}
+
+fn cube_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Environment Cube View"),
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        array_layer_count: Some(6),
+        ..Default::default()
+    })
+}
+
+/// Draws a level of every face of target from the source, a panorama for IblPass::Equirect and a cube otherwise.
+fn draw_faces(render_context: &RenderContext, pass: IblPass, source: &wgpu::Texture, target: &wgpu::Texture, level: u32, roughness: f32) {
+    let device = &render_context.device;
+    let queue = &render_context.queue;
+    let (bind_group_layout, pipeline) = get_ibl_pipeline(render_context, pass);
+    let source_view = source.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("IBL Source View"),
+        dimension: Some(if pass == IblPass::Equirect { wgpu::TextureViewDimension::D2 } else { wgpu::TextureViewDimension::Cube }),
+        ..Default::default()
+    });
+    let source_binding = if pass == IblPass::Equirect { 0 } else { 3 };
+    // the panorama wraps around horizontally
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("IBL Source Sampler"),
+        address_mode_u: if pass == IblPass::Equirect { wgpu::AddressMode::Repeat } else { wgpu::AddressMode::ClampToEdge },
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("IBL Encoder"),
+    });
+    for face in 0..6 {
+        let uniform = FaceUniform {
+            face,
+            roughness,
+            source_size: source.width() as f32,
+            target_size: target.width() as f32,
+        };
+        let uniform_buffer = create_uniform_buffer(device, "IBL Face Buffer", &uniform);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: source_binding,
+                    resource: wgpu::BindingResource::TextureView(&source_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("ibl_bind_group"),
+        });
+        let face_view = target.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("IBL Face View"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            base_array_layer: face,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        draw_fullscreen(&mut encoder, "IBL Pass", &pipeline, &bind_group, &face_view, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+}
+
+/// Renders the first level of every face of cube from an equirectangular panorama. The mip levels are left to generate_mipmaps.
+pub fn equirect_to_cube(render_context: &RenderContext, panorama: &wgpu::Texture, cube: &wgpu::Texture) {
+    assert_eq!(cube.format(), ENVIRONMENT_FORMAT);
+    draw_faces(render_context, IblPass::Equirect, panorama, cube, 0, 0.0);
+}
+
+/// The lookup table of the split sum, it does not depend on the environment so one is made per device.
+pub fn create_brdf_lut(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("BRDF LUT"),
+        size: wgpu::Extent3d {
+            width: BRDF_LUT_SIZE,
+            height: BRDF_LUT_SIZE,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: BRDF_LUT_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    // made once per render context, before there is one to cache the pipeline in
+    let (bind_group_layout, pipeline) = create_ibl_pipeline(device, IblPass::Brdf);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &bind_group_layout,
+        entries: &[],
+        label: Some("brdf_lut_bind_group"),
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("BRDF LUT Encoder"),
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    draw_fullscreen(&mut encoder, "BRDF LUT Pass", &pipeline, &bind_group, &view, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+    queue.submit(std::iter::once(encoder.finish()));
+    texture
+}
+
+impl EnvironmentMap {
+    /// Convolves a cube texture, which should have mip levels so that few samples are enough for bright details.
+    pub fn from_cube(render_context: &RenderContext, cube: &wgpu::Texture) -> Self {
+        let device = &render_context.device;
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+        let irradiance = create_cube(device, "Irradiance Map", IRRADIANCE_SIZE, 1, usage);
+        draw_faces(render_context, IblPass::Irradiance, cube, &irradiance, 0, 0.0);
+        let prefiltered = create_cube(device, "Prefiltered Map", PREFILTERED_SIZE, PREFILTERED_LEVELS, usage);
+        for level in 0..PREFILTERED_LEVELS {
+            let roughness = level as f32 / (PREFILTERED_LEVELS - 1) as f32;
+            draw_faces(render_context, IblPass::Prefilter, cube, &prefiltered, level, roughness);
+        }
+        Self {
+            irradiance_view: cube_view(&irradiance),
+            irradiance,
+            prefiltered_view: cube_view(&prefiltered),
+            prefiltered,
+        }
+    }
+
+    // black maps for when there is no environment, the flat ambient light is used instead
+    pub fn empty(device: &wgpu::Device) -> Self {
+        let irradiance = create_cube(device, "Empty Irradiance Map", 1, 1, wgpu::TextureUsages::TEXTURE_BINDING);
+        let prefiltered = create_cube(device, "Empty Prefiltered Map", 1, 1, wgpu::TextureUsages::TEXTURE_BINDING);
+        Self {
+            irradiance_view: cube_view(&irradiance),
+            irradiance,
+            prefiltered_view: cube_view(&prefiltered),
+            prefiltered,
+        }
+    }
+}
+
+// what an environment map is made from
+pub enum EnvironmentData {
+    // the cube texture of a skybox with the same source, it was loaded already
+    Shared(Handle<CubeTexture>),
+    // the files of the source, read on the worker
+    Read(CubeData),
+}
+
+// the maps of a source, computed when it is loaded. The cube texture is shared with a skybox of the same source that is loaded already
+impl Asset for EnvironmentMap {
+    type Key = String;
+    type Data = EnvironmentData;
+    fn read(source: &String, assets: &AssetServer) -> Result<EnvironmentData, AssetError> {
+        let skybox = assets.get_handle::<CubeTexture>(&(source.clone(), TextureOptions::default()));
+        if let Some(cube) = skybox.filter(|cube| cube.get().is_some()) {
+            return Ok(EnvironmentData::Shared(cube));
+        }
+        Ok(EnvironmentData::Read(CubeTexture::read(source)?))
+    }
+    // the cube texture is uploaded if it is not shared, then it is convolved
+    fn create(source: &String, data: EnvironmentData, render_context: &RenderContext) -> Result<Self, AssetError> {
+        let cube = match data {
+            EnvironmentData::Shared(cube) => cube.expect_loaded(),
+            EnvironmentData::Read(data) => Arc::new(CubeTexture::from_data(render_context, data, &TextureOptions::default(), source)?),
+        };
+        Ok(Self::from_cube(render_context, &cube.texture))
+    }
+}
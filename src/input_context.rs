@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use winit::{
     event::{DeviceEvent, ElementState, MouseButton, WindowEvent},
-    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
+    keyboard::{KeyCode, PhysicalKey},
 };
 #[derive(Default)]
 pub struct InputContext {
@@ -120,13 +120,10 @@ impl InputContext {
 
     pub fn handle_device_event(&mut self, event: &DeviceEvent) {
         // handle device events here
-        match event {
-            DeviceEvent::MouseMotion { delta } => {
-                // println!("Received mouse motion: {:?}", delta);
-                let old = self.device_mouse_delta_accumulated;
-                self.device_mouse_delta_accumulated = (delta.0 + old.0, delta.1 + old.1);
-            }
-            _ => {}
+        if let DeviceEvent::MouseMotion { delta } = event {
+            // println!("Received mouse motion: {:?}", delta);
+            let old = self.device_mouse_delta_accumulated;
+            self.device_mouse_delta_accumulated = (delta.0 + old.0, delta.1 + old.1);
         }
     }
 
@@ -135,7 +132,7 @@ impl InputContext {
     }
 
     pub fn get_key(&mut self, key: KeyCode) -> bool {
-        self.key_states.entry(key).or_insert(false).clone()
+        *self.key_states.entry(key).or_insert(false)
     }
 
     pub fn get_key_up(&mut self, key: KeyCode) -> bool {
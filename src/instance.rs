@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use crate::{render_context::RenderContext, transform::Transform};
+
+// one copy of a model in an instanced draw
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instance {
+    pub transform: Transform,
+    // multiplied with the sampled color
+    pub tint: [f32; 4],
+    // free for custom shaders, ignored by the built-in ones
+    pub custom: [f32; 4],
+}
+
+impl Instance {
+    pub fn from_transform(transform: Transform) -> Self {
+        Self {
+            transform,
+            ..Default::default()
+        }
+    }
+    // the instance transform is relative to the matrix of the renderable
+    pub fn to_raw(&self, object_matrix: &cgmath::Matrix4<f32>) -> InstanceRaw {
+        InstanceRaw {
+            model: (object_matrix * self.transform.to_matrix()).into(),
+            tint: self.tint,
+            custom: self.custom,
+        }
+    }
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            transform: Transform::default(),
+            tint: [1.0, 1.0, 1.0, 1.0],
+            custom: [0.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    model: [[f32; 4]; 4],
+    tint: [f32; 4],
+    custom: [f32; 4],
+}
+
+impl InstanceRaw {
+    // locations start at 10 so that vertex attributes have room to grow
+    const ATTRIBS: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+        10 => Float32x4, 11 => Float32x4, 12 => Float32x4, 13 => Float32x4,
+        14 => Float32x4, 15 => Float32x4
+    ];
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// the instances of a renderable and their gpu copy, uploaded again only after they or the renderable moved
+pub struct InstanceBuffer {
+    instances: Vec<Instance>,
+    buffer: Option<Arc<wgpu::Buffer>>,
+    dirty: bool,
+    uploaded_object_matrix: Option<cgmath::Matrix4<f32>>,
+}
+
+impl InstanceBuffer {
+    pub fn new(instances: Vec<Instance>) -> Self {
+        Self {
+            instances,
+            buffer: None,
+            dirty: true,
+            uploaded_object_matrix: None,
+        }
+    }
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+    pub fn instances_mut(&mut self) -> &mut Vec<Instance> {
+        self.dirty = true;
+        &mut self.instances
+    }
+    pub fn push(&mut self, instance: Instance) {
+        self.instances_mut().push(instance);
+    }
+    pub fn len(&self) -> u32 {
+        self.instances.len() as u32
+    }
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+    pub fn to_raw(&self, object_matrix: &cgmath::Matrix4<f32>) -> Vec<InstanceRaw> {
+        self.instances.iter().map(|instance| instance.to_raw(object_matrix)).collect()
+    }
+    pub fn get_buffer(&mut self, render_context: &RenderContext, object_matrix: &cgmath::Matrix4<f32>) -> Arc<wgpu::Buffer> {
+        if self.dirty || self.buffer.is_none() || self.uploaded_object_matrix != Some(*object_matrix) {
+            let raw = self.to_raw(object_matrix);
+            let contents: &[u8] = bytemuck::cast_slice(&raw);
+            let fits = self
+                .buffer
+                .as_ref()
+                .is_some_and(|buffer| buffer.size() >= contents.len() as wgpu::BufferAddress);
+            if fits {
+                render_context.queue.write_buffer(self.buffer.as_ref().unwrap(), 0, contents);
+            } else {
+                // grow to the next power of two so that adding instances one by one does not reallocate every frame
+                let size = (contents.len() as wgpu::BufferAddress).next_power_of_two();
+                let buffer = render_context.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Instance Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                render_context.queue.write_buffer(&buffer, 0, contents);
+                self.buffer = Some(Arc::new(buffer));
+            }
+            self.dirty = false;
+            self.uploaded_object_matrix = Some(*object_matrix);
+        }
+        self.buffer.clone().unwrap()
+    }
+}
+
+impl Default for InstanceBuffer {
+    fn default() -> Self {
+        Self::new(vec![Instance::default()])
+    }
+}
+
+// a copy gets its own gpu buffer, otherwise moving one of them would move both
+impl Clone for InstanceBuffer {
+    fn clone(&self) -> Self {
+        Self::new(self.instances.clone())
+    }
+}
@@ -1,7 +1,12 @@
 pub mod app;
+pub mod batching;
 pub mod camera;
 pub mod camera_uniform;
+pub mod transform;
 pub mod get_type;
+pub mod instance;
+pub mod light;
+pub mod material;
 pub mod input_context;
 pub mod render_context;
 pub mod render_data;
@@ -10,11 +15,23 @@ pub mod renderable;
 pub mod renderables;
 pub mod state;
 pub mod my_texture;
+pub mod mipmaps;
+pub mod bc_decode;
+pub mod compressed_texture;
+pub mod environment_map;
 pub mod my_pipeline;
 pub mod vertex;
 pub mod pipelines;
 pub mod cube_texture;
 pub mod textures;
 pub mod cache;
+pub mod assets;
 pub mod my_render_pass;
-pub mod render_passes;
\ No newline at end of file
+pub mod render_passes;
+pub mod scene;
+pub mod render_graph;
+pub mod screenshot;
+pub mod shadow;
+pub mod sky;
+pub mod post_process;
+pub mod post_effects;
\ No newline at end of file
@@ -0,0 +1,142 @@
+use cgmath::InnerSpace;
+
+use crate::shadow::ShadowLayout;
+
+// more lights than this are ignored by the lit pipelines
+pub const MAX_LIGHTS: usize = 16;
+
+// colors are linear, the intensity scales them. Point and spot lights fade out completely at their range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Directional {
+        // the direction the light travels in
+        direction: cgmath::Vector3<f32>,
+        color: [f32; 3],
+        intensity: f32,
+        // rendered into cascaded shadow maps that cover the view of the camera
+        cast_shadows: bool,
+    },
+    Point {
+        position: cgmath::Vector3<f32>,
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+    },
+    Spot {
+        position: cgmath::Vector3<f32>,
+        direction: cgmath::Vector3<f32>,
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+        // half angles of the cone in degrees, the light fades between them
+        inner_angle: f32,
+        outer_angle: f32,
+        cast_shadows: bool,
+    },
+}
+
+impl Light {
+    pub fn casts_shadows(&self) -> bool {
+        match *self {
+            Light::Directional { cast_shadows, .. } | Light::Spot { cast_shadows, .. } => cast_shadows,
+            Light::Point { .. } => false,
+        }
+    }
+    // the shadow layers are filled in by LightsUniform::set_shadow_layers
+    pub fn to_raw(&self) -> LightRaw {
+        let scale = |color: [f32; 3], intensity: f32| color.map(|c| c * intensity);
+        match *self {
+            Light::Directional { direction, color, intensity, .. } => {
+                let [r, g, b] = scale(color, intensity);
+                LightRaw {
+                    position: [0.0, 0.0, 0.0, LightRaw::DIRECTIONAL],
+                    direction: direction.normalize().extend(0.0).into(),
+                    color: [r, g, b, 0.0],
+                    cone: [0.0, 0.0, -1.0, 0.0],
+                }
+            }
+            Light::Point { position, color, intensity, range } => {
+                let [r, g, b] = scale(color, intensity);
+                LightRaw {
+                    position: position.extend(LightRaw::POINT).into(),
+                    direction: [0.0, 0.0, 0.0, 0.0],
+                    color: [r, g, b, range],
+                    cone: [0.0, 0.0, -1.0, 0.0],
+                }
+            }
+            Light::Spot { position, direction, color, intensity, range, inner_angle, outer_angle, .. } => {
+                let [r, g, b] = scale(color, intensity);
+                LightRaw {
+                    position: position.extend(LightRaw::SPOT).into(),
+                    direction: direction.normalize().extend(0.0).into(),
+                    color: [r, g, b, range],
+                    cone: [inner_angle.to_radians().cos(), outer_angle.to_radians().cos(), -1.0, 0.0],
+                }
+            }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Default)]
+pub struct LightRaw {
+    // w is the kind of light
+    pub position: [f32; 4],
+    pub direction: [f32; 4],
+    // rgb is the color times the intensity, a the range
+    pub color: [f32; 4],
+    // cosines of the inner and outer angles of spot lights, then the first shadow layer (-1 without shadows) and the number of layers
+    pub cone: [f32; 4],
+}
+
+impl LightRaw {
+    pub const DIRECTIONAL: f32 = 0.0;
+    pub const POINT: f32 = 1.0;
+    pub const SPOT: f32 = 2.0;
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsUniform {
+    // rgb is added to every lit surface, w is the intensity of the environment
+    ambient: [f32; 4],
+    // x is the number of lights in use, y is 1 if the environment lights the surfaces instead of the ambient color
+    count: [u32; 4],
+    lights: [LightRaw; MAX_LIGHTS],
+}
+
+impl LightsUniform {
+    pub fn new(lights: &[Light], ambient: [f32; 3]) -> Self {
+        if lights.len() > MAX_LIGHTS {
+            log::warn!("Only the first {} of {} lights are used", MAX_LIGHTS, lights.len());
+        }
+        let mut raw = [LightRaw::default(); MAX_LIGHTS];
+        let count = lights.len().min(MAX_LIGHTS);
+        for (raw, light) in raw.iter_mut().zip(lights) {
+            *raw = light.to_raw();
+        }
+        Self {
+            ambient: [ambient[0], ambient[1], ambient[2], 0.0],
+            count: [count as u32, 0, 0, 0],
+            lights: raw,
+        }
+    }
+    pub fn set_shadow_layers(&mut self, shadow_layout: &ShadowLayout) {
+        for (raw, layers) in self.lights.iter_mut().zip(&shadow_layout.light_layers) {
+            if let Some((first_layer, num_layers)) = *layers {
+                raw.cone[2] = first_layer as f32;
+                raw.cone[3] = num_layers as f32;
+            }
+        }
+    }
+    pub fn set_environment(&mut self, intensity: f32) {
+        self.ambient[3] = intensity;
+        self.count[1] = 1;
+    }
+}
+
+impl Default for LightsUniform {
+    fn default() -> Self {
+        Self::new(&[], [0.0, 0.0, 0.0])
+    }
+}
@@ -0,0 +1,65 @@
+use crate::my_texture::{TextureOptions, TextureSource};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    // fragments with an alpha below the cutoff are discarded
+    Mask(f32),
+    Blend,
+}
+
+// a texture of a material, with the wrapping and filtering of its sampler.
+// The color space is chosen by the slot the texture is used in
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialTexture {
+    pub source: TextureSource,
+    pub options: TextureOptions,
+}
+
+impl MaterialTexture {
+    pub fn new(source: TextureSource) -> Self {
+        Self { source, options: TextureOptions::default() }
+    }
+}
+
+// a metallic-roughness material, following the gltf conventions. Factors are multiplied with their texture,
+// the metallic value is read from the blue channel of the metallic-roughness texture and the roughness from green
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<MaterialTexture>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<MaterialTexture>,
+    pub normal_texture: Option<MaterialTexture>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<MaterialTexture>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: [f32; 3],
+    pub emissive_texture: Option<MaterialTexture>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl Default for Material {
+    // the defaults of the gltf specification
+    fn default() -> Self {
+        Self {
+            name: None,
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_factor: [0.0, 0.0, 0.0],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
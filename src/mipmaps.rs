@@ -0,0 +1,142 @@
+// fills the mip chain of a texture from its first level, so that minified textures are filtered instead of aliasing
+
+use std::collections::HashMap;
+
+use crate::{
+    post_process::{create_linear_sampler, draw_fullscreen, FULLSCREEN_SHADER},
+    render_context::RenderContext,
+};
+
+// every level halves the larger side until it is one texel
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// the usages a texture needs for generate_mipmaps, on top of what it is used for otherwise
+pub const MIPMAP_USAGES: wgpu::TextureUsages = wgpu::TextureUsages::TEXTURE_BINDING.union(wgpu::TextureUsages::RENDER_ATTACHMENT);
+
+// one per format and render context
+type MipmapPipelines = HashMap<wgpu::TextureFormat, (wgpu::BindGroupLayout, wgpu::RenderPipeline)>;
+
+fn get_mipmap_pipeline(render_context: &RenderContext, format: wgpu::TextureFormat) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+    let device = &render_context.device;
+    let pipelines = render_context.resources.get::<MipmapPipelines>();
+    let mut pipelines = pipelines.lock().unwrap();
+    pipelines.entry(format).or_insert_with(|| {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("mipmap_bind_group_layout"),
+        });
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap Shader"),
+            source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", FULLSCREEN_SHADER, include_str!("pipelines/mipmap.wgsl")).into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        (bind_group_layout, pipeline)
+    }).clone()
+}
+
+/// Renders every mip level after the first from the one before it, for each array layer,
+/// so the faces of a cube texture get their own chains. The texture needs MIPMAP_USAGES.
+pub fn generate_mipmaps(render_context: &RenderContext, texture: &wgpu::Texture) {
+    if texture.mip_level_count() == 1 {
+        return;
+    }
+    assert!(
+        texture.usage().contains(MIPMAP_USAGES),
+        "Generating mipmaps needs the usages {:?}, the texture has {:?}",
+        MIPMAP_USAGES,
+        texture.usage()
+    );
+    let device = &render_context.device;
+    let (bind_group_layout, pipeline) = get_mipmap_pipeline(render_context, texture.format());
+    let sampler = create_linear_sampler(device);
+    let level_view = |layer: u32, level: u32| {
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("mip level view"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    };
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Mipmap Encoder"),
+    });
+    for layer in 0..texture.depth_or_array_layers() {
+        for level in 1..texture.mip_level_count() {
+            let source = level_view(layer, level - 1);
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+                label: Some("mipmap_bind_group"),
+            });
+            draw_fullscreen(
+                &mut encoder,
+                "Mipmap Pass",
+                &pipeline,
+                &bind_group,
+                &level_view(layer, level),
+                wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            );
+        }
+    }
+    render_context.queue.submit(std::iter::once(encoder.finish()));
+}
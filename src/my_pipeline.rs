@@ -1,11 +1,11 @@
 // renderables rely on pipelines, pipeline relies on render pass
 
-use std::{any::TypeId, collections::HashMap, sync::Arc};
+use std::{any::TypeId, borrow::Cow, collections::HashMap, sync::Arc};
 
 use lazy_static::lazy_static;
-use wgpu::{naga::Type, RenderPipeline};
+use wgpu::RenderPipeline;
 
-use crate::{pipelines::{default_pipeline::DefaultPipeline, skybox_pipeline::SkyboxPipeline, ui_pipeline::UIPipeline}, render_context::RenderContext};
+use crate::{assets::{Asset, AssetError, AssetServer}, pipelines::{default_pipeline::DefaultPipeline, lit_pipeline::LitPipeline, pbr_pipeline::PbrPipeline, procedural_sky_pipeline::ProceduralSkyPipeline, skybox_pipeline::SkyboxPipeline, tonemap_pipeline::TonemapPipeline, transparent_pipeline::TransparentPipeline, ui_pipeline::UIPipeline}, render_context::RenderContext, vertex::VertexLayout};
 
 pub struct MyPipeline{
     pub pipeline: RenderPipeline,
@@ -13,14 +13,64 @@ pub struct MyPipeline{
 }
 
 pub trait PipelineBuilder{
+    // the vertex buffer layout the pipeline reads, renderables drawn with it must use the same one
+    fn vertex_layout(&self) -> VertexLayout;
     fn build_pipeline(&self, render_context: &RenderContext) -> MyPipeline;
 }
 
+// the wgsl of a shader module
+#[derive(Hash, PartialEq, Eq, Clone)]
+pub enum ShaderSource {
+    // compiled into the binary with include_str
+    Embedded { label: &'static str, source: &'static str },
+    // read when the shader is loaded, edits show up in the pipelines built after every handle to the old one was dropped
+    File(String),
+}
+
+impl std::fmt::Debug for ShaderSource {
+    // without the source, it is long
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderSource::Embedded { label, .. } => f.debug_tuple("Embedded").field(label).finish(),
+            ShaderSource::File(file_path) => f.debug_tuple("File").field(file_path).finish(),
+        }
+    }
+}
+
+impl Asset for wgpu::ShaderModule {
+    type Key = ShaderSource;
+    type Data = Cow<'static, str>;
+    fn read(source: &ShaderSource, _assets: &AssetServer) -> Result<Self::Data, AssetError> {
+        Ok(match source {
+            ShaderSource::Embedded { source, .. } => Cow::Borrowed(*source),
+            ShaderSource::File(file_path) => Cow::Owned(std::fs::read_to_string(file_path)?),
+        })
+    }
+    fn create(source: &ShaderSource, wgsl: Self::Data, render_context: &RenderContext) -> Result<Self, AssetError> {
+        let label = match source {
+            ShaderSource::Embedded { label, .. } => label.to_string(),
+            ShaderSource::File(file_path) => file_path.clone(),
+        };
+        Ok(render_context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&label),
+            source: wgpu::ShaderSource::Wgsl(wgsl),
+        }))
+    }
+    fn reads_files(source: &ShaderSource) -> bool {
+        matches!(source, ShaderSource::File(_))
+    }
+}
+
 lazy_static!{
     pub static ref PIPELINE_BUILDERS: Arc<HashMap<TypeId, Box<dyn PipelineBuilder + Send + Sync>>> ={
         Arc::new(HashMap::from([
             (TypeId::of::<DefaultPipeline>(), Box::new(DefaultPipeline) as Box<dyn PipelineBuilder + Send + Sync>),
+            (TypeId::of::<LitPipeline>(), Box::new(LitPipeline) as Box<dyn PipelineBuilder + Send + Sync>),
+            (TypeId::of::<PbrPipeline>(), Box::new(PbrPipeline) as Box<dyn PipelineBuilder + Send + Sync>),
+            (TypeId::of::<ProceduralSkyPipeline>(), Box::new(ProceduralSkyPipeline) as Box<dyn PipelineBuilder + Send + Sync>),
             (TypeId::of::<SkyboxPipeline>(), Box::new(SkyboxPipeline) as Box<dyn PipelineBuilder + Send + Sync>),
+            (TypeId::of::<TonemapPipeline>(), Box::new(TonemapPipeline) as Box<dyn PipelineBuilder + Send + Sync>),
+            (TypeId::of::<TransparentPipeline>(), Box::new(TransparentPipeline) as Box<dyn PipelineBuilder + Send + Sync>),
             (TypeId::of::<UIPipeline>(), Box::new(UIPipeline) as Box<dyn PipelineBuilder + Send + Sync>),
         ])
     )};
@@ -1,26 +1,75 @@
-use std::{any::TypeId, collections::HashMap, sync::Arc};
-
-use lazy_static::lazy_static;
-
-use crate::render_passes::{opauqe3d_render_pass::Opaque3DRenderPass, ui_render_pass::UiRenderPass};
+use std::any::TypeId;
 
+use crate::{
+    batching::{draw_batched, BatchStats}, camera::Camera, post_process::PostProcessStack, render_context::RenderContext, render_graph::{RenderGraphAttachments, TransientAttachment}, renderable::{sort_back_to_front, Renderable}, shadow::ShadowLayout
+};
 
+/// What a pass may use from the state besides its renderables.
+pub struct FrameInputs<'a> {
+    pub camera: &'a Camera,
+    pub post_process: &'a PostProcessStack,
+    // the layers of the shadow map for the lights of the frame
+    pub shadow_layout: &'a ShadowLayout,
+}
 
 pub trait RenderPassBuilder{
+    /// Attachments this pass loads or samples. The pass runs after every pass that writes them without reading them.
+    fn reads(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+    /// Attachments this pass renders into.
+    fn writes(&self) -> Vec<&'static str>;
+    /// Passes that must run before this one, in addition to the ones implied by reads.
+    fn dependencies(&self) -> Vec<TypeId> {
+        Vec::new()
+    }
+    /// Whether renderables of this pass are drawn farthest from the camera first.
+    fn sorts_back_to_front(&self) -> bool {
+        false
+    }
+    /// Attachments owned by the render graph that this pass introduces. They are sized to the surface.
+    fn transient_attachments(&self, config: &wgpu::SurfaceConfiguration) -> Vec<TransientAttachment> {
+        let _ = config;
+        Vec::new()
+    }
+    /// Passes that draw something of their own, like a fullscreen effect, instead of renderables.
+    /// They run whenever a pass that writes one of their reads runs.
+    fn draws_without_renderables(&self) -> bool {
+        false
+    }
+    /// Passes that draw every renderable of the frame, like the shadow pass, instead of only the ones that chose them.
+    /// They run whenever another pass does.
+    fn draws_every_renderable(&self) -> bool {
+        false
+    }
+    /// Draws what the pass itself contributes, after its renderables.
+    fn draw(&self, render_pass: &mut wgpu::RenderPass, render_context: &RenderContext, attachments: &RenderGraphAttachments) {
+        let _ = (render_pass, render_context, attachments);
+    }
     /// This function should create a render pass based on the provided parameters.
     fn create_render_pass<'a>(
         &self,
         encoder: &'a mut wgpu::CommandEncoder,
-        color_view: &'a wgpu::TextureView,
-        depth_view: &'a wgpu::TextureView,
+        attachments: &RenderGraphAttachments<'a>,
     ) -> wgpu::RenderPass<'a>;
+    /// Records the pass into the encoder and returns how its renderables were batched.
+    /// By default it begins the render pass of create_render_pass, draws the renderables and then what draw adds.
+    /// Passes that record several render passes of their own, or none, override it.
+    fn execute<'a>(
+        &self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        render_context: &RenderContext,
+        attachments: &RenderGraphAttachments<'a>,
+        renderables: &mut [&mut dyn Renderable],
+        frame: &FrameInputs,
+    ) -> BatchStats {
+        let mut render_pass = self.create_render_pass(encoder, attachments);
+        let keep_order = self.sorts_back_to_front();
+        if keep_order {
+            sort_back_to_front(renderables, frame.camera);
+        }
+        let stats = draw_batched(&mut render_pass, renderables, render_context, keep_order);
+        self.draw(&mut render_pass, render_context, attachments);
+        stats
+    }
 }
-
-lazy_static!{
-    pub static ref RENDER_PASS_BUILDERS: Vec<(TypeId, Box<dyn RenderPassBuilder + Send + Sync>)> ={
-        Vec::from([
-            (TypeId::of::<Opaque3DRenderPass>(), Box::new(Opaque3DRenderPass) as Box<dyn RenderPassBuilder + Send + Sync>),
-            (TypeId::of::<UiRenderPass>(), Box::new(UiRenderPass) as Box<dyn RenderPassBuilder + Send + Sync>),
-        ])
-    };
-}
\ No newline at end of file
@@ -1,10 +1,9 @@
-use std::{collections::HashMap, sync::Mutex};
+use std::sync::Arc;
 
-use image::{GenericImageView, Rgba};
-use lazy_static::lazy_static;
+use image::Rgba;
 use rusttype::{point, Font};
 
-use crate::render_context;
+use crate::{assets::{Asset, AssetError, AssetServer}, compressed_texture::{decoding_error, CompressedImage}, mipmaps::{generate_mipmaps, mip_level_count, MIPMAP_USAGES}, render_context};
 
 pub struct MyTexture {
     #[allow(unused)]
@@ -12,23 +11,202 @@ pub struct MyTexture {
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
 }
+
+// the pixels of a texture once its files are read, made into a texture on the gpu by MyTexture::from_data
+pub enum TextureData {
+    // a ktx2 or dds file
+    Compressed(CompressedImage),
+    // decoded mip levels, only the first one if the others are generated
+    Levels(Vec<image::RgbaImage>),
+}
 #[derive(Hash, PartialEq, PartialOrd, Ord, Eq, Debug, Clone)]
 pub enum TextureSource{
+    // ktx2 and dds files are uploaded in the format they are stored in, with their own mip levels
     FilePath(String),
     TextCharacter{character: char, font_file_path: String},
+    // a single pixel, for materials that only have a color
+    SolidColor([u8; 4]),
+    // decoded rgba8 pixels that do not come from a file of their own, like the images embedded in a gltf file.
+    // The name tells apart images with the same size
+    Pixels{name: String, width: u32, height: u32, rgba: Arc<Vec<u8>>},
+    // precomputed mip levels, one file each starting with the full size. Every level is half as large as the one before,
+    // the mips of the other sources are generated when they are loaded
+    MipChain(Vec<String>),
+}
+
+// how a texture is filtered when it is magnified or minified
+#[derive(Hash, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum TextureFiltering {
+    Nearest,
+    // linear within a mip level, the nearest level is used
+    Bilinear,
+    // linear within and between mip levels
+    #[default]
+    Trilinear,
+    // trilinear with up to this many samples along the direction the texture is stretched in, between 1 and 16
+    Anisotropic(u16),
+    // the anisotropy is clamped like the one of Anisotropic, above 1 it needs every filter to be linear
+    Custom {
+        mag_filter: wgpu::FilterMode,
+        min_filter: wgpu::FilterMode,
+        mipmap_filter: wgpu::FilterMode,
+        anisotropy_clamp: u16,
+    },
+}
+
+impl TextureFiltering {
+    // magnification, minification and mipmap filter, and the anisotropy clamp
+    pub fn filter_modes(self) -> (wgpu::FilterMode, wgpu::FilterMode, wgpu::FilterMode, u16) {
+        use wgpu::FilterMode::{Linear, Nearest};
+        match self {
+            TextureFiltering::Nearest => (Nearest, Nearest, Nearest, 1),
+            TextureFiltering::Bilinear => (Linear, Linear, Nearest, 1),
+            TextureFiltering::Trilinear => (Linear, Linear, Linear, 1),
+            TextureFiltering::Anisotropic(samples) => (Linear, Linear, Linear, samples.clamp(1, 16)),
+            TextureFiltering::Custom { mag_filter, min_filter, mipmap_filter, anisotropy_clamp } => {
+                (mag_filter, min_filter, mipmap_filter, anisotropy_clamp.clamp(1, 16))
+            }
+        }
+    }
+    // the filter modes, or an error if they cannot be used together
+    pub fn validate(self) -> Result<(wgpu::FilterMode, wgpu::FilterMode, wgpu::FilterMode, u16), AssetError> {
+        let (mag_filter, min_filter, mipmap_filter, anisotropy_clamp) = self.filter_modes();
+        if anisotropy_clamp > 1 && [mag_filter, min_filter, mipmap_filter].contains(&wgpu::FilterMode::Nearest) {
+            return Err(format!("{:?} is anisotropic, so every filter has to be linear", self).into());
+        }
+        Ok((mag_filter, min_filter, mipmap_filter, anisotropy_clamp))
+    }
 }
 
+#[derive(Hash, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum ColorSpace {
+    // colors, decoded to linear values when they are sampled
+    #[default]
+    Srgb,
+    // data like normals, roughness or occlusion that is sampled as it is stored
+    Linear,
+}
+
+// how a texture is stored and sampled. Textures are cached by their source together with these,
+// so the same file can be loaded with different options
+#[derive(Hash, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct TextureOptions {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    // None uses RenderContext::texture_filtering
+    pub filtering: Option<TextureFiltering>,
+    pub color_space: ColorSpace,
+    // the format the pixels are converted to. The color space picks between the srgb and the plain variant,
+    // formats without an srgb variant are decoded on the cpu if they are floats and need a linear color space otherwise.
+    // ktx2 and dds files keep the format they are stored in, only the color space applies to them
+    pub format: wgpu::TextureFormat,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            filtering: None,
+            color_space: ColorSpace::Srgb,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+impl TextureOptions {
+    // Repeat for tiling textures
+    pub fn with_address_mode(mut self, address_mode: wgpu::AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self
+    }
+    pub fn with_filtering(mut self, filtering: TextureFiltering) -> Self {
+        self.filtering = Some(filtering);
+        self
+    }
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+    pub fn with_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+    // the format the texture is created with
+    pub fn texture_format(&self) -> wgpu::TextureFormat {
+        match self.color_space {
+            ColorSpace::Srgb => self.format.add_srgb_suffix(),
+            ColorSpace::Linear => self.format.remove_srgb_suffix(),
+        }
+    }
+    pub fn create_sampler(&self, render_context: &render_context::RenderContext, label: Option<&str>) -> Result<wgpu::Sampler, AssetError> {
+        let (mag_filter, min_filter, mipmap_filter, anisotropy_clamp) =
+            self.filtering.unwrap_or(render_context.texture_filtering).validate()?;
+        Ok(render_context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter,
+            min_filter,
+            mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
+        }))
+    }
+    /// Converts rgba8 pixels to the texture format of the options.
+    /// Fails for formats pixels cannot be converted to, and for an srgb color space on a format without an srgb variant.
+    pub fn convert_pixels(&self, rgba: &[u8]) -> Result<Vec<u8>, AssetError> {
+        let format = self.texture_format();
+        let srgb_to_linear = |value: u8| {
+            let value = value as f32 / 255.0;
+            if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+        };
+        let pixels = match format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => rgba.to_vec(),
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                rgba.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect()
+            }
+            wgpu::TextureFormat::R8Unorm | wgpu::TextureFormat::Rg8Unorm => {
+                if self.color_space != ColorSpace::Linear {
+                    return Err(format!("{:?} has no srgb variant, load it with a linear color space", format).into());
+                }
+                let channels = if format == wgpu::TextureFormat::R8Unorm { 1 } else { 2 };
+                rgba.chunks_exact(4).flat_map(|p| p[..channels].to_vec()).collect()
+            }
+            wgpu::TextureFormat::Rgba16Float => rgba
+                .chunks_exact(4)
+                .flat_map(|p| {
+                    let value = |i: usize| match self.color_space {
+                        // alpha is never encoded
+                        ColorSpace::Srgb if i < 3 => srgb_to_linear(p[i]),
+                        _ => p[i] as f32 / 255.0,
+                    };
+                    [0, 1, 2, 3].map(|i| half::f16::from_f32(value(i)))
+                })
+                .flat_map(|value| value.to_le_bytes())
+                .collect(),
+            _ => return Err(format!("Textures cannot be loaded as {:?}", format).into()),
+        };
+        Ok(pixels)
+    }
+}
+
+pub const PLACEHOLDER_COLOR: [u8; 4] = [128, 128, 128, 255];
+
 impl MyTexture {
     fn load_image_from_file_path(file_path: &str) -> Result<image::ImageBuffer<Rgba<u8>, Vec<u8>>, image::ImageError> {
         let img = image::open(file_path)?;
         Ok(img.to_rgba8())
     }
-    fn load_image_from_text_character(character: char, font_file_path: String) -> image::ImageBuffer<Rgba<u8>, Vec<u8>> {
-        let mut fonts = FONTS.lock().unwrap();
-        let font = fonts.entry(font_file_path.clone()).or_insert_with(|| {
-            let font_data = std::fs::read(font_file_path).unwrap();
-            Font::try_from_vec(font_data).unwrap()
-        });
+    // the font stays loaded while a handle to it is kept, hold one while loading many characters
+    fn load_image_from_text_character(
+        character: char,
+        font_file_path: &str,
+        assets: &AssetServer,
+    ) -> image::ImageBuffer<Rgba<u8>, Vec<u8>> {
+        let font = assets.read::<Font<'static>>(font_file_path.to_string()).expect_loaded();
         let scale = rusttype::Scale::uniform(1024.0);
         let glyph = font.glyph(character).scaled(scale).positioned(point(0.0, 0.0));
         let bounding_box = glyph.pixel_bounding_box().unwrap();
@@ -43,58 +221,114 @@ impl MyTexture {
     }
     pub fn load(
         texture_source: TextureSource,
+        options: &TextureOptions,
         render_context: &render_context::RenderContext,
         label: Option<&str>,
-    ) -> Result<Self, image::ImageError> {
+    ) -> Result<Self, AssetError> {
+        let data = Self::read(&texture_source, &render_context.assets)?;
+        Self::from_data(data, options, render_context, label)
+    }
+    // reads and decodes the files of the source, without the gpu so it can run on a worker thread
+    pub fn read(texture_source: &TextureSource, assets: &AssetServer) -> Result<TextureData, image::ImageError> {
+        if let TextureSource::FilePath(file_path) = texture_source
+            && CompressedImage::is_container(file_path)
+        {
+            let image = CompressedImage::open(file_path)?;
+            if image.size.depth_or_array_layers != 1 {
+                return Err(decoding_error(file_path, format!("{} holds more than one image, cubes are loaded as a CubeTexture", file_path)));
+            }
+            return Ok(TextureData::Compressed(image));
+        }
+        let levels = match texture_source {
+            TextureSource::FilePath(file_path) => vec![Self::load_image_from_file_path(file_path)?],
+            TextureSource::TextCharacter { character, font_file_path } => vec![Self::load_image_from_text_character(*character, font_file_path, assets)],
+            TextureSource::SolidColor(color) => vec![image::ImageBuffer::from_pixel(1, 1, Rgba(*color))],
+            TextureSource::Pixels { width, height, rgba, .. } => vec![image::ImageBuffer::from_raw(*width, *height, rgba.to_vec()).unwrap()],
+            TextureSource::MipChain(file_paths) => {
+                if file_paths.is_empty() {
+                    return Err(decoding_error("mip chain", "A mip chain needs at least one level".to_string()));
+                }
+                file_paths.iter().map(|file_path| Self::load_image_from_file_path(file_path)).collect::<Result<Vec<_>, _>>()?
+            }
+        };
+        let (width, height) = levels[0].dimensions();
+        for (level, img) in levels.iter().enumerate() {
+            let expected = ((width >> level).max(1), (height >> level).max(1));
+            if img.dimensions() != expected {
+                return Err(decoding_error(
+                    "mip chain",
+                    format!("Mip level {} of {:?} is {:?} instead of {:?}", level, texture_source, img.dimensions(), expected),
+                ));
+            }
+        }
+        Ok(TextureData::Levels(levels))
+    }
+    // uploads what read returned
+    pub fn from_data(
+        data: TextureData,
+        options: &TextureOptions,
+        render_context: &render_context::RenderContext,
+        label: Option<&str>,
+    ) -> Result<Self, AssetError> {
         let device = &render_context.device;
         let queue = &render_context.queue;
-        let img = match texture_source {
-            TextureSource::FilePath(ref file_path) => Self::load_image_from_file_path(file_path)?,
-            TextureSource::TextCharacter { character, font_file_path } => Self::load_image_from_text_character(character, font_file_path),
+        let levels = match data {
+            TextureData::Compressed(image) => {
+                let texture = image.create_texture(render_context, options, label)?;
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let sampler = options.create_sampler(render_context, label)?;
+                return Ok(Self { texture, view, sampler });
+            }
+            TextureData::Levels(levels) => levels,
         };
-        let dimensions = img.dimensions();
+        let dimensions = levels[0].dimensions();
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
+        // a single image gets the full chain generated from it
+        let generate = levels.len() == 1;
+        let mip_level_count = if generate { mip_level_count(dimensions.0, dimensions.1) } else { levels.len() as u32 };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            format: options.texture_format(),
+            usage: if generate {
+                MIPMAP_USAGES | wgpu::TextureUsages::COPY_DST
+            } else {
+                wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+            },
             view_formats: &[],
         });
 
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                aspect: wgpu::TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            &img,
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
+        for (level, img) in levels.iter().enumerate() {
+            let level_size = size.mip_level_size(level as u32, wgpu::TextureDimension::D2);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &options.convert_pixels(img)?,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(options.texture_format().block_copy_size(None).unwrap() * level_size.width),
+                    rows_per_image: Some(level_size.height),
+                },
+                level_size,
+            );
+        }
+        if generate {
+            generate_mipmaps(render_context, &texture);
+        }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        let sampler = options.create_sampler(render_context, label)?;
 
         Ok(Self {
             texture,
@@ -103,9 +337,34 @@ impl MyTexture {
         })
     }
     
+    // a gray pixel, drawn in place of textures that are still loading or failed to load
+    pub fn placeholder(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let size = wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Placeholder Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            &PLACEHOLDER_COLOR,
+            wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(4), rows_per_image: Some(1) },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        Self { texture, view, sampler }
+    }
+
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float; // 1.
     
-    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+    // the sample count has to match the color targets it is used with
+    pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, sample_count: u32, label: &str) -> Self {
         let size = wgpu::Extent3d { // 2.
             width: config.width.max(1),
             height: config.height.max(1),
@@ -115,17 +374,28 @@ impl MyTexture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT // 3.
-                | wgpu::TextureUsages::TEXTURE_BINDING,
+            // the gl backend renders nothing into a multisampled depth texture that can also be sampled
+            usage: if sample_count == 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT // 3.
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            },
             view_formats: &[],
         };
         let texture = device.create_texture(&desc);
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(
+        let sampler = Self::create_comparison_sampler(device);
+        Self { texture, view, sampler }
+    }
+
+    // compares against the stored depth, with linear filtering the result is already blended between four texels
+    pub fn create_comparison_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(
             &wgpu::SamplerDescriptor { // 4.
                 address_mode_u: wgpu::AddressMode::ClampToEdge,
                 address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -138,12 +408,35 @@ impl MyTexture {
                 lod_max_clamp: 100.0,
                 ..Default::default()
             }
-        );
-        Self { texture, view, sampler }
+        )
     }
 }
 
 
-lazy_static!{
-    static ref FONTS: Mutex<HashMap<String, Font<'static>>> = Mutex::new(HashMap::new());
-}
\ No newline at end of file
+// textures are shared by every renderable with the same source and options
+impl Asset for MyTexture {
+    type Key = (TextureSource, TextureOptions);
+    type Data = TextureData;
+    fn read((texture_source, _options): &Self::Key, assets: &AssetServer) -> Result<TextureData, AssetError> {
+        Ok(Self::read(texture_source, assets)?)
+    }
+    fn create((_texture_source, options): &Self::Key, data: TextureData, render_context: &render_context::RenderContext) -> Result<Self, AssetError> {
+        Self::from_data(data, options, render_context, Some("texture"))
+    }
+    fn reads_files((texture_source, _options): &Self::Key) -> bool {
+        matches!(texture_source, TextureSource::FilePath(_) | TextureSource::TextCharacter { .. } | TextureSource::MipChain(_))
+    }
+}
+
+// fonts need no gpu, they are read on the worker that draws their characters
+impl Asset for Font<'static> {
+    type Key = String;
+    type Data = Self;
+    fn read(font_file_path: &String, _assets: &AssetServer) -> Result<Self, AssetError> {
+        let font_data = std::fs::read(font_file_path)?;
+        Ok(Font::try_from_vec(font_data).ok_or("not a font file")?)
+    }
+    fn create(_font_file_path: &String, font: Self, _render_context: &render_context::RenderContext) -> Result<Self, AssetError> {
+        Ok(font)
+    }
+}
@@ -0,0 +1,80 @@
+// downsamples the bright parts of the scene into a mip chain, blurs them on the way back up and adds them to the scene
+struct BloomUniform {
+    // x is the threshold, y the soft knee, z the intensity, w the upsample radius in texels
+    settings: vec4<f32>,
+};
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+@group(0) @binding(2)
+var<uniform> bloom: BloomUniform;
+// the first mip of the chain, only bound for the composite
+@group(0) @binding(3)
+var t_bloom: texture_2d<f32>;
+
+fn sample_offset(t: texture_2d<f32>, uv: vec2<f32>, offset: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t));
+    return textureSampleLevel(t, s_input, uv + offset * texel, 0.0).rgb;
+}
+
+// the 13 tap filter from Jimenez's Call of Duty presentation, it keeps small bright spots from flickering
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let a = sample_offset(t_input, uv, vec2<f32>(-2.0, -2.0));
+    let b = sample_offset(t_input, uv, vec2<f32>(0.0, -2.0));
+    let c = sample_offset(t_input, uv, vec2<f32>(2.0, -2.0));
+    let d = sample_offset(t_input, uv, vec2<f32>(-2.0, 0.0));
+    let e = sample_offset(t_input, uv, vec2<f32>(0.0, 0.0));
+    let f = sample_offset(t_input, uv, vec2<f32>(2.0, 0.0));
+    let g = sample_offset(t_input, uv, vec2<f32>(-2.0, 2.0));
+    let h = sample_offset(t_input, uv, vec2<f32>(0.0, 2.0));
+    let i = sample_offset(t_input, uv, vec2<f32>(2.0, 2.0));
+    let j = sample_offset(t_input, uv, vec2<f32>(-1.0, -1.0));
+    let k = sample_offset(t_input, uv, vec2<f32>(1.0, -1.0));
+    let l = sample_offset(t_input, uv, vec2<f32>(-1.0, 1.0));
+    let m = sample_offset(t_input, uv, vec2<f32>(1.0, 1.0));
+    return e * 0.125 + (a + c + g + i) * 0.03125 + (b + d + f + h) * 0.0625 + (j + k + l + m) * 0.125;
+}
+
+// 3x3 tent filter
+fn upsample(t: texture_2d<f32>, uv: vec2<f32>) -> vec3<f32> {
+    let r = bloom.settings.w;
+    var sum = sample_offset(t, uv, vec2<f32>(0.0, 0.0)) * 4.0;
+    sum += (sample_offset(t, uv, vec2<f32>(-r, 0.0)) + sample_offset(t, uv, vec2<f32>(r, 0.0))
+        + sample_offset(t, uv, vec2<f32>(0.0, -r)) + sample_offset(t, uv, vec2<f32>(0.0, r))) * 2.0;
+    sum += sample_offset(t, uv, vec2<f32>(-r, -r)) + sample_offset(t, uv, vec2<f32>(r, -r))
+        + sample_offset(t, uv, vec2<f32>(-r, r)) + sample_offset(t, uv, vec2<f32>(r, r));
+    return sum / 16.0;
+}
+
+// keeps what is brighter than the threshold, with a quadratic curve of the width of the knee around it
+fn threshold(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = bloom.settings.y;
+    var soft = clamp(brightness - bloom.settings.x + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-5);
+    let contribution = max(soft, brightness - bloom.settings.x) / max(brightness, 1e-5);
+    return color * contribution;
+}
+
+@fragment
+fn fs_prefilter(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(threshold(downsample(in.uv)), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// blended onto the larger mip
+@fragment
+fn fs_upsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(upsample(t_input, in.uv), 1.0);
+}
+
+@fragment
+fn fs_composite(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let scene = textureSampleLevel(t_input, s_input, in.uv, 0.0);
+    return vec4<f32>(scene.rgb + upsample(t_bloom, in.uv) * bloom.settings.z, scene.a);
+}
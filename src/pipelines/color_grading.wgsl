@@ -0,0 +1,36 @@
+// looks the scene color up in a 3d lut. The lut is indexed by log encoded colors so that it covers hdr values,
+// it stores its outputs in the same encoding. Lut3d does the encoding on the cpu side
+const MIN_EV: f32 = -14.0;
+const MAX_EV: f32 = 6.0;
+
+struct ColorGradingUniform {
+    // x is how much of the graded color replaces the original one
+    settings: vec4<f32>,
+};
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+@group(0) @binding(2)
+var<uniform> grading: ColorGradingUniform;
+@group(0) @binding(3)
+var t_lut: texture_3d<f32>;
+
+fn encode(color: vec3<f32>) -> vec3<f32> {
+    let ev = log2(max(color, vec3<f32>(exp2(MIN_EV))));
+    return clamp((ev - MIN_EV) / (MAX_EV - MIN_EV), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn decode(encoded: vec3<f32>) -> vec3<f32> {
+    return exp2(encoded * (MAX_EV - MIN_EV) + MIN_EV);
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_input, s_input, in.uv, 0.0);
+    let size = f32(textureDimensions(t_lut).x);
+    // the first and last texel centers are the ends of the range
+    let coordinates = encode(color.rgb) * (size - 1.0) / size + 0.5 / size;
+    let graded = decode(textureSampleLevel(t_lut, s_input, coordinates, 0.0).rgb);
+    return vec4<f32>(mix(color.rgb, graded, grading.settings.x), color.a);
+}
@@ -11,18 +11,30 @@ struct VertexInput {
     @location(1) tex_coords: vec2<f32>,
 };
 
+struct InstanceInput {
+    @location(10) model_0: vec4<f32>,
+    @location(11) model_1: vec4<f32>,
+    @location(12) model_2: vec4<f32>,
+    @location(13) model_3: vec4<f32>,
+    @location(14) tint: vec4<f32>,
+};
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
 };
 
 @vertex
 fn vs_main(
     model: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
+    let instance_model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.clip_position = camera.projection * camera.view * vec4<f32>(model.position, 1.0); // 2.
+    out.tint = instance.tint;
+    out.clip_position = camera.projection * camera.view * instance_model * vec4<f32>(model.position, 1.0); // 2.
     return out;
 }
 
@@ -33,5 +45,5 @@ var s_diffuse: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
 }
\ No newline at end of file
@@ -1,13 +1,12 @@
-use std::any::TypeId;
+use std::{any::TypeId, collections::HashMap, sync::Arc};
 
-use wgpu::{BindGroupLayout, RenderPipeline};
 
-use crate::{my_pipeline::{MyPipeline, PipelineBuilder}, my_texture::MyTexture, render_context::{self, RenderContext}, render_passes::opauqe3d_render_pass::Opaque3DRenderPass, vertex::Vertex};
+use crate::{assets::{Handle, WeakHandle}, instance::InstanceRaw, my_pipeline::{MyPipeline, PipelineBuilder, ShaderSource}, my_texture::MyTexture, render_context::RenderContext, render_passes::opauqe3d_render_pass::Opaque3DRenderPass, vertex::{Vertex, VertexLayout}};
 
 pub struct DefaultPipeline;
 
 impl DefaultPipeline {
-    fn create_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    pub(crate) fn create_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
                 wgpu::BindGroupLayoutEntry {
@@ -49,6 +48,20 @@ impl DefaultPipeline {
             label: Some("diffuse_bind_group"),
         })
     }
+    // shared by every renderable with the same texture, so that they can be batched.
+    // The bind group is forgotten once every handle to its texture was dropped. Textures that are loading or failed
+    // get the placeholder, ask again once the handle stopped loading
+    pub fn get_texture_bind_group(render_context: &RenderContext, texture: &Handle<MyTexture>) -> Arc<wgpu::BindGroup> {
+        let Some(loaded) = texture.get() else {
+            return render_context.placeholder_texture_bind_group.clone();
+        };
+        let bind_groups = render_context.resources.get::<TextureBindGroups>();
+        let mut bind_groups = bind_groups.lock().unwrap();
+        bind_groups.retain(|_, (texture, _)| texture.is_alive());
+        bind_groups.entry(texture.id()).or_insert_with(|| {
+            (texture.downgrade(), Arc::new(Self::create_texture_bind_group(&render_context.device, &loaded)))
+        }).1.clone()
+    }
     // bind groups like textures should be per-model
     // bind groups like instance buffers should be per-instance
 
@@ -76,18 +89,19 @@ impl DefaultPipeline {
 
     pub fn create_bind_groups<'a>(
         render_context: &'a RenderContext,
-        texture: &MyTexture
+        texture_bind_group: &'a wgpu::BindGroup,
     ) -> Vec<&'a wgpu::BindGroup> {
-        let texture_bind_group = Self::create_texture_bind_group(&render_context.device, texture);
         let camera_bind_group = &render_context.camera_bind_group;
         vec![texture_bind_group, camera_bind_group]
     }
 }
 
 impl PipelineBuilder for DefaultPipeline {
+    fn vertex_layout(&self) -> VertexLayout {
+        Vertex::LAYOUT
+    }
     fn build_pipeline(&self, render_context: &RenderContext) -> MyPipeline {
         let device = &render_context.device;
-        let config = &render_context.config;
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -96,10 +110,11 @@ impl PipelineBuilder for DefaultPipeline {
                 push_constant_ranges: &[],
             });
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("default.wgsl").into()),
-        });
+        let vertex_attributes = self.vertex_layout().attributes();
+        let shader = render_context.load_asset::<wgpu::ShaderModule>(ShaderSource::Embedded {
+            label: "Shader",
+            source: include_str!("default.wgsl"),
+        }).expect_loaded();
 
         let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
@@ -107,7 +122,7 @@ impl PipelineBuilder for DefaultPipeline {
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: Some("vs_main"), // 1.
-                buffers: &[Vertex::desc()],   // 2.
+                buffers: &[self.vertex_layout().desc(&vertex_attributes), InstanceRaw::desc()],   // 2.
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             fragment: Some(wgpu::FragmentState {
@@ -116,7 +131,7 @@ impl PipelineBuilder for DefaultPipeline {
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     // 4.
-                    format: config.format,
+                    format: RenderContext::HDR_FORMAT,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -142,7 +157,7 @@ impl PipelineBuilder for DefaultPipeline {
                 bias: wgpu::DepthBiasState::default(),
             }), // 1.
             multisample: wgpu::MultisampleState {
-                count: 1,                         // 2.
+                count: render_context.sample_count(), // 2.
                 mask: !0,                         // 3.
                 alpha_to_coverage_enabled: false, // 4.
             },
@@ -155,3 +170,6 @@ impl PipelineBuilder for DefaultPipeline {
         }
     }
 }
+
+// keyed by the id of the texture handle, one map per render context
+type TextureBindGroups = HashMap<u64, (WeakHandle<MyTexture>, Arc<wgpu::BindGroup>)>;
@@ -0,0 +1,48 @@
+// fast approximate anti-aliasing, after Timothy Lottes' FXAA without the quality presets
+struct FxaaUniform {
+    // x is the longest blur along an edge in texels, y the part of the local luma that reduces the blur, z the least reduction
+    settings: vec4<f32>,
+};
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+@group(0) @binding(2)
+var<uniform> fxaa: FxaaUniform;
+
+// edges are found on roughly tonemapped values, hdr highlights would dominate otherwise
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color / (1.0 + color), vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn sample_color(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(t_input, s_input, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_input));
+    let center = textureSampleLevel(t_input, s_input, in.uv, 0.0);
+    let luma_nw = luma(sample_color(in.uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_color(in.uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample_color(in.uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample_color(in.uv + vec2<f32>(1.0, 1.0) * texel));
+    let luma_m = luma(center.rgb);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // along the edge, perpendicular to the luma gradient
+    var direction = vec2<f32>(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * fxaa.settings.y, fxaa.settings.z);
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2<f32>(-fxaa.settings.x), vec2<f32>(fxaa.settings.x)) * texel;
+
+    let inner = 0.5 * (sample_color(in.uv + direction * (1.0 / 3.0 - 0.5)) + sample_color(in.uv + direction * (2.0 / 3.0 - 0.5)));
+    let outer = inner * 0.5 + 0.25 * (sample_color(in.uv - direction * 0.5) + sample_color(in.uv + direction * 0.5));
+    // the wider blur crossed another edge
+    let luma_outer = luma(outer);
+    if luma_outer < luma_min || luma_outer > luma_max {
+        return vec4<f32>(inner, center.a);
+    }
+    return vec4<f32>(outer, center.a);
+}
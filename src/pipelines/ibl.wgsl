@@ -0,0 +1,151 @@
+// the passes that prepare image based lighting. Each draws one face of a cube texture, or the brdf lookup table
+struct FaceUniform {
+    face: u32,
+    // of the prefiltered level that is drawn
+    roughness: f32,
+    // widths of the first level of the source and of the level that is drawn
+    source_size: f32,
+    target_size: f32,
+}
+@group(0) @binding(0)
+var t_equirect: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var<uniform> face: FaceUniform;
+@group(0) @binding(3)
+var t_source: texture_cube<f32>;
+
+const PI: f32 = 3.14159265;
+const SAMPLE_COUNT: u32 = 256u;
+
+// the direction through a point of a face, in the order and orientation of the layers of cube textures
+fn face_direction(index: u32, uv: vec2<f32>) -> vec3<f32> {
+    let st = uv * 2.0 - 1.0;
+    switch index {
+        case 0u: { return normalize(vec3<f32>(1.0, -st.y, -st.x)); }
+        case 1u: { return normalize(vec3<f32>(-1.0, -st.y, st.x)); }
+        case 2u: { return normalize(vec3<f32>(st.x, 1.0, st.y)); }
+        case 3u: { return normalize(vec3<f32>(st.x, -1.0, -st.y)); }
+        case 4u: { return normalize(vec3<f32>(st.x, -st.y, 1.0)); }
+        default: { return normalize(vec3<f32>(-st.x, -st.y, -1.0)); }
+    }
+}
+
+@fragment
+fn fs_equirect(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let direction = face_direction(face.face, in.uv);
+    // the middle of the panorama is in front of the default camera, which looks down -z. The sampler repeats around the seam
+    let uv = vec2<f32>(0.5 + atan2(direction.x, -direction.z) / (2.0 * PI), acos(clamp(direction.y, -1.0, 1.0)) / PI);
+    return vec4<f32>(textureSampleLevel(t_equirect, s_source, uv, 0.0).rgb, 1.0);
+}
+
+// evenly spread points of the unit square
+fn hammersley(i: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(SAMPLE_COUNT), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+// turns a direction around +z into the same direction around normal
+fn tangent_to_world(direction: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0), abs(normal.z) < 0.999);
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return tangent * direction.x + bitangent * direction.y + normal * direction.z;
+}
+
+// a half vector around normal, more of them where the ggx distribution is larger
+fn importance_sample_ggx(xi: vec2<f32>, normal: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return tangent_to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), normal);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// the source level with texels about as large as the solid angle a sample stands for, so few samples do not alias
+fn source_level(pdf: f32) -> f32 {
+    let sample_angle = 1.0 / (f32(SAMPLE_COUNT) * pdf + 1e-4);
+    let texel_angle = 4.0 * PI / (6.0 * face.source_size * face.source_size);
+    return max(0.5 * log2(sample_angle / texel_angle) + 1.0, 0.0);
+}
+
+@fragment
+fn fs_irradiance(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let normal = face_direction(face.face, in.uv);
+    var sum = vec3<f32>(0.0);
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        // cosine weighted directions, the average of their radiance is the irradiance divided by pi,
+        // which is the diffuse light of a white surface
+        let xi = hammersley(i);
+        let phi = 2.0 * PI * xi.x;
+        let cos_theta = sqrt(1.0 - xi.y);
+        let sin_theta = sqrt(xi.y);
+        let direction = tangent_to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), normal);
+        sum += textureSampleLevel(t_source, s_source, direction, source_level(cos_theta / PI)).rgb;
+    }
+    return vec4<f32>(sum / f32(SAMPLE_COUNT), 1.0);
+}
+
+@fragment
+fn fs_prefilter(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let normal = face_direction(face.face, in.uv);
+    // a larger source is not sampled finer than the level that is drawn
+    let base_level = max(log2(face.source_size / face.target_size), 0.0);
+    if face.roughness == 0.0 {
+        return vec4<f32>(textureSampleLevel(t_source, s_source, normal, base_level).rgb, 1.0);
+    }
+    // the view is assumed to be along the normal, which loses the stretched reflections at grazing angles
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let half_direction = importance_sample_ggx(hammersley(i), normal, face.roughness);
+        let n_dot_h = dot(normal, half_direction);
+        let light_direction = 2.0 * n_dot_h * half_direction - normal;
+        let n_dot_l = dot(normal, light_direction);
+        if n_dot_l > 0.0 {
+            // with the view along the normal the pdf of the light direction is the distribution over four
+            let pdf = distribution_ggx(n_dot_h, face.roughness) / 4.0;
+            let level = max(source_level(pdf), base_level);
+            sum += textureSampleLevel(t_source, s_source, light_direction, level).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    return vec4<f32>(sum / weight, 1.0);
+}
+
+fn geometry_schlick_ggx_ibl(n_dot_x: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+// the scale and bias of f0 in the specular light, for n dot v along u and the roughness along v
+@fragment
+fn fs_brdf(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let n_dot_v = max(in.uv.x, 1e-4);
+    let roughness = in.uv.y;
+    let view_direction = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let normal = vec3<f32>(0.0, 0.0, 1.0);
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let half_direction = importance_sample_ggx(hammersley(i), normal, roughness);
+        let v_dot_h = max(dot(view_direction, half_direction), 0.0);
+        let light_direction = 2.0 * v_dot_h * half_direction - view_direction;
+        let n_dot_l = light_direction.z;
+        if n_dot_l > 0.0 {
+            let geometry = geometry_schlick_ggx_ibl(n_dot_v, roughness) * geometry_schlick_ggx_ibl(n_dot_l, roughness);
+            let visibility = geometry * v_dot_h / (max(half_direction.z, 1e-4) * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+    return vec4<f32>(scale / f32(SAMPLE_COUNT), bias / f32(SAMPLE_COUNT), 0.0, 1.0);
+}
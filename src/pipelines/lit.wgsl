@@ -0,0 +1,185 @@
+// Vertex shader
+struct CameraUniform{
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    position: vec4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+const MAX_LIGHTS: u32 = 16u;
+const DIRECTIONAL: f32 = 0.0;
+const POINT: f32 = 1.0;
+const SPOT: f32 = 2.0;
+
+struct Light {
+    // w is the kind of light
+    position: vec4<f32>,
+    direction: vec4<f32>,
+    // rgb is the color times the intensity, a the range
+    color: vec4<f32>,
+    // cosines of the inner and outer angles of spot lights, then the first shadow layer (-1 without shadows) and the number of layers
+    cone: vec4<f32>,
+}
+
+struct LightsUniform {
+    // w is the intensity of the environment
+    ambient: vec4<f32>,
+    // y is 1 if the environment replaces the ambient color
+    count: vec4<u32>,
+    lights: array<Light, MAX_LIGHTS>,
+}
+@group(2) @binding(0)
+var<uniform> lights: LightsUniform;
+
+const MAX_SHADOW_LAYERS: u32 = 8u;
+
+struct ShadowUniform {
+    view_projections: array<mat4x4<f32>, MAX_SHADOW_LAYERS>,
+    // view space depths where the cascades end
+    cascade_splits: vec4<f32>,
+    // x is the size of a texel in uv, y the depth bias and z the normal bias
+    settings: vec4<f32>,
+}
+@group(2) @binding(1)
+var<uniform> shadows: ShadowUniform;
+@group(2) @binding(2)
+var shadow_map: texture_depth_2d_array;
+@group(2) @binding(3)
+var shadow_sampler: sampler_comparison;
+
+// the environment, black without one
+@group(2) @binding(4)
+var irradiance_map: texture_cube<f32>;
+@group(2) @binding(7)
+var environment_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(10) model_0: vec4<f32>,
+    @location(11) model_1: vec4<f32>,
+    @location(12) model_2: vec4<f32>,
+    @location(13) model_3: vec4<f32>,
+    @location(14) tint: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) world_normal: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let instance_model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let world_position = instance_model * vec4<f32>(model.position, 1.0);
+    // the cofactor matrix keeps normals perpendicular under non-uniform scale, like the inverse transpose up to a factor
+    let m = mat3x3<f32>(instance_model[0].xyz, instance_model[1].xyz, instance_model[2].xyz);
+    let normal_matrix = mat3x3<f32>(cross(m[1], m[2]), cross(m[2], m[0]), cross(m[0], m[1]));
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.tint = instance.tint;
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * model.normal;
+    out.clip_position = camera.projection * camera.view * world_position;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+const SHININESS: f32 = 32.0;
+const SPECULAR_STRENGTH: f32 = 0.5;
+
+// 1 where the light reaches the position, 0 in its shadow. cone.z is the first shadow layer of the light, cone.w the number of layers
+fn shadow_factor(light: Light, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if light.cone.z < 0.0 {
+        return 1.0;
+    }
+    var layer = u32(light.cone.z);
+    if light.position.w == DIRECTIONAL {
+        // the first cascade that reaches past the position
+        let view_depth = -(camera.view * vec4<f32>(world_position, 1.0)).z;
+        let num_cascades = u32(light.cone.w);
+        if view_depth > shadows.cascade_splits[num_cascades - 1u] {
+            return 1.0;
+        }
+        for (var i = 0u; i + 1u < num_cascades; i++) {
+            if view_depth > shadows.cascade_splits[i] {
+                layer += 1u;
+            }
+        }
+    }
+    let clip = shadows.view_projections[layer] * vec4<f32>(world_position + normal * shadows.settings.z, 1.0);
+    let ndc = clip.xyz / clip.w;
+    if any(abs(ndc.xy) > vec2<f32>(1.0)) || ndc.z > 1.0 || ndc.z < 0.0 {
+        return 1.0;
+    }
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    // 3x3 percentage closer filtering, every lookup is filtered between four texels by the sampler
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadows.settings.x;
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, layer, ndc.z - shadows.settings.y);
+        }
+    }
+    return lit / 9.0;
+}
+
+// smooth fall off that reaches zero at the range of the light
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let ratio = distance / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let albedo = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+    let normal = normalize(in.world_normal);
+    let view_direction = normalize(camera.position.xyz - in.world_position);
+    // the light of the environment from the direction of the normal, if there is one
+    var ambient = lights.ambient.rgb;
+    if lights.count.y != 0u {
+        ambient = textureSampleLevel(irradiance_map, environment_sampler, normal, 0.0).rgb * lights.ambient.w;
+    }
+    var color = ambient * albedo.rgb;
+    for (var i = 0u; i < min(lights.count.x, MAX_LIGHTS); i++) {
+        let light = lights.lights[i];
+        var light_direction: vec3<f32>;
+        var radiance = light.color.rgb;
+        if light.position.w == DIRECTIONAL {
+            light_direction = -light.direction.xyz;
+        } else {
+            let to_light = light.position.xyz - in.world_position;
+            let distance = length(to_light);
+            light_direction = to_light / distance;
+            radiance *= attenuation(distance, light.color.a);
+            if light.position.w == SPOT {
+                let cos_angle = dot(-light_direction, light.direction.xyz);
+                radiance *= smoothstep(light.cone.y, light.cone.x, cos_angle);
+            }
+        }
+        radiance *= shadow_factor(light, in.world_position, normal);
+        let diffuse = max(dot(normal, light_direction), 0.0);
+        let half_direction = normalize(light_direction + view_direction);
+        var specular = pow(max(dot(normal, half_direction), 0.0), SHININESS) * SPECULAR_STRENGTH;
+        // no highlights on the side facing away from the light
+        specular *= step(0.0, dot(normal, light_direction));
+        color += radiance * (diffuse * albedo.rgb + specular);
+    }
+    return vec4<f32>(color, albedo.a);
+}
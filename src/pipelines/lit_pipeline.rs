@@ -0,0 +1,89 @@
+use std::any::TypeId;
+
+use crate::{instance::InstanceRaw, my_pipeline::{MyPipeline, PipelineBuilder, ShaderSource}, my_texture::MyTexture, pipelines::default_pipeline::DefaultPipeline, render_context::RenderContext, render_passes::opauqe3d_render_pass::Opaque3DRenderPass, vertex::{Vertex, VertexAttribute, VertexLayout}};
+
+// the default pipeline with Blinn-Phong shading from the lights of the state. Meshes drawn with it need normals
+pub struct LitPipeline;
+
+impl LitPipeline {
+    pub fn create_bind_groups<'a>(
+        render_context: &'a RenderContext,
+        texture_bind_group: &'a wgpu::BindGroup,
+    ) -> Vec<&'a wgpu::BindGroup> {
+        vec![texture_bind_group, &render_context.camera_bind_group, &render_context.lights_bind_group]
+    }
+}
+
+impl PipelineBuilder for LitPipeline {
+    fn vertex_layout(&self) -> VertexLayout {
+        Vertex::LAYOUT.with(VertexAttribute::Normal)
+    }
+    fn build_pipeline(&self, render_context: &RenderContext) -> MyPipeline {
+        let device = &render_context.device;
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Lit Pipeline Layout"),
+                bind_group_layouts: &[
+                    &DefaultPipeline::create_texture_bind_group_layout(device),
+                    &render_context.camera_bind_group_layout,
+                    &render_context.lights_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let vertex_attributes = self.vertex_layout().attributes();
+        let shader = render_context.load_asset::<wgpu::ShaderModule>(ShaderSource::Embedded {
+            label: "Lit Shader",
+            source: include_str!("lit.wgsl"),
+        }).expect_loaded();
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Lit Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[self.vertex_layout().desc(&vertex_attributes), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: RenderContext::HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: MyTexture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: render_context.sample_count(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+        MyPipeline{
+            pipeline: render_pipeline,
+            render_pass_builder: TypeId::of::<Opaque3DRenderPass>(),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+// one mip level from the level above it, the linear sampler averages the texels under each pixel.
+// For srgb formats the average is taken in linear space and encoded again when written
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_source, s_source, in.uv);
+}
@@ -1,3 +1,8 @@
 pub mod default_pipeline;
+pub mod lit_pipeline;
+pub mod pbr_pipeline;
+pub mod procedural_sky_pipeline;
 pub mod skybox_pipeline;
+pub mod tonemap_pipeline;
+pub mod transparent_pipeline;
 pub mod ui_pipeline;
\ No newline at end of file
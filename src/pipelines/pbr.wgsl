@@ -0,0 +1,294 @@
+// Vertex shader
+struct CameraUniform{
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    position: vec4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+const MAX_LIGHTS: u32 = 16u;
+const DIRECTIONAL: f32 = 0.0;
+const POINT: f32 = 1.0;
+const SPOT: f32 = 2.0;
+
+struct Light {
+    // w is the kind of light
+    position: vec4<f32>,
+    direction: vec4<f32>,
+    // rgb is the color times the intensity, a the range
+    color: vec4<f32>,
+    // cosines of the inner and outer angles of spot lights, then the first shadow layer (-1 without shadows) and the number of layers
+    cone: vec4<f32>,
+}
+
+struct LightsUniform {
+    // w is the intensity of the environment
+    ambient: vec4<f32>,
+    // y is 1 if the environment replaces the ambient color
+    count: vec4<u32>,
+    lights: array<Light, MAX_LIGHTS>,
+}
+@group(2) @binding(0)
+var<uniform> lights: LightsUniform;
+
+const MAX_SHADOW_LAYERS: u32 = 8u;
+
+struct ShadowUniform {
+    view_projections: array<mat4x4<f32>, MAX_SHADOW_LAYERS>,
+    // view space depths where the cascades end
+    cascade_splits: vec4<f32>,
+    // x is the size of a texel in uv, y the depth bias and z the normal bias
+    settings: vec4<f32>,
+}
+@group(2) @binding(1)
+var<uniform> shadows: ShadowUniform;
+@group(2) @binding(2)
+var shadow_map: texture_depth_2d_array;
+@group(2) @binding(3)
+var shadow_sampler: sampler_comparison;
+
+// the environment, black without one
+const PREFILTERED_LEVELS: u32 = 5u;
+@group(2) @binding(4)
+var irradiance_map: texture_cube<f32>;
+@group(2) @binding(5)
+var prefiltered_map: texture_cube<f32>;
+@group(2) @binding(6)
+var brdf_lut: texture_2d<f32>;
+@group(2) @binding(7)
+var environment_sampler: sampler;
+
+struct MaterialUniform {
+    base_color_factor: vec4<f32>,
+    emissive_factor: vec4<f32>,
+    // metallic, roughness, normal scale, occlusion strength
+    factors: vec4<f32>,
+    // x is the alpha cutoff, negative if the material is not masked. y is 1 for double sided materials
+    alpha: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> material: MaterialUniform;
+@group(0) @binding(1)
+var t_base_color: texture_2d<f32>;
+@group(0) @binding(2)
+var s_base_color: sampler;
+@group(0) @binding(3)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(4)
+var s_metallic_roughness: sampler;
+@group(0) @binding(5)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(6)
+var s_normal: sampler;
+@group(0) @binding(7)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(8)
+var s_occlusion: sampler;
+@group(0) @binding(9)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(10)
+var s_emissive: sampler;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(10) model_0: vec4<f32>,
+    @location(11) model_1: vec4<f32>,
+    @location(12) model_2: vec4<f32>,
+    @location(13) model_3: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let instance_model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let world_position = instance_model * vec4<f32>(model.position, 1.0);
+    // the cofactor matrix keeps normals perpendicular under non-uniform scale, like the inverse transpose up to a factor
+    let m = mat3x3<f32>(instance_model[0].xyz, instance_model[1].xyz, instance_model[2].xyz);
+    let normal_matrix = mat3x3<f32>(cross(m[1], m[2]), cross(m[2], m[0]), cross(m[0], m[1]));
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * model.normal;
+    out.clip_position = camera.projection * camera.view * world_position;
+    return out;
+}
+
+const PI: f32 = 3.14159265;
+
+// builds the tangent frame from screen space derivatives, so meshes do not need tangents
+fn perturb_normal(normal: vec3<f32>, position: vec3<f32>, tex_coords: vec2<f32>, tangent_normal: vec3<f32>) -> vec3<f32> {
+    let dp1 = dpdx(position);
+    let dp2 = dpdy(position);
+    let duv1 = dpdx(tex_coords);
+    let duv2 = dpdy(tex_coords);
+    let dp2_perp = cross(dp2, normal);
+    let dp1_perp = cross(normal, dp1);
+    let tangent = dp2_perp * duv1.x + dp1_perp * duv2.x;
+    let bitangent = dp2_perp * duv1.y + dp1_perp * duv2.y;
+    let length_squared = max(dot(tangent, tangent), dot(bitangent, bitangent));
+    if length_squared < 1e-20 {
+        return normal;
+    }
+    let scale = inverseSqrt(length_squared);
+    // texture v grows downwards, the green channel of gltf normal maps points up
+    let tbn = mat3x3<f32>(tangent * scale, -bitangent * scale, normal);
+    return normalize(tbn * tangent_normal);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// like fresnel_schlick, but rough surfaces reflect less at grazing angles since the light comes from every direction
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// the light of the environment from the precomputed maps, split into the prefiltered sky and the brdf lookup table.
+// Without an environment the ambient color lights the diffuse part
+fn ambient_light(normal: vec3<f32>, view_direction: vec3<f32>, n_dot_v: f32, albedo: vec3<f32>, f0: vec3<f32>, metallic: f32, roughness: f32) -> vec3<f32> {
+    if lights.count.y == 0u {
+        return lights.ambient.rgb * albedo;
+    }
+    let fresnel = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    let irradiance = textureSampleLevel(irradiance_map, environment_sampler, normal, 0.0).rgb;
+    let diffuse = (1.0 - fresnel) * (1.0 - metallic) * albedo * irradiance;
+    let reflection = reflect(-view_direction, normal);
+    let prefiltered = textureSampleLevel(prefiltered_map, environment_sampler, reflection, roughness * f32(PREFILTERED_LEVELS - 1u)).rgb;
+    let brdf = textureSampleLevel(brdf_lut, environment_sampler, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+    let specular = prefiltered * (fresnel * brdf.x + brdf.y);
+    return (diffuse + specular) * lights.ambient.w;
+}
+
+// 1 where the light reaches the position, 0 in its shadow. cone.z is the first shadow layer of the light, cone.w the number of layers
+fn shadow_factor(light: Light, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if light.cone.z < 0.0 {
+        return 1.0;
+    }
+    var layer = u32(light.cone.z);
+    if light.position.w == DIRECTIONAL {
+        // the first cascade that reaches past the position
+        let view_depth = -(camera.view * vec4<f32>(world_position, 1.0)).z;
+        let num_cascades = u32(light.cone.w);
+        if view_depth > shadows.cascade_splits[num_cascades - 1u] {
+            return 1.0;
+        }
+        for (var i = 0u; i + 1u < num_cascades; i++) {
+            if view_depth > shadows.cascade_splits[i] {
+                layer += 1u;
+            }
+        }
+    }
+    let clip = shadows.view_projections[layer] * vec4<f32>(world_position + normal * shadows.settings.z, 1.0);
+    let ndc = clip.xyz / clip.w;
+    if any(abs(ndc.xy) > vec2<f32>(1.0)) || ndc.z > 1.0 || ndc.z < 0.0 {
+        return 1.0;
+    }
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    // 3x3 percentage closer filtering, every lookup is filtered between four texels by the sampler
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadows.settings.x;
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, layer, ndc.z - shadows.settings.y);
+        }
+    }
+    return lit / 9.0;
+}
+
+// smooth fall off that reaches zero at the range of the light
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let ratio = distance / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    // sample everything first, the derivatives need uniform control flow
+    let base_color = textureSample(t_base_color, s_base_color, in.tex_coords) * material.base_color_factor;
+    let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.tex_coords).rgb;
+    let normal_sample = textureSample(t_normal, s_normal, in.tex_coords).rgb;
+    let occlusion_sample = textureSample(t_occlusion, s_occlusion, in.tex_coords).r;
+    let emissive = textureSample(t_emissive, s_emissive, in.tex_coords).rgb * material.emissive_factor.rgb;
+    var normal = normalize(in.world_normal);
+    let tangent_normal = normalize((normal_sample * 2.0 - 1.0) * vec3<f32>(material.factors.z, material.factors.z, 1.0));
+    normal = perturb_normal(normal, in.world_position, in.tex_coords, tangent_normal);
+
+    if material.alpha.x >= 0.0 && base_color.a < material.alpha.x {
+        discard;
+    }
+    if !front_facing {
+        if material.alpha.y == 0.0 {
+            discard;
+        }
+        normal = -normal;
+    }
+
+    let metallic = material.factors.x * metallic_roughness.b;
+    let roughness = clamp(material.factors.y * metallic_roughness.g, 0.04, 1.0);
+    let occlusion = 1.0 + material.factors.w * (occlusion_sample - 1.0);
+    let albedo = base_color.rgb;
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let view_direction = normalize(camera.position.xyz - in.world_position);
+    let n_dot_v = max(dot(normal, view_direction), 1e-4);
+
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count.x, MAX_LIGHTS); i++) {
+        let light = lights.lights[i];
+        var light_direction: vec3<f32>;
+        var radiance = light.color.rgb;
+        if light.position.w == DIRECTIONAL {
+            light_direction = -light.direction.xyz;
+        } else {
+            let to_light = light.position.xyz - in.world_position;
+            let distance = length(to_light);
+            light_direction = to_light / distance;
+            radiance *= attenuation(distance, light.color.a);
+            if light.position.w == SPOT {
+                let cos_angle = dot(-light_direction, light.direction.xyz);
+                radiance *= smoothstep(light.cone.y, light.cone.x, cos_angle);
+            }
+        }
+        radiance *= shadow_factor(light, in.world_position, normal);
+        let n_dot_l = max(dot(normal, light_direction), 0.0);
+        let half_direction = normalize(light_direction + view_direction);
+        let n_dot_h = max(dot(normal, half_direction), 0.0);
+        let fresnel = fresnel_schlick(max(dot(half_direction, view_direction), 0.0), f0);
+        let specular = distribution_ggx(n_dot_h, roughness) * geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness) * fresnel
+            / (4.0 * n_dot_v * max(n_dot_l, 1e-4));
+        let diffuse = (1.0 - fresnel) * (1.0 - metallic) * albedo / PI;
+        color += (diffuse + specular) * radiance * n_dot_l;
+    }
+    color += ambient_light(normal, view_direction, n_dot_v, albedo, f0, metallic, roughness) * occlusion;
+    color += emissive;
+    return vec4<f32>(color, base_color.a);
+}
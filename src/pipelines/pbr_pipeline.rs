@@ -0,0 +1,248 @@
+use std::{any::TypeId, collections::HashMap, sync::{Arc, Weak}};
+
+
+use crate::{assets::Handle, instance::InstanceRaw, material::{AlphaMode, Material}, my_pipeline::{MyPipeline, PipelineBuilder, ShaderSource}, my_texture::{ColorSpace, MyTexture, TextureOptions, TextureSource}, render_context::RenderContext, render_passes::opauqe3d_render_pass::Opaque3DRenderPass, vertex::{Vertex, VertexAttribute, VertexLayout}};
+
+// metallic-roughness shading with a Cook-Torrance brdf, lit by the lights of the state. Meshes drawn with it need normals,
+// the tangent frame for normal maps is derived in the shader. Instance tints are ignored, the base color comes from the material
+pub struct PbrPipeline;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 4],
+    // metallic, roughness, normal scale, occlusion strength
+    pub factors: [f32; 4],
+    // x is the alpha cutoff, negative if the material is not masked. y is 1 for double sided materials
+    pub alpha: [f32; 4],
+}
+
+impl MaterialUniform {
+    pub fn new(material: &Material) -> Self {
+        let [r, g, b] = material.emissive_factor;
+        let alpha_cutoff = match material.alpha_mode {
+            AlphaMode::Mask(cutoff) => cutoff,
+            AlphaMode::Opaque | AlphaMode::Blend => -1.0,
+        };
+        Self {
+            base_color_factor: material.base_color_factor,
+            emissive_factor: [r, g, b, 0.0],
+            factors: [material.metallic_factor, material.roughness_factor, material.normal_scale, material.occlusion_strength],
+            alpha: [alpha_cutoff, if material.double_sided { 1.0 } else { 0.0 }, 0.0, 0.0],
+        }
+    }
+}
+
+impl PbrPipeline {
+    // stand-ins for the maps a material does not have, they leave the factors unchanged
+    pub const WHITE: TextureSource = TextureSource::SolidColor([255, 255, 255, 255]);
+    pub const FLAT_NORMAL: TextureSource = TextureSource::SolidColor([128, 128, 255, 255]);
+
+    pub(crate) fn create_material_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let mut entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }];
+        // base color, metallic-roughness, normal, occlusion and emissive, each with its own sampler
+        for i in 0..5 {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 1 + i * 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 2 + i * 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &entries,
+            label: Some("material_bind_group_layout"),
+        })
+    }
+
+    // the stand-ins of the base color, metallic-roughness, normal, occlusion and emissive maps and their color spaces.
+    // Only the colors are srgb encoded, the other maps hold data
+    const MAPS: [(TextureSource, ColorSpace); 5] = [
+        (Self::WHITE, ColorSpace::Srgb),
+        (Self::WHITE, ColorSpace::Linear),
+        (Self::FLAT_NORMAL, ColorSpace::Linear),
+        (Self::WHITE, ColorSpace::Linear),
+        (Self::WHITE, ColorSpace::Srgb),
+    ];
+
+    // the maps of the material, or the stand-ins for the ones it does not have
+    pub fn load_material_textures(render_context: &RenderContext, material: &Material) -> [Handle<MyTexture>; 5] {
+        let textures = [
+            &material.base_color_texture,
+            &material.metallic_roughness_texture,
+            &material.normal_texture,
+            &material.occlusion_texture,
+            &material.emissive_texture,
+        ];
+        std::array::from_fn(|i| match textures[i] {
+            Some(texture) => render_context.request_asset((texture.source.clone(), texture.options.with_color_space(Self::MAPS[i].1))),
+            None => Self::stand_in(render_context, i),
+        })
+    }
+
+    fn stand_in(render_context: &RenderContext, map: usize) -> Handle<MyTexture> {
+        let (source, color_space) = Self::MAPS[map].clone();
+        render_context.load_asset((source, TextureOptions::default().with_color_space(color_space)))
+    }
+
+    pub fn create_material_bind_group(render_context: &RenderContext, material: &Material, textures: &[Handle<MyTexture>; 5]) -> wgpu::BindGroup {
+        use wgpu::util::DeviceExt;
+        let device = &render_context.device;
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Buffer"),
+            contents: bytemuck::cast_slice(&[MaterialUniform::new(material)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        // maps that are loading or failed are drawn with the stand-in of their slot, so a missing normal map stays flat
+        let textures: Vec<_> = textures
+            .iter()
+            .enumerate()
+            .map(|(map, texture)| texture.get().unwrap_or_else(|| Self::stand_in(render_context, map).expect_loaded()))
+            .collect();
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniform_buffer.as_entire_binding(),
+        }];
+        for (i, texture) in textures.iter().enumerate() {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 1 + i as u32 * 2,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: 2 + i as u32 * 2,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Self::create_material_bind_group_layout(device),
+            entries: &entries,
+            label: Some("material_bind_group"),
+        })
+    }
+    // shared by every renderable with the same material, so that they can be batched.
+    // Also whether some of its maps are still loading, the bind group is made again once they are all loaded.
+    // The bind group and the handles of the maps are forgotten once every Arc of the material was dropped
+    pub fn get_material_bind_group(render_context: &RenderContext, material: &Arc<Material>) -> (Arc<wgpu::BindGroup>, bool) {
+        let key = Arc::as_ptr(material) as usize;
+        let bind_groups = render_context.resources.get::<MaterialBindGroups>();
+        let mut bind_groups = bind_groups.lock().unwrap();
+        // before the lookup, a new material can have the address of a dropped one
+        bind_groups.retain(|_, (material, ..)| material.strong_count() > 0);
+        let entry = bind_groups.entry(key).or_insert_with(|| {
+            let textures = Self::load_material_textures(render_context, material);
+            let bind_group = Arc::new(Self::create_material_bind_group(render_context, material, &textures));
+            let loading = textures.iter().any(Handle::is_loading);
+            (Arc::downgrade(material), textures, bind_group, loading)
+        });
+        if entry.3 && !entry.1.iter().any(Handle::is_loading) {
+            entry.2 = Arc::new(Self::create_material_bind_group(render_context, material, &entry.1));
+            entry.3 = false;
+        }
+        (entry.2.clone(), entry.3)
+    }
+    pub fn create_bind_groups<'a>(
+        render_context: &'a RenderContext,
+        material_bind_group: &'a wgpu::BindGroup,
+    ) -> Vec<&'a wgpu::BindGroup> {
+        vec![material_bind_group, &render_context.camera_bind_group, &render_context.lights_bind_group]
+    }
+}
+
+impl PipelineBuilder for PbrPipeline {
+    fn vertex_layout(&self) -> VertexLayout {
+        Vertex::LAYOUT.with(VertexAttribute::Normal)
+    }
+    fn build_pipeline(&self, render_context: &RenderContext) -> MyPipeline {
+        let device = &render_context.device;
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("PBR Pipeline Layout"),
+                bind_group_layouts: &[
+                    &Self::create_material_bind_group_layout(device),
+                    &render_context.camera_bind_group_layout,
+                    &render_context.lights_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let vertex_attributes = self.vertex_layout().attributes();
+        let shader = render_context.load_asset::<wgpu::ShaderModule>(ShaderSource::Embedded {
+            label: "PBR Shader",
+            source: include_str!("pbr.wgsl"),
+        }).expect_loaded();
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("PBR Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[self.vertex_layout().desc(&vertex_attributes), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: RenderContext::HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // back faces are discarded in the shader unless the material is double sided
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: MyTexture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: render_context.sample_count(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+        MyPipeline{
+            pipeline: render_pipeline,
+            render_pass_builder: TypeId::of::<Opaque3DRenderPass>(),
+        }
+    }
+}
+
+// keyed by the address of the material, one map per render context. The textures are kept loaded while the material is alive,
+// the bool tells whether some of them are still loading
+type MaterialBindGroups = HashMap<usize, (Weak<Material>, [Handle<MyTexture>; 5], Arc<wgpu::BindGroup>, bool)>;
@@ -0,0 +1,140 @@
+// Vertex Shader
+struct CameraUniform {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+struct VertexInput{
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+};
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    // like the skybox, the cube moves with the camera and is drawn at the far plane
+    let view = camera.view;
+    let view_without_translation = mat4x4<f32>(
+        view[0][0], view[0][1], view[0][2], 0.0,
+        view[1][0], view[1][1], view[1][2], 0.0,
+        view[2][0], view[2][1], view[2][2], 0.0,
+        0.0, 0.0, 0.0, 1.0
+    );
+    var out: VertexOutput;
+    out.direction = input.position;
+    out.position = (camera.projection * view_without_translation * vec4<f32>(input.position, 1.0)).xyww;
+    return out;
+}
+
+struct SkyUniform {
+    // x scales the light of the sun, y is the cosine of the angular radius of the sun disk
+    params: vec4<f32>,
+}
+struct SunUniform {
+    // towards the sun
+    direction: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> sky: SkyUniform;
+@group(0) @binding(1)
+var<uniform> sun: SunUniform;
+
+// single scattering in an earth like atmosphere, in meters. The sky.rs constants have to match
+const PI: f32 = 3.14159265;
+const PLANET_RADIUS: f32 = 6371e3;
+const ATMOSPHERE_RADIUS: f32 = 6471e3;
+const VIEW_HEIGHT: f32 = 100.0;
+const RAYLEIGH_SCATTERING: vec3<f32> = vec3<f32>(5.8e-6, 13.5e-6, 33.1e-6);
+const RAYLEIGH_HEIGHT: f32 = 8000.0;
+const MIE_SCATTERING: f32 = 21e-6;
+// mie particles absorb a bit of the light too
+const MIE_EXTINCTION: f32 = 1.1 * MIE_SCATTERING;
+const MIE_HEIGHT: f32 = 1200.0;
+const MIE_G: f32 = 0.76;
+// ozone only absorbs, mostly orange light. It keeps the sky blue while the sun is low
+const OZONE_ABSORPTION: vec3<f32> = vec3<f32>(0.65e-6, 1.881e-6, 0.085e-6);
+const OZONE_CENTER: f32 = 25000.0;
+const OZONE_WIDTH: f32 = 15000.0;
+const GROUND_ALBEDO: vec3<f32> = vec3<f32>(0.1, 0.09, 0.08);
+const VIEW_STEPS: u32 = 16u;
+const SUN_STEPS: u32 = 8u;
+
+// the distances along the ray to where it enters and leaves a sphere around the center of the planet, negative if it misses.
+// The squared lengths are not subtracted directly, they are too close to each other near the ground for f32
+fn ray_sphere(origin: vec3<f32>, direction: vec3<f32>, radius: f32) -> vec2<f32> {
+    let b = dot(origin, direction);
+    let distance = length(origin);
+    let c = (distance - radius) * (distance + radius);
+    let d = b * b - c;
+    if d < 0.0 {
+        return vec2<f32>(-1.0);
+    }
+    return vec2<f32>(-b - sqrt(d), -b + sqrt(d));
+}
+
+// the rayleigh, mie and ozone densities at a height, the ozone is in a layer above the ground
+fn densities(height: f32) -> vec3<f32> {
+    return vec3<f32>(exp(-height / RAYLEIGH_HEIGHT), exp(-height / MIE_HEIGHT), max(1.0 - abs(height - OZONE_CENTER) / OZONE_WIDTH, 0.0));
+}
+
+// the densities integrated from position to the sun, infinite in the shadow of the planet
+fn optical_depth_to_sun(position: vec3<f32>, sun_direction: vec3<f32>) -> vec3<f32> {
+    if ray_sphere(position, sun_direction, PLANET_RADIUS).x > 0.0 {
+        return vec3<f32>(1e20);
+    }
+    let step = ray_sphere(position, sun_direction, ATMOSPHERE_RADIUS).y / f32(SUN_STEPS);
+    var depth = vec3<f32>(0.0);
+    for (var i = 0u; i < SUN_STEPS; i++) {
+        depth += densities(length(position + sun_direction * (f32(i) + 0.5) * step) - PLANET_RADIUS) * step;
+    }
+    return depth;
+}
+
+fn transmittance(depth: vec3<f32>) -> vec3<f32> {
+    return exp(-(RAYLEIGH_SCATTERING * depth.x + MIE_EXTINCTION * depth.y + OZONE_ABSORPTION * depth.z));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = normalize(in.direction);
+    let sun_direction = normalize(sun.direction.xyz);
+    let origin = vec3<f32>(0.0, PLANET_RADIUS + VIEW_HEIGHT, 0.0);
+    // the view ray ends at the ground or at the top of the atmosphere
+    let ground = ray_sphere(origin, direction, PLANET_RADIUS).x;
+    let hits_ground = ground > 0.0;
+    let ray_length = select(ray_sphere(origin, direction, ATMOSPHERE_RADIUS).y, ground, hits_ground);
+    let step = ray_length / f32(VIEW_STEPS);
+    var view_depth = vec3<f32>(0.0);
+    var rayleigh = vec3<f32>(0.0);
+    var mie = vec3<f32>(0.0);
+    for (var i = 0u; i < VIEW_STEPS; i++) {
+        let position = origin + direction * (f32(i) + 0.5) * step;
+        let density = densities(length(position) - PLANET_RADIUS) * step;
+        view_depth += density;
+        // the sunlight that reaches the position and the part of what it scatters that reaches the view
+        let light = transmittance(view_depth + optical_depth_to_sun(position, sun_direction));
+        rayleigh += light * density.x;
+        mie += light * density.y;
+    }
+    let mu = dot(direction, sun_direction);
+    let rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    let g2 = MIE_G * MIE_G;
+    let mie_phase = 3.0 / (8.0 * PI) * (1.0 - g2) * (1.0 + mu * mu) / ((2.0 + g2) * pow(1.0 + g2 - 2.0 * MIE_G * mu, 1.5));
+    var color = rayleigh * RAYLEIGH_SCATTERING * rayleigh_phase + mie * MIE_SCATTERING * mie_phase;
+    let view_transmittance = transmittance(view_depth);
+    if hits_ground {
+        // lambertian ground lit by the sun
+        let position = origin + direction * ground;
+        let normal = normalize(position);
+        let sunlight = transmittance(optical_depth_to_sun(position, sun_direction)) * max(dot(normal, sun_direction), 0.0);
+        color += view_transmittance * GROUND_ALBEDO / PI * sunlight;
+    } else if mu > sky.params.y {
+        // the disk of the sun, much brighter than the sky around it
+        color += view_transmittance * 100.0;
+    }
+    return vec4<f32>(color * sky.params.x, 1.0);
+}
@@ -0,0 +1,135 @@
+use std::any::TypeId;
+
+use crate::{my_pipeline::{MyPipeline, PipelineBuilder, ShaderSource}, my_texture::MyTexture, render_context::RenderContext, render_passes::opauqe3d_render_pass::Opaque3DRenderPass, vertex::{Vertex, VertexLayout}};
+
+// an atmosphere lit by the sun of RenderContext::sun_buffer, drawn behind everything like the skybox
+pub struct ProceduralSkyPipeline;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyUniform {
+    // x scales the light of the sun, y is the cosine of the angular radius of the sun disk
+    pub params: [f32; 4],
+}
+
+impl SkyUniform {
+    pub fn new(intensity: f32, sun_size: f32) -> Self {
+        Self {
+            params: [intensity, sun_size.to_radians().cos(), 0.0, 0.0],
+        }
+    }
+}
+
+impl ProceduralSkyPipeline {
+    // the sky uniform of the renderable, then the sun
+    fn create_sky_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let uniform_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[uniform_entry(0), uniform_entry(1)],
+            label: Some("sky_bind_group_layout"),
+        })
+    }
+
+    pub fn create_sky_bind_group(render_context: &RenderContext, sky_buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+        render_context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Self::create_sky_bind_group_layout(&render_context.device),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: sky_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: render_context.sun_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("sky_bind_group"),
+        })
+    }
+    pub fn create_bind_groups<'a>(
+        render_context: &'a RenderContext,
+        sky_bind_group: &'a wgpu::BindGroup,
+    ) -> Vec<&'a wgpu::BindGroup> {
+        vec![sky_bind_group, &render_context.camera_bind_group]
+    }
+}
+
+impl PipelineBuilder for ProceduralSkyPipeline {
+    fn vertex_layout(&self) -> VertexLayout {
+        Vertex::LAYOUT
+    }
+    fn build_pipeline(&self, render_context: &RenderContext) -> MyPipeline {
+        let device = &render_context.device;
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Procedural Sky Pipeline Layout"),
+                bind_group_layouts: &[
+                    &Self::create_sky_bind_group_layout(device),
+                    &render_context.camera_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let vertex_attributes = self.vertex_layout().attributes();
+        let shader = render_context.load_asset::<wgpu::ShaderModule>(ShaderSource::Embedded {
+            label: "Procedural Sky Shader",
+            source: include_str!("procedural_sky.wgsl"),
+        }).expect_loaded();
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Procedural Sky Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[self.vertex_layout().desc(&vertex_attributes)],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: RenderContext::HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // the sky is at the far plane, so everything else is drawn over it
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: MyTexture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: render_context.sample_count(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+        MyPipeline { pipeline: render_pipeline, render_pass_builder: TypeId::of::<Opaque3DRenderPass>() }
+    }
+}
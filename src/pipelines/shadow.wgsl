@@ -0,0 +1,23 @@
+// renders the depth of shadow casters as seen from a light, there is no fragment stage
+@group(0) @binding(0)
+var<uniform> light_view_projection: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(10) model_0: vec4<f32>,
+    @location(11) model_1: vec4<f32>,
+    @location(12) model_2: vec4<f32>,
+    @location(13) model_3: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> @builtin(position) vec4<f32> {
+    let instance_model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    return light_view_projection * instance_model * vec4<f32>(model.position, 1.0);
+}
@@ -1,8 +1,6 @@
 use std::any::TypeId;
 
-use wgpu::RenderPipeline;
-
-use crate::{cube_texture::CubeTexture, my_pipeline::{MyPipeline, PipelineBuilder}, my_texture::MyTexture, render_context::RenderContext, render_passes::opauqe3d_render_pass::Opaque3DRenderPass, vertex::Vertex};
+use crate::{cube_texture::CubeTexture, my_pipeline::{MyPipeline, PipelineBuilder, ShaderSource}, my_texture::MyTexture, render_context::RenderContext, render_passes::opauqe3d_render_pass::Opaque3DRenderPass, vertex::{Vertex, VertexLayout}};
 
 
 pub struct SkyboxPipeline;
@@ -17,7 +15,7 @@ impl SkyboxPipeline {
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
                         view_dimension: wgpu::TextureViewDimension::Cube,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
                     },
                     count: None,
                 },
@@ -26,18 +24,19 @@ impl SkyboxPipeline {
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     // This should match the filterable field of the
                     // corresponding Texture entry above.
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
             ],
             label: Some("texture_bind_group_layout"),
         })
     }
+    // has to match the layout of RenderContext::camera_bind_group
     fn create_camera_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -82,22 +81,25 @@ impl SkyboxPipeline {
 }
 
 impl PipelineBuilder for SkyboxPipeline {
+    fn vertex_layout(&self) -> VertexLayout {
+        Vertex::LAYOUT
+    }
     fn build_pipeline(&self, render_context: &RenderContext) -> MyPipeline {
         let device = &render_context.device;
-        let config = &render_context.config;
         let bind_group_layouts = Self::create_bind_group_layouts(device);
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &bind_group_layouts.iter().map(|x| x).collect::<Vec<_>>(),
+                bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
                 push_constant_ranges: &[],
             });
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("skybox.wgsl").into()),
-        });
+        let vertex_attributes = self.vertex_layout().attributes();
+        let shader = render_context.load_asset::<wgpu::ShaderModule>(ShaderSource::Embedded {
+            label: "Shader",
+            source: include_str!("skybox.wgsl"),
+        }).expect_loaded();
 
         let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
@@ -105,7 +107,7 @@ impl PipelineBuilder for SkyboxPipeline {
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: Some("vs_main"), // 1.
-                buffers: &[Vertex::desc()],   // 2.
+                buffers: &[self.vertex_layout().desc(&vertex_attributes)],   // 2.
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             fragment: Some(wgpu::FragmentState {
@@ -114,7 +116,7 @@ impl PipelineBuilder for SkyboxPipeline {
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     // 4.
-                    format: config.format,
+                    format: RenderContext::HDR_FORMAT,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -140,7 +142,7 @@ impl PipelineBuilder for SkyboxPipeline {
                 bias: wgpu::DepthBiasState::default(),
             }), // 1.
             multisample: wgpu::MultisampleState {
-                count: 1,                         // 2.
+                count: render_context.sample_count(), // 2.
                 mask: !0,                         // 3.
                 alpha_to_coverage_enabled: false, // 4.
             },
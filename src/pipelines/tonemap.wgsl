@@ -0,0 +1,71 @@
+// maps the hdr scene color to the surface, drawn as one triangle that covers the screen
+const NONE: f32 = 0.0;
+const REINHARD: f32 = 1.0;
+const ACES: f32 = 2.0;
+const AGX: f32 = 3.0;
+
+struct TonemapUniform {
+    // x is the exposure, y the operator
+    settings: vec4<f32>,
+}
+@group(0) @binding(0)
+var t_hdr: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> tonemap: TonemapUniform;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    return clamp(x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// polynomial fit of the AgX base contrast curve, from Benjamin Wrensch's minimal AgX
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+    var x = inset * color;
+    x = clamp(log2(max(x, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+    x = agx_contrast((x - min_ev) / (max_ev - min_ev));
+    x = outset * x;
+    // the curve produces display encoded values, the srgb surface encodes them again
+    return pow(max(x, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let hdr = textureLoad(t_hdr, vec2<i32>(position.xy), 0);
+    let color = hdr.rgb * tonemap.settings.x;
+    let tonemapper = tonemap.settings.y;
+    var mapped: vec3<f32>;
+    if tonemapper == REINHARD {
+        mapped = color / (1.0 + color);
+    } else if tonemapper == ACES {
+        mapped = aces(color);
+    } else if tonemapper == AGX {
+        mapped = agx(color);
+    } else {
+        mapped = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    }
+    return vec4<f32>(mapped, hdr.a);
+}
@@ -0,0 +1,155 @@
+use std::any::TypeId;
+
+use crate::{my_pipeline::{MyPipeline, PipelineBuilder, ShaderSource}, render_context::RenderContext, render_passes::tonemap_render_pass::TonemapRenderPass, vertex::VertexLayout};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tonemapping {
+    // clamps like rendering straight to the surface did
+    #[default]
+    None,
+    Reinhard,
+    Aces,
+    AgX,
+}
+
+impl Tonemapping {
+    pub const ALL: [Tonemapping; 4] = [Tonemapping::None, Tonemapping::Reinhard, Tonemapping::Aces, Tonemapping::AgX];
+
+    // the operator after this one, for switching through them with a key
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|tonemapping| *tonemapping == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TonemapUniform {
+    // x is the exposure, y the operator
+    pub settings: [f32; 4],
+}
+
+impl TonemapUniform {
+    pub fn new(tonemapping: Tonemapping, exposure: f32) -> Self {
+        let operator = Tonemapping::ALL.iter().position(|t| *t == tonemapping).unwrap();
+        Self {
+            settings: [exposure, operator as f32, 0.0, 0.0],
+        }
+    }
+}
+
+// draws the hdr scene color onto the surface. It has no renderables, TonemapRenderPass draws it
+pub struct TonemapPipeline;
+
+impl TonemapPipeline {
+    pub(crate) fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // read with textureLoad, the target has the size of the surface
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("tonemap_bind_group_layout"),
+        })
+    }
+    pub fn create_bind_group(render_context: &RenderContext, hdr_view: &wgpu::TextureView) -> wgpu::BindGroup {
+        let device = &render_context.device;
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &Self::create_bind_group_layout(device),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(hdr_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: render_context.tonemap_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("tonemap_bind_group"),
+        })
+    }
+}
+
+impl PipelineBuilder for TonemapPipeline {
+    // the triangle is generated from the vertex index
+    fn vertex_layout(&self) -> VertexLayout {
+        VertexLayout::default()
+    }
+    fn build_pipeline(&self, render_context: &RenderContext) -> MyPipeline {
+        let device = &render_context.device;
+        let config = &render_context.config;
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Tonemap Pipeline Layout"),
+                bind_group_layouts: &[&Self::create_bind_group_layout(device)],
+                push_constant_ranges: &[],
+            });
+
+        let shader = render_context.load_asset::<wgpu::ShaderModule>(ShaderSource::Embedded {
+            label: "Tonemap Shader",
+            source: include_str!("tonemap.wgsl"),
+        }).expect_loaded();
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tonemap Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+        MyPipeline{
+            pipeline: render_pipeline,
+            render_pass_builder: TypeId::of::<TonemapRenderPass>(),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::any::TypeId;
+
+use crate::{instance::InstanceRaw, my_pipeline::{MyPipeline, PipelineBuilder, ShaderSource}, my_texture::MyTexture, pipelines::default_pipeline::DefaultPipeline, render_context::RenderContext, render_passes::transparent3d_render_pass::Transparent3DRenderPass, vertex::{Vertex, VertexLayout}};
+
+// same bindings and shader as the default pipeline, but alpha blended and without depth writes
+pub struct TransparentPipeline;
+
+impl TransparentPipeline {
+    pub fn create_bind_groups<'a>(
+        render_context: &'a RenderContext,
+        texture_bind_group: &'a wgpu::BindGroup,
+    ) -> Vec<&'a wgpu::BindGroup> {
+        DefaultPipeline::create_bind_groups(render_context, texture_bind_group)
+    }
+}
+
+impl PipelineBuilder for TransparentPipeline {
+    fn vertex_layout(&self) -> VertexLayout {
+        Vertex::LAYOUT
+    }
+    fn build_pipeline(&self, render_context: &RenderContext) -> MyPipeline {
+        let device = &render_context.device;
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Transparent Pipeline Layout"),
+                bind_group_layouts: &[&DefaultPipeline::create_texture_bind_group_layout(device), &render_context.camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let vertex_attributes = self.vertex_layout().attributes();
+        let shader = render_context.load_asset::<wgpu::ShaderModule>(ShaderSource::Embedded {
+            label: "Shader",
+            source: include_str!("default.wgsl"),
+        }).expect_loaded();
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Transparent Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[self.vertex_layout().desc(&vertex_attributes), InstanceRaw::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: RenderContext::HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // glass panes and foliage cards are seen from both sides
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: MyTexture::DEPTH_FORMAT,
+                // transparent surfaces must not hide what is behind them
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: render_context.sample_count(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+        MyPipeline{
+            pipeline: render_pipeline,
+            render_pass_builder: TypeId::of::<Transparent3DRenderPass>(),
+        }
+    }
+}
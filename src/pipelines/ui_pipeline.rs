@@ -1,8 +1,6 @@
 use std::any::TypeId;
 
-use wgpu::RenderPipeline;
-
-use crate::{my_pipeline::{MyPipeline, PipelineBuilder}, my_texture::MyTexture, render_context::RenderContext, render_passes::ui_render_pass::UiRenderPass, vertex::Vertex};
+use crate::{my_pipeline::{MyPipeline, PipelineBuilder, ShaderSource}, my_texture::MyTexture, render_context::RenderContext, render_passes::ui_render_pass::UiRenderPass, vertex::{Vertex, VertexLayout}};
 
 pub struct UIPipeline;
 
@@ -64,6 +62,9 @@ impl UIPipeline {
 }
 
 impl PipelineBuilder for UIPipeline {
+    fn vertex_layout(&self) -> VertexLayout {
+        Vertex::LAYOUT
+    }
     fn build_pipeline(&self, render_context: &RenderContext) -> crate::my_pipeline::MyPipeline {
         let device = &render_context.device;
         let config = &render_context.config;
@@ -72,14 +73,15 @@ impl PipelineBuilder for UIPipeline {
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &bind_group_layouts.iter().map(|x| x).collect::<Vec<_>>(),
+                bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
                 push_constant_ranges: &[],
             });
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("ui.wgsl").into()),
-        });
+        let vertex_attributes = self.vertex_layout().attributes();
+        let shader = render_context.load_asset::<wgpu::ShaderModule>(ShaderSource::Embedded {
+            label: "Shader",
+            source: include_str!("ui.wgsl"),
+        }).expect_loaded();
 
         let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
@@ -87,7 +89,7 @@ impl PipelineBuilder for UIPipeline {
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: Some("vs_main"), // 1.
-                buffers: &[Vertex::desc()],   // 2.
+                buffers: &[self.vertex_layout().desc(&vertex_attributes)],   // 2.
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             fragment: Some(wgpu::FragmentState {
@@ -0,0 +1,25 @@
+// darkens the image towards its corners
+struct VignetteUniform {
+    // x is the intensity, y the distance from the center where it starts, z how far it takes to reach full intensity.
+    // The distance is 1 in the corners
+    settings: vec4<f32>,
+    color: vec4<f32>,
+};
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+@group(0) @binding(2)
+var<uniform> vignette: VignetteUniform;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(t_input, s_input, in.uv, 0.0);
+    let size = vec2<f32>(textureDimensions(t_input));
+    // round regardless of the aspect ratio
+    let half_extent = vec2<f32>(size.x / size.y, 1.0) * 0.5;
+    let distance = length((in.uv - 0.5) * half_extent * 2.0) / length(half_extent);
+    let start = vignette.settings.y;
+    let amount = smoothstep(start, start + vignette.settings.z, distance) * vignette.settings.x;
+    return vec4<f32>(mix(color.rgb, vignette.color.rgb, amount), color.a);
+}
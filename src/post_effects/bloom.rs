@@ -0,0 +1,175 @@
+use crate::{
+    post_process::{create_linear_sampler, create_uniform_buffer, draw_fullscreen, get_fullscreen_pipeline, input_bind_group_layout_entries, FullscreenPipelineDescriptor, PostEffect},
+    render_context::RenderContext,
+};
+
+const SHADER: &str = include_str!("../pipelines/bloom.wgsl");
+
+// the blurred bright parts of the scene at half the size of the surface and below
+struct BloomMips {
+    width: u32,
+    height: u32,
+    #[allow(unused)]
+    texture: wgpu::Texture,
+    views: Vec<wgpu::TextureView>,
+}
+
+impl BloomMips {
+    fn new(device: &wgpu::Device, width: u32, height: u32, mip_count: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("bloom mips"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mip_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: RenderContext::HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let views = (0..mip_count).map(|mip| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        }).collect();
+        Self { width, height, texture, views }
+    }
+}
+
+// every bloom of a render context fills the chain from scratch, so they can all share it
+type SharedBloomMips = Option<BloomMips>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bloom {
+    // colors are only bloomed where their brightest channel exceeds this
+    pub threshold: f32,
+    // eases colors in below the threshold instead of cutting them off
+    pub knee: f32,
+    pub intensity: f32,
+    // spread of the blur between two mips, in texels
+    pub radius: f32,
+    // more mips give a wider glow, the chain stops before the mips get smaller than a texel
+    pub mip_count: u32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.15,
+            radius: 1.0,
+            mip_count: 5,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BloomUniform {
+    pub settings: [f32; 4],
+}
+
+impl BloomUniform {
+    pub fn new(bloom: &Bloom) -> Self {
+        Self {
+            settings: [bloom.threshold, bloom.knee, bloom.intensity, bloom.radius],
+        }
+    }
+}
+
+impl PostEffect for Bloom {
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, render_context: &RenderContext, input: &wgpu::TextureView, output: &wgpu::TextureView) {
+        let device = &render_context.device;
+        let width = (render_context.config.width / 2).max(1);
+        let height = (render_context.config.height / 2).max(1);
+        let mip_count = self.mip_count.clamp(1, width.min(height).ilog2() + 1);
+        let mips = render_context.resources.get::<SharedBloomMips>();
+        let mut mips = mips.lock().unwrap();
+        let up_to_date = mips.as_ref().is_some_and(|mips| {
+            mips.width == width && mips.height == height && mips.views.len() == mip_count as usize
+        });
+        if !up_to_date {
+            *mips = Some(BloomMips::new(device, width, height, mip_count));
+        }
+        let mips = mips.as_ref().unwrap();
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &input_bind_group_layout_entries(),
+            label: Some("bloom_bind_group_layout"),
+        });
+        let [texture_entry, sampler_entry, uniform_entry] = input_bind_group_layout_entries();
+        let composite_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry, sampler_entry, uniform_entry, wgpu::BindGroupLayoutEntry { binding: 3, ..texture_entry }],
+            label: Some("bloom_composite_bind_group_layout"),
+        });
+        let pipeline = |entry_point, bind_group_layout, blend| {
+            get_fullscreen_pipeline(render_context, &FullscreenPipelineDescriptor {
+                label: "Bloom",
+                shader: SHADER,
+                entry_point,
+                bind_group_layout,
+                blend,
+            })
+        };
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let prefilter_pipeline = pipeline("fs_prefilter", &bind_group_layout, None);
+        let downsample_pipeline = pipeline("fs_downsample", &bind_group_layout, None);
+        let upsample_pipeline = pipeline("fs_upsample", &bind_group_layout, Some(wgpu::BlendState { color: additive, alpha: additive }));
+        let composite_pipeline = pipeline("fs_composite", &composite_bind_group_layout, None);
+
+        let sampler = create_linear_sampler(device);
+        let uniform_buffer = create_uniform_buffer(device, "Bloom Buffer", &BloomUniform::new(self));
+        let create_bind_group = |layout: &wgpu::BindGroupLayout, source: &wgpu::TextureView, bloom: Option<&wgpu::TextureView>| {
+            let mut entries = vec![
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ];
+            if let Some(bloom) = bloom {
+                entries.push(wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(bloom),
+                });
+            }
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &entries,
+                label: Some("bloom_bind_group"),
+            })
+        };
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+
+        let bind_group = create_bind_group(&bind_group_layout, input, None);
+        draw_fullscreen(encoder, "Bloom Prefilter", &prefilter_pipeline, &bind_group, &mips.views[0], clear);
+        for mip in 1..mips.views.len() {
+            let bind_group = create_bind_group(&bind_group_layout, &mips.views[mip - 1], None);
+            draw_fullscreen(encoder, "Bloom Downsample", &downsample_pipeline, &bind_group, &mips.views[mip], clear);
+        }
+        // every mip gets the blurred smaller ones added on top
+        for mip in (1..mips.views.len()).rev() {
+            let bind_group = create_bind_group(&bind_group_layout, &mips.views[mip], None);
+            draw_fullscreen(encoder, "Bloom Upsample", &upsample_pipeline, &bind_group, &mips.views[mip - 1], wgpu::LoadOp::Load);
+        }
+        let bind_group = create_bind_group(&composite_bind_group_layout, input, Some(&mips.views[0]));
+        draw_fullscreen(encoder, "Bloom Composite", &composite_pipeline, &bind_group, output, clear);
+    }
+}
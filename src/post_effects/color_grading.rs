@@ -0,0 +1,176 @@
+use std::{collections::HashMap, sync::{Arc, Weak}};
+
+
+use crate::{
+    post_process::{create_linear_sampler, create_uniform_buffer, draw_fullscreen, get_fullscreen_pipeline, input_bind_group_layout_entries, FullscreenPipelineDescriptor, PostEffect},
+    render_context::RenderContext,
+};
+
+/// A 3d color lookup table for hdr colors. It is indexed by log encoded colors between 2^MIN_EV and 2^MAX_EV,
+/// and stores its outputs encoded the same way, like color_grading.wgsl expects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3d {
+    pub size: u32,
+    // the encoded outputs of the lattice points, red changes fastest, then green
+    pub values: Vec<[f32; 3]>,
+}
+
+impl Lut3d {
+    // the range of the encoding, the same as in color_grading.wgsl
+    pub const MIN_EV: f32 = -14.0;
+    pub const MAX_EV: f32 = 6.0;
+
+    pub fn encode(color: [f32; 3]) -> [f32; 3] {
+        color.map(|c| ((c.max(Self::MIN_EV.exp2()).log2() - Self::MIN_EV) / (Self::MAX_EV - Self::MIN_EV)).clamp(0.0, 1.0))
+    }
+
+    pub fn decode(encoded: [f32; 3]) -> [f32; 3] {
+        encoded.map(|e| (e * (Self::MAX_EV - Self::MIN_EV) + Self::MIN_EV).exp2())
+    }
+
+    /// Builds the table from a grading function of linear colors.
+    pub fn from_fn(size: u32, grade: impl Fn([f32; 3]) -> [f32; 3]) -> Self {
+        assert!(size >= 2, "A lut needs at least two lattice points per axis");
+        let step = 1.0 / (size - 1) as f32;
+        let mut values = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let color = Self::decode([r as f32 * step, g as f32 * step, b as f32 * step]);
+                    values.push(Self::encode(grade(color)));
+                }
+            }
+        }
+        Self { size, values }
+    }
+
+    pub fn identity(size: u32) -> Self {
+        Self::from_fn(size, |color| color)
+    }
+
+    fn create_texture_view(&self, render_context: &RenderContext) -> wgpu::TextureView {
+        assert_eq!(self.values.len(), (self.size * self.size * self.size) as usize, "The lut does not have size^3 values");
+        let size = wgpu::Extent3d {
+            width: self.size,
+            height: self.size,
+            depth_or_array_layers: self.size,
+        };
+        let texture = render_context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("color grading lut"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let texels: Vec<u16> = self
+            .values
+            .iter()
+            .flat_map(|[r, g, b]| [*r, *g, *b, 1.0].map(|c| half::f16::from_f32(c).to_bits()))
+            .collect();
+        render_context.queue.write_texture(
+            texture.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(8 * self.size),
+                rows_per_image: Some(self.size),
+            },
+            size,
+        );
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+}
+
+// keyed by the address of the lut, one map per render context. The texture is forgotten once every Arc of the lut was dropped
+type LutTextures = HashMap<usize, (Weak<Lut3d>, Arc<wgpu::TextureView>)>;
+
+fn get_lut_texture_view(render_context: &RenderContext, lut: &Arc<Lut3d>) -> Arc<wgpu::TextureView> {
+    let key = Arc::as_ptr(lut) as usize;
+    let textures = render_context.resources.get::<LutTextures>();
+    let mut textures = textures.lock().unwrap();
+    textures.retain(|_, (lut, _)| lut.strong_count() > 0);
+    textures.entry(key).or_insert_with(|| {
+        (Arc::downgrade(lut), Arc::new(lut.create_texture_view(render_context)))
+    }).1.clone()
+}
+
+// grades the scene through a lut. Changing the lut means replacing the Arc, the texture is made once per lut
+pub struct ColorGrading {
+    pub lut: Arc<Lut3d>,
+    // 0 leaves the colors alone, 1 replaces them with the graded ones
+    pub strength: f32,
+}
+
+impl ColorGrading {
+    pub fn new(lut: Lut3d) -> Self {
+        Self {
+            lut: Arc::new(lut),
+            strength: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColorGradingUniform {
+    pub settings: [f32; 4],
+}
+
+impl PostEffect for ColorGrading {
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, render_context: &RenderContext, input: &wgpu::TextureView, output: &wgpu::TextureView) {
+        let device = &render_context.device;
+        let [texture_entry, sampler_entry, uniform_entry] = input_bind_group_layout_entries();
+        let lut_entry = wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D3,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            ..texture_entry
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[texture_entry, sampler_entry, uniform_entry, lut_entry],
+            label: Some("color_grading_bind_group_layout"),
+        });
+        let pipeline = get_fullscreen_pipeline(render_context, &FullscreenPipelineDescriptor {
+            label: "Color Grading",
+            shader: include_str!("../pipelines/color_grading.wgsl"),
+            entry_point: "fs_main",
+            bind_group_layout: &bind_group_layout,
+            blend: None,
+        });
+        let sampler = create_linear_sampler(device);
+        let uniform = ColorGradingUniform {
+            settings: [self.strength, 0.0, 0.0, 0.0],
+        };
+        let uniform_buffer = create_uniform_buffer(device, "Color Grading Buffer", &uniform);
+        let lut_view = get_lut_texture_view(render_context, &self.lut);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&lut_view),
+                },
+            ],
+            label: Some("color_grading_bind_group"),
+        });
+        draw_fullscreen(encoder, "Color Grading", &pipeline, &bind_group, output, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+    }
+}
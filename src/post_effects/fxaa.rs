@@ -0,0 +1,41 @@
+use crate::{post_process::{apply_fullscreen_effect, PostEffect}, render_context::RenderContext};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fxaa {
+    // the longest blur along an edge, in texels
+    pub span_max: f32,
+    // edges in bright areas are blurred less, by this part of their luma
+    pub reduce_mul: f32,
+    pub reduce_min: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Self {
+            span_max: 8.0,
+            reduce_mul: 1.0 / 8.0,
+            reduce_min: 1.0 / 128.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FxaaUniform {
+    pub settings: [f32; 4],
+}
+
+impl FxaaUniform {
+    pub fn new(fxaa: &Fxaa) -> Self {
+        Self {
+            settings: [fxaa.span_max, fxaa.reduce_mul, fxaa.reduce_min, 0.0],
+        }
+    }
+}
+
+impl PostEffect for Fxaa {
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, render_context: &RenderContext, input: &wgpu::TextureView, output: &wgpu::TextureView) {
+        let shader = include_str!("../pipelines/fxaa.wgsl");
+        apply_fullscreen_effect(encoder, render_context, "FXAA", shader, &FxaaUniform::new(self), input, output);
+    }
+}
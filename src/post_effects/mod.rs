@@ -0,0 +1,4 @@
+pub mod bloom;
+pub mod color_grading;
+pub mod fxaa;
+pub mod vignette;
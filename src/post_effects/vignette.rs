@@ -0,0 +1,47 @@
+use crate::{post_process::{apply_fullscreen_effect, PostEffect}, render_context::RenderContext};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vignette {
+    // 0 leaves the corners alone, 1 replaces them with the color
+    pub intensity: f32,
+    // distances are measured from the center, the corners are at 1
+    pub radius: f32,
+    // how far beyond the radius the full intensity is reached
+    pub smoothness: f32,
+    pub color: [f32; 3],
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.5,
+            radius: 0.5,
+            smoothness: 0.5,
+            color: [0.0, 0.0, 0.0],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VignetteUniform {
+    pub settings: [f32; 4],
+    pub color: [f32; 4],
+}
+
+impl VignetteUniform {
+    pub fn new(vignette: &Vignette) -> Self {
+        let [r, g, b] = vignette.color;
+        Self {
+            settings: [vignette.intensity, vignette.radius, vignette.smoothness, 0.0],
+            color: [r, g, b, 1.0],
+        }
+    }
+}
+
+impl PostEffect for Vignette {
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, render_context: &RenderContext, input: &wgpu::TextureView, output: &wgpu::TextureView) {
+        let shader = include_str!("../pipelines/vignette.wgsl");
+        apply_fullscreen_effect(encoder, render_context, "Vignette", shader, &VignetteUniform::new(self), input, output);
+    }
+}
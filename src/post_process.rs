@@ -0,0 +1,262 @@
+// fullscreen effects that run on the hdr scene color between the 3d passes and tonemapping
+
+use std::{any::Any, collections::HashMap};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    render_context::RenderContext,
+    render_graph::{RenderGraphAttachments, HDR_COLOR, POST_COLOR},
+};
+
+// the vertex stage of every effect, it is put in front of the effect shaders
+pub const FULLSCREEN_SHADER: &str = include_str!("pipelines/fullscreen.wgsl");
+
+pub trait PostEffect: Any + Send + Sync {
+    /// Renders the effect of input into output. Both have RenderContext::HDR_FORMAT and the size of the surface,
+    /// every pixel of output has to be written.
+    fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        render_context: &RenderContext,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    );
+}
+
+// the effects run in the order they were pushed, their parameters can be changed between frames
+#[derive(Default)]
+pub struct PostProcessStack {
+    pub effects: Vec<Box<dyn PostEffect>>,
+}
+
+impl PostProcessStack {
+    pub fn push<T: PostEffect>(&mut self, effect: T) {
+        self.effects.push(Box::new(effect));
+    }
+
+    pub fn get<T: PostEffect>(&self) -> Option<&T> {
+        self.effects.iter().find_map(|effect| {
+            let effect: &dyn Any = effect.as_ref();
+            effect.downcast_ref::<T>()
+        })
+    }
+
+    // for editing the parameters of an effect at runtime
+    pub fn get_mut<T: PostEffect>(&mut self) -> Option<&mut T> {
+        self.effects.iter_mut().find_map(|effect| {
+            let effect: &mut dyn Any = effect.as_mut();
+            effect.downcast_mut::<T>()
+        })
+    }
+
+    pub fn remove<T: PostEffect>(&mut self) {
+        self.effects.retain(|effect| {
+            let effect: &dyn Any = effect.as_ref();
+            !effect.is::<T>()
+        });
+    }
+
+    /// Runs every effect on HDR_COLOR, going back and forth between it and POST_COLOR.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, render_context: &RenderContext, attachments: &RenderGraphAttachments) {
+        let mut input = HDR_COLOR;
+        let mut output = POST_COLOR;
+        for effect in self.effects.iter() {
+            effect.render(encoder, render_context, attachments.get(input), attachments.get(output));
+            std::mem::swap(&mut input, &mut output);
+        }
+        // the passes after this one read HDR_COLOR
+        if input == POST_COLOR {
+            let render_graph = &render_context.render_graph;
+            let source = render_graph.transient_texture(POST_COLOR);
+            encoder.copy_texture_to_texture(
+                source.as_image_copy(),
+                render_graph.transient_texture(HDR_COLOR).as_image_copy(),
+                source.size(),
+            );
+        }
+    }
+}
+
+/// Describes an effect pipeline. The fragment shader receives FullscreenOutput from FULLSCREEN_SHADER
+/// and renders into a target with RenderContext::HDR_FORMAT.
+pub struct FullscreenPipelineDescriptor<'a> {
+    // also identifies the pipeline in the cache, together with the entry point
+    pub label: &'static str,
+    pub shader: &'static str,
+    pub entry_point: &'static str,
+    pub bind_group_layout: &'a wgpu::BindGroupLayout,
+    pub blend: Option<wgpu::BlendState>,
+}
+
+// keyed by the label and the entry point, one map per render context
+type FullscreenPipelines = HashMap<(&'static str, &'static str), wgpu::RenderPipeline>;
+
+pub fn get_fullscreen_pipeline(render_context: &RenderContext, descriptor: &FullscreenPipelineDescriptor) -> wgpu::RenderPipeline {
+    let device = &render_context.device;
+    let key = (descriptor.label, descriptor.entry_point);
+    let pipelines = render_context.resources.get::<FullscreenPipelines>();
+    let mut pipelines = pipelines.lock().unwrap();
+    pipelines.entry(key).or_insert_with(|| {
+        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(descriptor.label),
+            bind_group_layouts: &[descriptor.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(descriptor.label),
+            source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", FULLSCREEN_SHADER, descriptor.shader).into()),
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(descriptor.label),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some(descriptor.entry_point),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: RenderContext::HDR_FORMAT,
+                    blend: descriptor.blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }).clone()
+}
+
+// the input texture, a linear sampler and the uniform of the effect, as bindings 0, 1 and 2
+pub fn input_bind_group_layout_entries() -> [wgpu::BindGroupLayoutEntry; 3] {
+    [
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        },
+    ]
+}
+
+pub fn create_linear_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    })
+}
+
+pub fn create_uniform_buffer<T: bytemuck::Pod>(device: &wgpu::Device, label: &str, uniform: &T) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(std::slice::from_ref(uniform)),
+        usage: wgpu::BufferUsages::UNIFORM,
+    })
+}
+
+/// Draws the fullscreen triangle into output. With LoadOp::Load the pipeline can blend onto what is there.
+pub fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    output: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+/// Runs fs_main of a shader that reads the input and a uniform through input_bind_group_layout_entries,
+/// which is all most effects need.
+pub fn apply_fullscreen_effect<T: bytemuck::Pod>(
+    encoder: &mut wgpu::CommandEncoder,
+    render_context: &RenderContext,
+    label: &'static str,
+    shader: &'static str,
+    uniform: &T,
+    input: &wgpu::TextureView,
+    output: &wgpu::TextureView,
+) {
+    let device = &render_context.device;
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &input_bind_group_layout_entries(),
+        label: Some(label),
+    });
+    let pipeline = get_fullscreen_pipeline(render_context, &FullscreenPipelineDescriptor {
+        label,
+        shader,
+        entry_point: "fs_main",
+        bind_group_layout: &bind_group_layout,
+        blend: None,
+    });
+    let sampler = create_linear_sampler(device);
+    let uniform_buffer = create_uniform_buffer(device, label, uniform);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(input),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+        ],
+        label: Some(label),
+    });
+    draw_fullscreen(encoder, label, &pipeline, &bind_group, output, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+}
@@ -1,16 +1,19 @@
-use std::{any::TypeId, collections::{BTreeSet, HashMap, HashSet}, sync::Arc};
+use std::{any::TypeId, collections::HashSet, sync::Arc};
 
 use tokio::runtime::Runtime;
 use wgpu::{Surface, util::DeviceExt};
 use winit::window::Window;
 
 use crate::{
-    camera_uniform::CameraUniform, my_render_pass::RENDER_PASS_BUILDERS, my_texture::MyTexture, renderable::Renderable, state::State
+    assets::{Asset, AssetServer, Handle}, batching::{BatchBuffers, BatchStats}, cache::{create_pipeline_cache, CacheKey, ContextResources, PipelineCache}, camera_uniform::CameraUniform, compressed_texture::COMPRESSION_FEATURES, cube_texture::CubeTexture, environment_map::{create_brdf_lut, create_environment_sampler, Environment, EnvironmentMap}, light::LightsUniform, my_pipeline::PIPELINE_BUILDERS, my_render_pass::FrameInputs, pipelines::{default_pipeline::DefaultPipeline, tonemap_pipeline::{TonemapUniform, Tonemapping}}, screenshot::FrameReadback, shadow::{ShadowLayout, ShadowMaps}, sky::SunUniform, my_texture::{MyTexture, TextureFiltering}, render_graph::RenderGraph, renderable::Renderable, state::State
 };
 
 pub struct RenderContext {
-    pub window: Arc<Window>,
-    surface: wgpu::Surface<'static>,
+    // None for a headless context
+    pub window: Option<Arc<Window>>,
+    surface: Option<wgpu::Surface<'static>>,
+    // the render target of a headless context
+    pub offscreen_texture: Option<wgpu::Texture>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
@@ -19,10 +22,54 @@ pub struct RenderContext {
     // most pipelines will use this
     pub camera_bind_group_layout: wgpu::BindGroupLayout,
     pub camera_bind_group: wgpu::BindGroup,
+    pub lights_buffer: wgpu::Buffer,
+    pub lights_bind_group_layout: wgpu::BindGroupLayout,
+    pub lights_bind_group: wgpu::BindGroup,
+    // exposure and operator of the tonemap pass
+    pub tonemap_buffer: wgpu::Buffer,
+    // the direction of the sun for the procedural sky
+    pub sun_buffer: wgpu::Buffer,
+    pub shadow_maps: ShadowMaps,
+    // whether lights went without shadows in the last frame, it is only warned about when it starts
+    shadows_out_of_layers: bool,
+    // the parts of image based lighting that do not depend on the environment
+    pub brdf_lut: wgpu::Texture,
+    environment_sampler: wgpu::Sampler,
+    // the environment the lights bind group was made with
+    environment_map: Option<Handle<EnvironmentMap>>,
+    // false while the lights bind group holds the empty maps in place of an environment that is loading
+    environment_map_bound: bool,
     pub depth_texture: MyTexture,
+    // the filtering of textures loaded from now on, the ones already loaded keep theirs
+    pub texture_filtering: TextureFiltering,
+    // samples per pixel of the 3d passes, change it with set_sample_count
+    sample_count: u32,
+    supported_sample_counts: Vec<u32>,
+    pub render_graph: RenderGraph,
+    // how the renderables of the last frame were merged into draws
+    pub batch_stats: BatchStats,
+    pub(crate) batch_buffers: BatchBuffers,
+    // pipelines and bind groups made for the device of this context
+    pub(crate) cache: PipelineCache,
+    pub(crate) resources: ContextResources,
+    // set to capture the next rendered frame into screenshot
+    pub screenshot_requested: bool,
+    pub screenshot: Option<image::RgbaImage>,
+    // textures, meshes and the other assets shared by the renderables
+    pub assets: AssetServer,
+    // request_asset reads the files of textures and cube textures on worker threads instead of waiting for them.
+    // On with a window, off without one so that offscreen renders show every texture in their first frame
+    pub asynchronous_loading: bool,
+    // drawn in place of textures that are still loading or failed to load
+    pub placeholder_texture: Arc<MyTexture>,
+    pub placeholder_texture_bind_group: Arc<wgpu::BindGroup>,
+    pub placeholder_cube_texture: Arc<CubeTexture>,
 }
 
 impl RenderContext {
+    // the 3d passes render into a target of this format, the tonemap pass maps it to the surface
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
     pub fn new(window: Window) -> Self {
         // The instance is a handle to our GPU
         // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
@@ -44,23 +91,8 @@ impl RenderContext {
                 force_fallback_adapter: false,
             }))
             .unwrap();
-        let (device, queue) = runtime
-            .block_on(adapter.request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web, we'll have to disable some.
-                    required_limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
-                    label: None,
-                    memory_hints: Default::default(),
-                },
-                None, // Trace path
-            ))
-            .unwrap();
+        let (device, queue) = Self::request_device(&runtime, &adapter);
+        let supported_sample_counts = Self::query_sample_counts(&adapter, &device);
         let surface_caps = surface.get_capabilities(&adapter);
         // Shader code in this tutorial assumes an sRGB surface texture. Using a different
         // one will result in all the colors coming out darker. If you want to support non
@@ -72,8 +104,14 @@ impl RenderContext {
             .copied()
             .unwrap_or(surface_caps.formats[0]);
         let size = window.inner_size();
+        // COPY_SRC is needed to read back frames for screenshots
+        let usage = if surface_caps.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        };
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -82,7 +120,121 @@ impl RenderContext {
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
+        Self::from_parts(Some(window), Some(surface), device, queue, config, supported_sample_counts)
+    }
 
+    /// Creates a context without a window that renders into an owned offscreen texture.
+    /// Returns None if no adapter is available.
+    pub fn new_headless(
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        force_fallback_adapter: bool,
+    ) -> Option<Self> {
+        // GL is included so that software rasterizers like llvmpipe can be picked up
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            flags: wgpu::InstanceFlags::DEBUG,
+            ..Default::default()
+        });
+        let runtime = Runtime::new().unwrap();
+        let adapter = runtime.block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter,
+        }))?;
+        let (device, queue) = Self::request_device(&runtime, &adapter);
+        let supported_sample_counts = Self::query_sample_counts(&adapter, &device);
+        // the surface configuration is never applied to a surface, pipelines only read format and size from it
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        Some(Self::from_parts(None, None, device, queue, config, supported_sample_counts))
+    }
+
+    fn request_device(runtime: &Runtime, adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+        runtime
+            .block_on(adapter.request_device(
+                &wgpu::DeviceDescriptor {
+                    // compressed textures are decoded on the cpu when the adapter cannot sample them.
+                    // The format features of the adapter allow sample counts beyond the guaranteed ones
+                    required_features: adapter.features()
+                        & (COMPRESSION_FEATURES | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES),
+                    // WebGL doesn't support all of wgpu's features, so if
+                    // we're building for the web, we'll have to disable some.
+                    required_limits: if cfg!(target_arch = "wasm32") {
+                        wgpu::Limits::downlevel_webgl2_defaults()
+                    } else {
+                        wgpu::Limits::default()
+                    },
+                    label: None,
+                    memory_hints: Default::default(),
+                },
+                None, // Trace path
+            ))
+            .unwrap()
+    }
+
+    // the sample counts that both the hdr target and the depth texture can be rendered and resolved with.
+    // Without the adapter specific format features the device only allows the guaranteed ones
+    fn query_sample_counts(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Vec<u32> {
+        let flags = |format: wgpu::TextureFormat| {
+            if device.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+                adapter.get_texture_format_features(format).flags
+            } else {
+                format.guaranteed_format_features(device.features()).flags
+            }
+        };
+        let hdr_flags = flags(Self::HDR_FORMAT);
+        let depth_flags = flags(MyTexture::DEPTH_FORMAT);
+        [1, 2, 4, 8]
+            .into_iter()
+            .filter(|count| {
+                *count == 1
+                    || (hdr_flags.sample_count_supported(*count)
+                        && hdr_flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+                        && depth_flags.sample_count_supported(*count))
+            })
+            .collect()
+    }
+
+    fn create_offscreen_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen texture"),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+            view_formats: &[],
+        })
+    }
+
+    fn from_parts(
+        window: Option<Arc<Window>>,
+        surface: Option<wgpu::Surface<'static>>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        supported_sample_counts: Vec<u32>,
+    ) -> Self {
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+        let offscreen_texture = match surface {
+            Some(_) => None,
+            None => Some(Self::create_offscreen_texture(&device, &config)),
+        };
         let camera_uniform = CameraUniform::default();
 
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -90,12 +242,12 @@ impl RenderContext {
             contents: bytemuck::cast_slice(&[camera_uniform]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
-        let depth_texture = MyTexture::create_depth_texture(&device, &config, "depth texture");
+        let depth_texture = MyTexture::create_depth_texture(&device, &config, 1, "depth texture");
 
         let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -113,34 +265,299 @@ impl RenderContext {
             }],
             label: Some("camera_bind_group"),
         });
+        let lights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Lights Buffer"),
+            contents: bytemuck::cast_slice(&[LightsUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let tonemap_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tonemap Buffer"),
+            contents: bytemuck::cast_slice(&[TonemapUniform::new(Tonemapping::default(), 1.0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sun_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sun Buffer"),
+            contents: bytemuck::cast_slice(&[SunUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let shadow_maps = ShadowMaps::new(&device);
+        // the lights, then the shadow uniform, shadow map and comparison sampler,
+        // then the irradiance and prefiltered maps of the environment, the brdf lookup table and their sampler
+        let uniform_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture_entry = |binding: u32, view_dimension: wgpu::TextureViewDimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let lights_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry(0),
+                uniform_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                texture_entry(4, wgpu::TextureViewDimension::Cube),
+                texture_entry(5, wgpu::TextureViewDimension::Cube),
+                texture_entry(6, wgpu::TextureViewDimension::D2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("lights_bind_group_layout"),
+        });
+        let brdf_lut = create_brdf_lut(&device, &queue);
+        let environment_sampler = create_environment_sampler(&device);
+        let lights_bind_group = Self::create_lights_bind_group(
+            &device,
+            &lights_bind_group_layout,
+            &lights_buffer,
+            &shadow_maps,
+            &EnvironmentMap::empty(&device),
+            &brdf_lut,
+            &environment_sampler,
+        );
+        let placeholder_texture = Arc::new(MyTexture::placeholder(&device, &queue));
+        let placeholder_texture_bind_group = Arc::new(DefaultPipeline::create_texture_bind_group(&device, &placeholder_texture));
+        let placeholder_cube_texture = Arc::new(CubeTexture::placeholder(&device, &queue));
         Self {
+            asynchronous_loading: window.is_some(),
             window,
             surface,
+            offscreen_texture,
             device,
             queue,
             config,
             size,
             camera_buffer,
             depth_texture,
+            texture_filtering: TextureFiltering::default(),
+            sample_count: 1,
+            supported_sample_counts,
             camera_bind_group_layout,
             camera_bind_group,
+            lights_buffer,
+            lights_bind_group_layout,
+            lights_bind_group,
+            tonemap_buffer,
+            sun_buffer,
+            shadow_maps,
+            shadows_out_of_layers: false,
+            brdf_lut,
+            environment_sampler,
+            environment_map: None,
+            environment_map_bound: true,
+            render_graph: RenderGraph::default(),
+            batch_stats: BatchStats::default(),
+            batch_buffers: BatchBuffers::default(),
+            cache: create_pipeline_cache(),
+            resources: ContextResources::default(),
+            screenshot_requested: false,
+            screenshot: None,
+            assets: AssetServer::default(),
+            placeholder_texture,
+            placeholder_texture_bind_group,
+            placeholder_cube_texture,
         }
     }
 
+    fn create_lights_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        lights_buffer: &wgpu::Buffer,
+        shadow_maps: &ShadowMaps,
+        environment_map: &EnvironmentMap,
+        brdf_lut: &wgpu::Texture,
+        environment_sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: lights_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: shadow_maps.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&shadow_maps.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&shadow_maps.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&environment_map.irradiance_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&environment_map.prefiltered_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&brdf_lut.create_view(&wgpu::TextureViewDescriptor::default())),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::Sampler(environment_sampler),
+                },
+            ],
+            label: Some("lights_bind_group"),
+        })
+    }
+
+    // the lights bind group holds the maps of the environment, it is made again when the source changes and once it is loaded
+    fn update_environment(&mut self, environment: Option<&Environment>) {
+        let source = environment.map(|environment| environment.source.clone());
+        if source.as_ref() != self.environment_map.as_ref().map(|environment_map| environment_map.key()) {
+            self.environment_map = source.map(|source| self.request_asset(source));
+        } else if self.environment_map_bound {
+            return;
+        }
+        // the empty maps are bound until the environment is loaded, and for good if it failed
+        self.environment_map_bound = !self.environment_map.as_ref().is_some_and(|environment_map| environment_map.is_loading());
+        let environment_map = self
+            .environment_map
+            .as_ref()
+            .and_then(|environment_map| environment_map.get())
+            .unwrap_or_else(|| Arc::new(EnvironmentMap::empty(&self.device)));
+        self.lights_bind_group = Self::create_lights_bind_group(
+            &self.device,
+            &self.lights_bind_group_layout,
+            &self.lights_buffer,
+            &self.shadow_maps,
+            &environment_map,
+            &self.brdf_lut,
+            &self.environment_sampler,
+        );
+    }
+
+    // shared with everything else that loaded the same key, see AssetServer::load
+    pub fn load_asset<T: Asset>(&self, key: T::Key) -> Handle<T> {
+        self.assets.load(key, self)
+    }
+
+    // like load_asset, but with asynchronous_loading the handle is returned while the files are read on a worker.
+    // The asset is made after one of the next frames, draw a placeholder until then
+    pub fn request_asset<T: Asset>(&self, key: T::Key) -> Handle<T> {
+        if self.asynchronous_loading {
+            self.assets.load_async(key, self)
+        } else {
+            self.assets.load(key, self)
+        }
+    }
+
+    // blocks until every asset that was requested has been made, like before a screenshot
+    pub fn finish_loading(&self) {
+        self.assets.finish_loading(self);
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // 1 is always among them
+    pub fn supported_sample_counts(&self) -> &[u32] {
+        &self.supported_sample_counts
+    }
+
+    /// Sets the number of samples per pixel of the 3d passes. The pipelines in the cache are rebuilt for it on their next use.
+    /// A count the adapter does not support leaves the current one.
+    pub fn set_sample_count(&mut self, sample_count: u32) -> Result<(), UnsupportedSampleCount> {
+        if !self.supported_sample_counts.contains(&sample_count) {
+            return Err(UnsupportedSampleCount { sample_count, supported: self.supported_sample_counts.clone() });
+        }
+        if sample_count == self.sample_count {
+            return Ok(());
+        }
+        self.sample_count = sample_count;
+        self.depth_texture = MyTexture::create_depth_texture(&self.device, &self.config, sample_count, "depth texture");
+        for pipeline_type in PIPELINE_BUILDERS.keys() {
+            self.cache.invalidate(&CacheKey::Pipeline(*pipeline_type));
+        }
+        Ok(())
+    }
+
+    /// Captures the next frame produced by render. Pick it up with take_screenshot.
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    pub fn take_screenshot(&mut self) -> Option<image::RgbaImage> {
+        self.screenshot.take()
+    }
+
+    /// Reads the current content of the offscreen texture of a headless context.
+    pub fn read_offscreen_texture(&self) -> Option<image::RgbaImage> {
+        let texture = self.offscreen_texture.as_ref()?;
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Readback Encoder"),
+            });
+        let readback = FrameReadback::new(&self.device, &mut encoder, texture);
+        self.queue.submit(std::iter::once(encoder.finish()));
+        Some(readback.into_image(&self.device))
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.size = new_size;
         self.config.width = new_size.width;
         self.config.height = new_size.height;
-        self.surface.configure(&self.device, &self.config);
-        self.depth_texture = MyTexture::create_depth_texture(&self.device, &self.config, "depth texture");
+        match &self.surface {
+            Some(surface) => surface.configure(&self.device, &self.config),
+            None => self.offscreen_texture = Some(Self::create_offscreen_texture(&self.device, &self.config)),
+        }
+        self.depth_texture = MyTexture::create_depth_texture(&self.device, &self.config, self.sample_count, "depth texture");
     }
 
     pub fn render(&mut self, state: &mut State) -> Result<(), wgpu::SurfaceError> {
         // get render target
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let output = match &self.surface {
+            Some(surface) => Some(surface.get_current_texture()?),
+            None => None,
+        };
+        let view = match &output {
+            Some(output) => output.texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            None => self
+                .offscreen_texture
+                .as_ref()
+                .unwrap()
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+        };
         // update camera transform
         let aspect = self.config.width as f32 / self.config.height as f32;
         let camera_uniform = CameraUniform::new(&state.camera, aspect, true);
@@ -149,6 +566,28 @@ impl RenderContext {
             0,
             bytemuck::cast_slice(&[camera_uniform]),
         );
+        let tonemap_uniform = TonemapUniform::new(state.tonemapping, state.exposure);
+        self.queue.write_buffer(&self.tonemap_buffer, 0, bytemuck::cast_slice(&[tonemap_uniform]));
+        let sun_uniform = state.sun_direction().map(SunUniform::new).unwrap_or_default();
+        self.queue.write_buffer(&self.sun_buffer, 0, bytemuck::cast_slice(&[sun_uniform]));
+        let shadow_layout = ShadowLayout::new(&state.lights, &state.camera, aspect, &state.shadows);
+        let out_of_layers = shadow_layout.lights_out_of_layers > 0;
+        if out_of_layers && !self.shadows_out_of_layers {
+            log::warn!("The shadow map has no layers left, {} lights are drawn without shadows", shadow_layout.lights_out_of_layers);
+        }
+        self.shadows_out_of_layers = out_of_layers;
+        let mut lights_uniform = LightsUniform::new(&state.lights, state.ambient_light);
+        lights_uniform.set_shadow_layers(&shadow_layout);
+        self.update_environment(state.environment.as_ref());
+        if let Some(environment) = &state.environment {
+            lights_uniform.set_environment(environment.intensity);
+        }
+        self.queue.write_buffer(&self.lights_buffer, 0, bytemuck::cast_slice(&[lights_uniform]));
+        self.queue.write_buffer(
+            &self.shadow_maps.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[shadow_layout.to_uniform(&state.shadows)]),
+        );
 
 
 
@@ -157,31 +596,84 @@ impl RenderContext {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
-        
+
         // Begin render passes
-        let mut renderable_refs: HashMap<TypeId, Vec<&mut dyn Renderable>> = HashMap::new();
-        for renderable in state.renderables.iter_mut().map(|renderable|renderable){
-            let render_pass_type = renderable.get_render_pass_builder(self);
-            let renderable_ref = renderable.as_mut();            
-            renderable_refs.entry(render_pass_type).or_insert(vec![]).push(renderable_ref);
-        }
-        
-        for (render_pass_type, render_pass_builder) in &*RENDER_PASS_BUILDERS {
-            if !renderable_refs.contains_key(&render_pass_type) {
-                // if the render pass type is not in the renderable_refs, we skip it
-                continue;
-            }
-            let mut render_pass = render_pass_builder.create_render_pass(&mut encoder,&view, &self.depth_texture.view);
-            let renderables = renderable_refs.get_mut(&render_pass_type).unwrap();
-            for renderable in renderables {
-                renderable.render(&mut render_pass, self);
-            }
-            renderable_refs.remove(&render_pass_type); // remove the entry after using it
+        let renderable_pass_types: Vec<TypeId> = state.renderables.iter().map(|renderable| renderable.get_render_pass_builder(self)).collect();
+        // check if there is any render pass type that is not in the render graph
+        assert!(
+            renderable_pass_types.iter().all(|render_pass_type| self.render_graph.contains_pass(*render_pass_type)),
+            "There are render pass types that are not in the render graph"
+        );
+        let active_passes: HashSet<TypeId> = renderable_pass_types.iter().copied().collect();
+        let render_pass_types = self.render_graph.resolve(&active_passes);
+        self.render_graph.allocate_transient_attachments(&self.device, &self.config, self.sample_count, &render_pass_types);
+        let mut batch_stats = BatchStats::default();
+        let attachments = self.render_graph.attachments(&view, &self.depth_texture.view, &self.shadow_maps.view);
+        let frame = FrameInputs {
+            camera: &state.camera,
+            post_process: &state.post_process,
+            shadow_layout: &shadow_layout,
+        };
+        for render_pass_type in render_pass_types {
+            let render_pass_builder = self.render_graph.get_pass(render_pass_type);
+            // passes that draw on their own may have no renderables
+            let every_renderable = render_pass_builder.draws_every_renderable();
+            let mut renderables: Vec<&mut dyn Renderable> = state
+                .renderables
+                .iter_mut()
+                .zip(&renderable_pass_types)
+                .filter(|(_, pass_type)| every_renderable || **pass_type == render_pass_type)
+                .map(|(renderable, _)| renderable.as_mut() as &mut dyn Renderable)
+                .collect();
+            batch_stats.add(render_pass_builder.execute(&mut encoder, self, &attachments, &mut renderables, &frame));
         }
-        // check if there is any render pass type that is not in RENDER_PASS_BUILDERS
-        assert!(renderable_refs.is_empty(), "There are render pass types that are not in RENDER_PASS_BUILDERS");
+        log::debug!(
+            "{} renderables in {} draws, {} draws saved by batching",
+            batch_stats.renderables,
+            batch_stats.draws,
+            batch_stats.saved_draws()
+        );
+        self.batch_stats = batch_stats;
+        let target_texture = match &output {
+            Some(output) => &output.texture,
+            None => self.offscreen_texture.as_ref().unwrap(),
+        };
+        let readback = if !self.screenshot_requested {
+            None
+        } else if !target_texture.usage().contains(wgpu::TextureUsages::COPY_SRC)
+            || !FrameReadback::is_supported_format(target_texture.format())
+        {
+            log::warn!("Screenshot is not supported for the current render target");
+            None
+        } else {
+            Some(FrameReadback::new(&self.device, &mut encoder, target_texture))
+        };
+        self.screenshot_requested = false;
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        self.batch_buffers.end_frame();
+        if let Some(readback) = readback {
+            self.screenshot = Some(readback.into_image(&self.device));
+        }
+        if let Some(output) = output {
+            output.present();
+        }
+        // upload the assets whose files were read during the frame, the renderables draw them from the next frame on
+        self.assets.update(self);
         Ok(())
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedSampleCount {
+    pub sample_count: u32,
+    // the counts of the adapter, see supported_sample_counts
+    pub supported: Vec<u32>,
+}
+
+impl std::fmt::Display for UnsupportedSampleCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "A sample count of {} is not supported, the adapter supports {:?}", self.sample_count, self.supported)
+    }
+}
+
+impl std::error::Error for UnsupportedSampleCount {}
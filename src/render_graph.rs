@@ -0,0 +1,293 @@
+// passes declare the attachments they read and write, the graph derives the execution order from that
+
+use std::{
+    any::TypeId,
+    collections::{BTreeSet, HashMap, HashSet},
+};
+
+use crate::{
+    my_render_pass::RenderPassBuilder,
+    render_passes::{opauqe3d_render_pass::Opaque3DRenderPass, post_process_render_pass::PostProcessRenderPass, shadow_render_pass::ShadowRenderPass, tonemap_render_pass::TonemapRenderPass, transparent3d_render_pass::Transparent3DRenderPass, ui_render_pass::UiRenderPass},
+};
+
+// the texture that ends up on screen, or the offscreen texture of a headless context
+pub const SURFACE_COLOR: &str = "surface_color";
+// the depth texture of the render context
+pub const DEPTH: &str = "depth";
+// the floating point target the 3d passes render into, tonemapped onto SURFACE_COLOR
+pub const HDR_COLOR: &str = "hdr_color";
+// the second target of the post processing chain, effects go back and forth between it and HDR_COLOR
+pub const POST_COLOR: &str = "post_color";
+// the depth texture array of the shadow maps of the render context, one layer per cascade or spot light
+pub const SHADOW_MAP: &str = "shadow_map";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransientAttachment {
+    pub name: &'static str,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+    // while msaa is on, the graph also keeps a copy with the sample count of the render context,
+    // passes render into it through color_target and resolve it into the attachment
+    pub multisampled: bool,
+}
+
+pub struct RenderGraphAttachments<'a> {
+    views: HashMap<&'static str, &'a wgpu::TextureView>,
+    multisampled_views: HashMap<&'static str, &'a wgpu::TextureView>,
+}
+
+impl<'a> RenderGraphAttachments<'a> {
+    pub fn get(&self, name: &str) -> &'a wgpu::TextureView {
+        self.views
+            .get(name)
+            .copied()
+            .unwrap_or_else(|| panic!("Attachment {} is not declared by any render pass", name))
+    }
+
+    /// The view to render into and the view to resolve it into, if the attachment is multisampled at the moment.
+    pub fn color_target(&self, name: &str) -> (&'a wgpu::TextureView, Option<&'a wgpu::TextureView>) {
+        match self.multisampled_views.get(name) {
+            Some(multisampled_view) => (multisampled_view, Some(self.get(name))),
+            None => (self.get(name), None),
+        }
+    }
+}
+
+struct TransientTexture {
+    attachment: TransientAttachment,
+    width: u32,
+    height: u32,
+    sample_count: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    // only while the sample count is above 1
+    #[allow(unused)]
+    multisampled_texture: Option<wgpu::Texture>,
+    multisampled_view: Option<wgpu::TextureView>,
+}
+
+pub struct RenderGraph {
+    // registration order only breaks ties between passes that do not depend on each other
+    passes: Vec<(TypeId, Box<dyn RenderPassBuilder + Send + Sync>)>,
+    // resolved lazily, reset when a pass is added
+    sorted_passes: Option<Vec<usize>>,
+    transient_textures: HashMap<&'static str, TransientTexture>,
+}
+
+impl Default for RenderGraph {
+    fn default() -> Self {
+        let mut render_graph = Self {
+            passes: Vec::new(),
+            sorted_passes: None,
+            transient_textures: HashMap::new(),
+        };
+        render_graph.add_pass(ShadowRenderPass);
+        render_graph.add_pass(Opaque3DRenderPass);
+        render_graph.add_pass(Transparent3DRenderPass);
+        render_graph.add_pass(PostProcessRenderPass);
+        render_graph.add_pass(TonemapRenderPass);
+        render_graph.add_pass(UiRenderPass);
+        render_graph
+    }
+}
+
+impl RenderGraph {
+    pub fn add_pass<T: RenderPassBuilder + Send + Sync + 'static>(&mut self, pass: T) {
+        let type_id = TypeId::of::<T>();
+        assert!(!self.contains_pass(type_id), "Render pass is already in the render graph");
+        self.passes.push((type_id, Box::new(pass)));
+        self.sorted_passes = None;
+    }
+
+    pub fn contains_pass(&self, pass_type: TypeId) -> bool {
+        self.passes.iter().any(|(type_id, _)| *type_id == pass_type)
+    }
+
+    pub fn get_pass(&self, pass_type: TypeId) -> &(dyn RenderPassBuilder + Send + Sync) {
+        self.passes
+            .iter()
+            .find(|(type_id, _)| *type_id == pass_type)
+            .map(|(_, pass)| pass.as_ref())
+            .expect("Render pass is not in the render graph")
+    }
+
+    // Kahn's algorithm, always picking the earliest registered pass that is ready
+    fn sort_passes(&self) -> Vec<usize> {
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); self.passes.len()];
+        let mut num_dependencies = vec![0; self.passes.len()];
+        for (i, (_, pass)) in self.passes.iter().enumerate() {
+            let mut dependencies: BTreeSet<usize> = BTreeSet::new();
+            for attachment in pass.reads() {
+                for (j, (_, other)) in self.passes.iter().enumerate() {
+                    if i != j && other.writes().contains(&attachment) && !other.reads().contains(&attachment) {
+                        dependencies.insert(j);
+                    }
+                }
+            }
+            for dependency in pass.dependencies() {
+                match self.passes.iter().position(|(type_id, _)| *type_id == dependency) {
+                    Some(j) => {
+                        dependencies.insert(j);
+                    }
+                    // a dependency on a pass that is not registered does not constrain anything
+                    None => log::warn!("Render pass depends on a pass that is not in the render graph"),
+                }
+            }
+            num_dependencies[i] = dependencies.len();
+            for j in dependencies {
+                dependents[j].push(i);
+            }
+        }
+        let mut ready: BTreeSet<usize> = (0..self.passes.len()).filter(|i| num_dependencies[*i] == 0).collect();
+        let mut sorted = Vec::with_capacity(self.passes.len());
+        while let Some(i) = ready.pop_first() {
+            sorted.push(i);
+            for &j in &dependents[i] {
+                num_dependencies[j] -= 1;
+                if num_dependencies[j] == 0 {
+                    ready.insert(j);
+                }
+            }
+        }
+        assert!(sorted.len() == self.passes.len(), "There is a cycle in the render graph");
+        sorted
+    }
+
+    /// Returns the passes to execute this frame in order.
+    /// Passes without renderables are dropped unless they draw on their own, and so are passes whose output never reaches SURFACE_COLOR.
+    pub fn resolve(&mut self, active_passes: &HashSet<TypeId>) -> Vec<TypeId> {
+        if self.sorted_passes.is_none() {
+            self.sorted_passes = Some(self.sort_passes());
+        }
+        let sorted_passes = self.sorted_passes.as_ref().unwrap();
+        // passes that draw without renderables join once a pass they read from is active, earlier passes are decided first
+        let mut active_passes = active_passes.clone();
+        if !active_passes.is_empty() {
+            for (type_id, pass) in self.passes.iter() {
+                if pass.draws_every_renderable() {
+                    active_passes.insert(*type_id);
+                }
+            }
+        }
+        for &i in sorted_passes {
+            let (type_id, pass) = &self.passes[i];
+            let reads_active_pass = pass.reads().iter().any(|attachment| {
+                self.passes.iter().any(|(other_type, other)| {
+                    other_type != type_id && active_passes.contains(other_type) && other.writes().contains(attachment)
+                })
+            });
+            if pass.draws_without_renderables() && reads_active_pass {
+                active_passes.insert(*type_id);
+            }
+        }
+        // walk backwards from the final output, collecting what the kept passes still need
+        let mut needed_attachments: HashSet<&'static str> = HashSet::from([SURFACE_COLOR]);
+        let mut kept = Vec::new();
+        for &i in sorted_passes.iter().rev() {
+            let (type_id, pass) = &self.passes[i];
+            if !active_passes.contains(type_id) {
+                continue;
+            }
+            if !pass.writes().iter().any(|attachment| needed_attachments.contains(attachment)) {
+                continue;
+            }
+            needed_attachments.extend(pass.reads());
+            kept.push(*type_id);
+        }
+        kept.reverse();
+        kept
+    }
+
+    /// Creates or resizes the transient attachments of the given passes.
+    pub fn allocate_transient_attachments(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        passes: &[TypeId],
+    ) {
+        let width = config.width.max(1);
+        let height = config.height.max(1);
+        for pass_type in passes {
+            for attachment in self.get_pass(*pass_type).transient_attachments(config) {
+                let up_to_date = self.transient_textures.get(attachment.name).is_some_and(|existing| {
+                    existing.attachment == attachment
+                        && existing.width == width
+                        && existing.height == height
+                        && existing.sample_count == sample_count
+                });
+                if up_to_date {
+                    continue;
+                }
+                let create_texture = |sample_count: u32, usage: wgpu::TextureUsages| {
+                    device.create_texture(&wgpu::TextureDescriptor {
+                        label: Some(attachment.name),
+                        size: wgpu::Extent3d {
+                            width,
+                            height,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: attachment.format,
+                        usage: usage | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                        view_formats: &[],
+                    })
+                };
+                let texture = create_texture(1, attachment.usage);
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                // it is only rendered into and resolved
+                let multisampled_texture = (attachment.multisampled && sample_count > 1)
+                    .then(|| create_texture(sample_count, wgpu::TextureUsages::empty()));
+                let multisampled_view = multisampled_texture
+                    .as_ref()
+                    .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+                self.transient_textures.insert(
+                    attachment.name,
+                    TransientTexture {
+                        attachment,
+                        width,
+                        height,
+                        sample_count,
+                        texture,
+                        view,
+                        multisampled_texture,
+                        multisampled_view,
+                    },
+                );
+            }
+        }
+    }
+
+    // for copies, passes render through the views of attachments
+    pub fn transient_texture(&self, name: &str) -> &wgpu::Texture {
+        &self
+            .transient_textures
+            .get(name)
+            .unwrap_or_else(|| panic!("Transient attachment {} has not been allocated", name))
+            .texture
+    }
+
+    pub fn attachments<'a>(
+        &'a self,
+        surface_color: &'a wgpu::TextureView,
+        depth: &'a wgpu::TextureView,
+        shadow_map: &'a wgpu::TextureView,
+    ) -> RenderGraphAttachments<'a> {
+        let mut views: HashMap<&'static str, &'a wgpu::TextureView> = self
+            .transient_textures
+            .iter()
+            .map(|(name, transient)| (*name, &transient.view))
+            .collect();
+        views.insert(SURFACE_COLOR, surface_color);
+        views.insert(DEPTH, depth);
+        views.insert(SHADOW_MAP, shadow_map);
+        let multisampled_views = self
+            .transient_textures
+            .iter()
+            .filter_map(|(name, transient)| Some((*name, transient.multisampled_view.as_ref()?)))
+            .collect();
+        RenderGraphAttachments { views, multisampled_views }
+    }
+}
@@ -1,2 +1,6 @@
 pub mod opauqe3d_render_pass;
+pub mod post_process_render_pass;
+pub mod shadow_render_pass;
+pub mod tonemap_render_pass;
+pub mod transparent3d_render_pass;
 pub mod ui_render_pass;
\ No newline at end of file
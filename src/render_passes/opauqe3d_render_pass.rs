@@ -1,18 +1,26 @@
-use crate::my_render_pass::RenderPassBuilder;
+use crate::{my_render_pass::RenderPassBuilder, render_graph::{RenderGraphAttachments, DEPTH, HDR_COLOR, SHADOW_MAP}};
 
 
 pub struct Opaque3DRenderPass;
 
 impl RenderPassBuilder for Opaque3DRenderPass{
+    // the lit pipelines sample the shadow map
+    fn reads(&self) -> Vec<&'static str> {
+        vec![SHADOW_MAP]
+    }
+    fn writes(&self) -> Vec<&'static str> {
+        vec![HDR_COLOR, DEPTH]
+    }
     fn create_render_pass<'a>(
         &self,
         encoder: &'a mut wgpu::CommandEncoder,
-        color_view: &'a wgpu::TextureView,
-        depth_view: &'a wgpu::TextureView,
+        attachments: &RenderGraphAttachments<'a>,
     ) -> wgpu::RenderPass<'a> {
+        // rendered through the multisampled copy while msaa is on
+        let (view, resolve_target) = attachments.color_target(HDR_COLOR);
         let color_attachment = Some(wgpu::RenderPassColorAttachment {
-                view: &color_view,
-                resolve_target: None,
+                view,
+                resolve_target,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color {
                         r: 0.1,
@@ -24,7 +32,7 @@ impl RenderPassBuilder for Opaque3DRenderPass{
                 },
             });
         let depth_stencil_attachment = wgpu::RenderPassDepthStencilAttachment {
-            view: depth_view,
+            view: attachments.get(DEPTH),
             depth_ops: Some(wgpu::Operations {
                 load: wgpu::LoadOp::Clear(1.0),
                 store: wgpu::StoreOp::Store,
@@ -40,4 +48,4 @@ impl RenderPassBuilder for Opaque3DRenderPass{
         };
         encoder.begin_render_pass(&render_pass_descriptor)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,64 @@
+use std::any::TypeId;
+
+use crate::{
+    batching::BatchStats, my_render_pass::{FrameInputs, RenderPassBuilder}, render_context::RenderContext, renderable::Renderable, render_graph::{RenderGraphAttachments, TransientAttachment, HDR_COLOR, POST_COLOR}, render_passes::transparent3d_render_pass::Transparent3DRenderPass
+};
+
+// stands for the post processing chain of the state in the render graph.
+// Every effect needs render passes of its own, so it runs PostProcessStack::render instead of a single pass
+pub struct PostProcessRenderPass;
+
+impl RenderPassBuilder for PostProcessRenderPass{
+    fn reads(&self) -> Vec<&'static str> {
+        vec![HDR_COLOR]
+    }
+    fn writes(&self) -> Vec<&'static str> {
+        vec![HDR_COLOR]
+    }
+    fn dependencies(&self) -> Vec<TypeId> {
+        vec![TypeId::of::<Transparent3DRenderPass>()]
+    }
+    fn transient_attachments(&self, _config: &wgpu::SurfaceConfiguration) -> Vec<TransientAttachment> {
+        vec![TransientAttachment {
+            name: POST_COLOR,
+            format: RenderContext::HDR_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            multisampled: false,
+        }]
+    }
+    fn draws_without_renderables(&self) -> bool {
+        true
+    }
+    // keeps the hdr target as it is, the effects record their passes in execute
+    fn create_render_pass<'a>(
+        &self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        attachments: &RenderGraphAttachments<'a>,
+    ) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post Process Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: attachments.get(HDR_COLOR),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+    fn execute<'a>(
+        &self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        render_context: &RenderContext,
+        attachments: &RenderGraphAttachments<'a>,
+        _renderables: &mut [&mut dyn Renderable],
+        frame: &FrameInputs,
+    ) -> BatchStats {
+        frame.post_process.render(encoder, render_context, attachments);
+        BatchStats::default()
+    }
+}
@@ -0,0 +1,34 @@
+use crate::{
+    batching::BatchStats, my_render_pass::{FrameInputs, RenderPassBuilder}, render_context::RenderContext, renderable::Renderable, render_graph::{RenderGraphAttachments, SHADOW_MAP}
+};
+
+// renders the depth of the shadow casters into the layers of the shadow map, for the lit passes that sample it.
+// A layer takes a render pass of its own, so it runs ShadowMaps::render instead of a single pass
+pub struct ShadowRenderPass;
+
+impl RenderPassBuilder for ShadowRenderPass {
+    fn writes(&self) -> Vec<&'static str> {
+        vec![SHADOW_MAP]
+    }
+    fn draws_every_renderable(&self) -> bool {
+        true
+    }
+    fn create_render_pass<'a>(
+        &self,
+        _encoder: &'a mut wgpu::CommandEncoder,
+        _attachments: &RenderGraphAttachments<'a>,
+    ) -> wgpu::RenderPass<'a> {
+        unreachable!("The shadow pass records a render pass per layer in execute")
+    }
+    fn execute<'a>(
+        &self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        render_context: &RenderContext,
+        _attachments: &RenderGraphAttachments<'a>,
+        renderables: &mut [&mut dyn Renderable],
+        frame: &FrameInputs,
+    ) -> BatchStats {
+        render_context.shadow_maps.render(encoder, render_context, frame.shadow_layout, renderables);
+        BatchStats::default()
+    }
+}
@@ -0,0 +1,64 @@
+use std::any::TypeId;
+
+use crate::{
+    my_render_pass::RenderPassBuilder, pipelines::tonemap_pipeline::TonemapPipeline, render_context::RenderContext, render_graph::{RenderGraphAttachments, TransientAttachment, HDR_COLOR, SURFACE_COLOR}, render_passes::{post_process_render_pass::PostProcessRenderPass, transparent3d_render_pass::Transparent3DRenderPass}, renderable::{get_pipeline_from_cache, unpack_pipeline}
+};
+
+pub struct TonemapRenderPass;
+
+impl RenderPassBuilder for TonemapRenderPass{
+    fn reads(&self) -> Vec<&'static str> {
+        vec![HDR_COLOR]
+    }
+    fn writes(&self) -> Vec<&'static str> {
+        vec![SURFACE_COLOR]
+    }
+    // the transparent geometry and the post processing have to be in the hdr target as well
+    fn dependencies(&self) -> Vec<TypeId> {
+        vec![TypeId::of::<Transparent3DRenderPass>(), TypeId::of::<PostProcessRenderPass>()]
+    }
+    fn transient_attachments(&self, _config: &wgpu::SurfaceConfiguration) -> Vec<TransientAttachment> {
+        vec![TransientAttachment {
+            name: HDR_COLOR,
+            format: RenderContext::HDR_FORMAT,
+            // the post processing chain may copy its result back into it
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            // the 3d passes resolve into it
+            multisampled: true,
+        }]
+    }
+    fn draws_without_renderables(&self) -> bool {
+        true
+    }
+    fn draw(&self, render_pass: &mut wgpu::RenderPass, render_context: &RenderContext, attachments: &RenderGraphAttachments) {
+        let pipeline = get_pipeline_from_cache(TypeId::of::<TonemapPipeline>(), render_context);
+        let pipeline = unpack_pipeline(&pipeline);
+        let bind_group = TonemapPipeline::create_bind_group(render_context, attachments.get(HDR_COLOR));
+        render_pass.set_pipeline(&pipeline.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+    fn create_render_pass<'a>(
+        &self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        attachments: &RenderGraphAttachments<'a>,
+    ) -> wgpu::RenderPass<'a> {
+        let color_attachment = Some(wgpu::RenderPassColorAttachment {
+                view: attachments.get(SURFACE_COLOR),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // every pixel is overwritten
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            });
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[color_attachment],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        };
+        encoder.begin_render_pass(&render_pass_descriptor)
+    }
+}
@@ -0,0 +1,53 @@
+use std::any::TypeId;
+
+use crate::{my_render_pass::RenderPassBuilder, render_graph::{RenderGraphAttachments, DEPTH, HDR_COLOR}, render_passes::opauqe3d_render_pass::Opaque3DRenderPass};
+
+pub struct Transparent3DRenderPass;
+
+impl RenderPassBuilder for Transparent3DRenderPass{
+    // blends over the opaque geometry and is depth tested against it
+    fn reads(&self) -> Vec<&'static str> {
+        vec![HDR_COLOR, DEPTH]
+    }
+    fn writes(&self) -> Vec<&'static str> {
+        vec![HDR_COLOR]
+    }
+    fn dependencies(&self) -> Vec<TypeId> {
+        vec![TypeId::of::<Opaque3DRenderPass>()]
+    }
+    fn sorts_back_to_front(&self) -> bool {
+        true
+    }
+    fn create_render_pass<'a>(
+        &self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        attachments: &RenderGraphAttachments<'a>,
+    ) -> wgpu::RenderPass<'a> {
+        // rendered through the multisampled copy while msaa is on
+        let (view, resolve_target) = attachments.color_target(HDR_COLOR);
+        let color_attachment = Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            });
+        let depth_stencil_attachment = wgpu::RenderPassDepthStencilAttachment {
+            view: attachments.get(DEPTH),
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        };
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Transparent Render Pass"),
+            color_attachments: &[color_attachment],
+            depth_stencil_attachment: Some(depth_stencil_attachment),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        };
+        encoder.begin_render_pass(&render_pass_descriptor)
+    }
+}
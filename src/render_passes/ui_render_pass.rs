@@ -1,32 +1,34 @@
-use crate::my_render_pass::RenderPassBuilder;
+use std::any::TypeId;
+
+use crate::{my_render_pass::RenderPassBuilder, render_graph::{RenderGraphAttachments, SURFACE_COLOR}, render_passes::transparent3d_render_pass::Transparent3DRenderPass};
 
 pub struct UiRenderPass;
 
 impl RenderPassBuilder for UiRenderPass{
+    // the ui is drawn on top of whatever has been rendered to the surface
+    fn reads(&self) -> Vec<&'static str> {
+        vec![SURFACE_COLOR]
+    }
+    fn writes(&self) -> Vec<&'static str> {
+        vec![SURFACE_COLOR]
+    }
+    // ui always overlays on top of game objects, including transparent ones
+    fn dependencies(&self) -> Vec<TypeId> {
+        vec![TypeId::of::<Transparent3DRenderPass>()]
+    }
     fn create_render_pass<'a>(
         &self,
         encoder: &'a mut wgpu::CommandEncoder,
-        color_view: &'a wgpu::TextureView,
-        depth_view: &'a wgpu::TextureView,
+        attachments: &RenderGraphAttachments<'a>,
     ) -> wgpu::RenderPass<'a> {
-        let _ = depth_view;
         let color_attachment = Some(wgpu::RenderPassColorAttachment {
-                view: &color_view,
+                view: attachments.get(SURFACE_COLOR),
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Load, // For UI render pass, we typically load the existing content of the texture
                     store: wgpu::StoreOp::Store,
                 },
             });
-        // In a UI render pass, we do not use a depth buffer
-        // let depth_stencil_attachment = wgpu::RenderPassDepthStencilAttachment {
-        //     view: depth_view,
-        //     depth_ops: Some(wgpu::Operations {
-        //         load: wgpu::LoadOp::Clear(1.0),
-        //         store: wgpu::StoreOp::Store,
-        //     }),
-        //     stencil_ops: None,
-        // };
         let render_pass_descriptor = wgpu::RenderPassDescriptor {
             label: Some("Render Pass"),
             color_attachments: &[color_attachment],
@@ -36,4 +38,4 @@ impl RenderPassBuilder for UiRenderPass{
         };
         encoder.begin_render_pass(&render_pass_descriptor)
     }
-}
\ No newline at end of file
+}
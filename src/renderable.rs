@@ -1,24 +1,27 @@
 // a cache that returns an object that implements a trait Render
 
 use std::any::TypeId;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use lazy_static::lazy_static;
-use wgpu::RenderPipeline;
+use cgmath::EuclideanSpace;
 
-use crate::cache::{self, CacheValue, CACHE};
+use crate::cache::{self, CacheValue};
+use crate::camera::Camera;
+use crate::instance::InstanceRaw;
 use crate::my_pipeline::{MyPipeline, PIPELINE_BUILDERS};
-use crate::render_context::{self, RenderContext};
+use crate::render_context::RenderContext;
+use crate::transform::Transform;
+use crate::vertex::{Vertex, VertexLayout};
 
-fn get_pipeline_from_cache(pipeline_type: TypeId, render_context: &RenderContext)->Arc<CacheValue>{
-    CACHE.get_with(cache::CacheKey::Pipeline(pipeline_type), || {
+pub(crate) fn get_pipeline_from_cache(pipeline_type: TypeId, render_context: &RenderContext)->Arc<CacheValue>{
+    render_context.cache.get_with(cache::CacheKey::Pipeline(pipeline_type), || {
         let pipeline = PIPELINE_BUILDERS.get(&pipeline_type).expect("Pipeline builder not found")
             .build_pipeline(render_context);
         Arc::new(CacheValue::Pipeline(pipeline))
     })
 }
 
-fn unpack_pipeline(pipeline: &Arc<CacheValue>) -> &MyPipeline {
+pub(crate) fn unpack_pipeline(pipeline: &Arc<CacheValue>) -> &MyPipeline {
     if let CacheValue::Pipeline(my_pipeline) = pipeline.as_ref() {
         my_pipeline
     } else {
@@ -26,29 +29,108 @@ fn unpack_pipeline(pipeline: &Arc<CacheValue>) -> &MyPipeline {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexFormat {
+    Uint16,
+    // for meshes with more than 65536 vertices
+    Uint32,
+    // the vertices are drawn in order, get_num_vertices tells how many
+    None,
+}
+
+impl IndexFormat {
+    pub fn to_wgpu(self) -> Option<wgpu::IndexFormat> {
+        match self {
+            IndexFormat::Uint16 => Some(wgpu::IndexFormat::Uint16),
+            IndexFormat::Uint32 => Some(wgpu::IndexFormat::Uint32),
+            IndexFormat::None => None,
+        }
+    }
+}
+
 pub trait Renderable {
     fn choose_pipeline(&self) -> TypeId;
     fn get_vertex_buffer(&self, render_context: &RenderContext) -> Arc<wgpu::Buffer>;
-    fn get_index_buffer(&self, render_context: &RenderContext) -> Arc<wgpu::Buffer>;
+    // None for renderables without indices
+    fn get_index_buffer(&self, render_context: &RenderContext) -> Option<Arc<wgpu::Buffer>>;
     fn get_bind_groups<'a>(&'a mut self, render_context: &'a RenderContext) -> Vec<&'a wgpu::BindGroup>;
+    // the attributes in the vertex buffer, they have to be the ones the pipeline expects
+    fn get_vertex_layout(&self) -> VertexLayout {
+        Vertex::LAYOUT
+    }
     fn get_num_indices(&self) -> u32;
+    fn get_index_format(&self) -> IndexFormat {
+        IndexFormat::Uint16
+    }
+    // only used when the index format is None
+    fn get_num_vertices(&self) -> u32 {
+        0
+    }
+    // bound as the second vertex buffer, for pipelines that draw instances
+    fn get_instance_buffer(&mut self, render_context: &RenderContext) -> Option<Arc<wgpu::Buffer>> {
+        let _ = render_context;
+        None
+    }
+    fn get_num_instances(&self) -> u32 {
+        1
+    }
+    // renderables that return true are merged with the ones drawn with the same pipeline, buffers and bind groups
+    // into one instanced draw. They draw from their instance buffer while nothing is merged with them
+    fn is_batched(&self) -> bool {
+        false
+    }
+    // world space instance data, only asked for by batches with more than one renderable
+    fn get_instances(&self) -> Option<Vec<InstanceRaw>> {
+        None
+    }
+    fn get_transform(&self) -> Transform {
+        Transform::default()
+    }
+    // None if the renderable cannot be moved, like the skybox or the ui
+    fn get_transform_mut(&mut self) -> Option<&mut Transform> {
+        None
+    }
+    // rendered into the shadow maps of the lights, from the vertex and instance buffers
+    fn casts_shadows(&self) -> bool {
+        false
+    }
+    // world space position, used to sort transparent renderables
+    fn get_position(&self) -> cgmath::Point3<f32> {
+        cgmath::Point3::from_vec(self.get_transform().position)
+    }
     fn render(&mut self, render_pass: &mut wgpu::RenderPass,
          render_context: &RenderContext,
     ){
+        let num_instances = self.get_num_instances();
+        if num_instances == 0 {
+            return;
+        }
         let pipeline_type = self.choose_pipeline();
         let pipeline = get_pipeline_from_cache(pipeline_type, render_context);
         let pipeline = unpack_pipeline(&pipeline);
         render_pass.set_pipeline(&pipeline.pipeline);
+        let instance_buffer = self.get_instance_buffer(render_context);
         let vertex_buffer = self.get_vertex_buffer(render_context);
         let index_buffer = self.get_index_buffer(render_context);
+        let index_format = self.get_index_format();
         let num_indices = self.get_num_indices();
+        let num_vertices = self.get_num_vertices();
         let bind_groups = self.get_bind_groups(render_context);        
         for (i, bind_group) in bind_groups.iter().enumerate() {
             render_pass.set_bind_group(i as u32, *bind_group, &[]);
         }
         render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..num_indices, 0, 0..1);
+        if let Some(instance_buffer) = &instance_buffer {
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        }
+        match index_format.to_wgpu() {
+            Some(index_format) => {
+                let index_buffer = index_buffer.expect("Indexed renderable has no index buffer");
+                render_pass.set_index_buffer(index_buffer.slice(..), index_format);
+                render_pass.draw_indexed(0..num_indices, 0, 0..num_instances);
+            }
+            None => render_pass.draw(0..num_vertices, 0..num_instances),
+        }
     }
     fn get_render_pass_builder(&self, render_context: &RenderContext) -> TypeId {
         let pipeline_type = self.choose_pipeline();
@@ -58,6 +140,39 @@ pub trait Renderable {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexLayoutMismatch {
+    pub expected: VertexLayout,
+    pub actual: VertexLayout,
+}
+
+impl std::fmt::Display for VertexLayoutMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The pipeline expects the vertex layout {:?}, but the renderable provides {:?}", self.expected, self.actual)
+    }
+}
+
+impl std::error::Error for VertexLayoutMismatch {}
+
+/// Checks that the vertex buffer of a renderable has the layout its pipeline reads.
+pub fn validate_vertex_layout(renderable: &dyn Renderable) -> Result<(), VertexLayoutMismatch> {
+    let expected = PIPELINE_BUILDERS.get(&renderable.choose_pipeline()).expect("Pipeline builder not found").vertex_layout();
+    let actual = renderable.get_vertex_layout();
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(VertexLayoutMismatch { expected, actual })
+    }
+}
+
+/// Sorts renderables so that the one farthest from the camera comes first.
+pub fn sort_back_to_front(renderables: &mut [&mut dyn Renderable], camera: &Camera) {
+    let view = camera.build_view_matrix();
+    // the camera looks down -z in view space, so the farthest renderable has the smallest z
+    let view_z = |renderable: &dyn Renderable| (view * renderable.get_position().to_homogeneous()).z;
+    renderables.sort_by(|a, b| view_z(&**a).total_cmp(&view_z(&**b)));
+}
+
 // lazy_static! {
 //     pub static ref RENDERABLES: Mutex<Vec<Box<dyn Renderable + Send + Sync>>> =
 //         Mutex::new(Default::default());
@@ -1,106 +1,156 @@
-use std::{any::TypeId, collections::HashMap, sync::{Arc, Mutex}};
-
-use lazy_static::lazy_static;
-use wgpu::util::DeviceExt;
+use std::{any::TypeId, sync::{Arc, OnceLock}};
 
 use crate::{
-    pipelines::default_pipeline::DefaultPipeline, render_context::RenderContext,  renderable::Renderable, my_texture::{MyTexture, TextureSource}, vertex::Vertex
+    assets::Handle, instance::{Instance, InstanceBuffer, InstanceRaw}, pipelines::{default_pipeline::DefaultPipeline, transparent_pipeline::TransparentPipeline}, render_context::RenderContext,  renderable::Renderable, renderables::mesh::{builtin_mesh, MeshData, MeshSource}, my_texture::{MyTexture, TextureOptions, TextureSource}, transform::Transform, vertex::Vertex
 };
 
 pub struct Cube{
     texture_file_path: String,
+    texture_options: TextureOptions,
+    texture: Option<Handle<MyTexture>>,
+    texture_bind_group: Option<Arc<wgpu::BindGroup>>,
+    // the bind group holds the placeholder until the texture is loaded
+    texture_loading: bool,
+    mesh: OnceLock<Handle<MeshData>>,
+    transparent: bool,
+    pub transform: Transform,
+    instances: InstanceBuffer,
 }
 impl Cube{
     pub fn new(texture_file_path: String) -> Self {
         Self {
             texture_file_path,
+            texture_options: TextureOptions::default(),
+            texture: None,
+            texture_bind_group: None,
+            texture_loading: false,
+            mesh: OnceLock::new(),
+            transparent: false,
+            transform: Transform::default(),
+            instances: InstanceBuffer::default(),
+        }
+    }
+    // the texture's alpha channel is blended in the transparent render pass
+    pub fn new_transparent(texture_file_path: String) -> Self {
+        Self {
+            transparent: true,
+            ..Self::new(texture_file_path)
         }
     }
+    pub fn with_texture_options(mut self, texture_options: TextureOptions) -> Self {
+        self.texture_options = texture_options;
+        self.texture = None;
+        self.texture_bind_group = None;
+        self
+    }
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+    // instances are placed relative to the transform of the cube, all of them are drawn in one call
+    pub fn with_instances(mut self, instances: Vec<Instance>) -> Self {
+        self.instances = InstanceBuffer::new(instances);
+        self
+    }
+    pub fn instances(&self) -> &[Instance] {
+        self.instances.instances()
+    }
+    pub fn instances_mut(&mut self) -> &mut Vec<Instance> {
+        self.instances.instances_mut()
+    }
 }
 
 
 impl Renderable for Cube {
     fn choose_pipeline(&self) -> TypeId {
-        TypeId::of::<DefaultPipeline>()
+        if self.transparent {
+            TypeId::of::<TransparentPipeline>()
+        } else {
+            TypeId::of::<DefaultPipeline>()
+        }
     }
     fn get_vertex_buffer(&self, render_context: &RenderContext) -> Arc<wgpu::Buffer> {
-        VERTEX_BUFFER.lock().unwrap().get_or_insert_with(||{
-            let vertex_buffer = render_context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&VERTICES),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-            Arc::new(vertex_buffer)
-        }).clone()
-    }
-    fn get_index_buffer(&self, render_context: &RenderContext) -> Arc<wgpu::Buffer> {
-        INDEX_BUFFER.lock().unwrap().get_or_insert_with(||{
-            let index_buffer = render_context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(&INDICES),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-            Arc::new(index_buffer)
-        }).clone()
+        builtin_mesh(&self.mesh, render_context, MeshSource::Cube).vertex_buffer(render_context, Vertex::LAYOUT)
+    }
+    fn get_index_buffer(&self, render_context: &RenderContext) -> Option<Arc<wgpu::Buffer>> {
+        builtin_mesh(&self.mesh, render_context, MeshSource::Cube).index_buffer(render_context)
     }
     fn get_bind_groups<'a>(&'a mut self, render_context: &'a RenderContext) -> Vec<&'a wgpu::BindGroup> {
-        let texture = TEXTURES.lock().unwrap().entry(self.texture_file_path.clone()).or_insert_with(|| {
-            let texture = MyTexture::load(TextureSource::FilePath(self.texture_file_path.clone()), render_context, Some("cube texture")).unwrap();
-            Arc::new(texture)
-        }).clone();
-        let bind_groups: Vec<&'a wgpu::BindGroup> = DefaultPipeline::create_bind_groups(render_context, &texture);
+        if self.texture_bind_group.is_none() || self.texture_loading {
+            let texture = self.texture.get_or_insert_with(|| {
+                render_context.request_asset((TextureSource::FilePath(self.texture_file_path.clone()), self.texture_options))
+            });
+            self.texture_bind_group = Some(DefaultPipeline::get_texture_bind_group(render_context, texture));
+            self.texture_loading = texture.is_loading();
+        }
+        let bind_groups: Vec<&'a wgpu::BindGroup> = DefaultPipeline::create_bind_groups(render_context, self.texture_bind_group.as_ref().unwrap());
         bind_groups
     }
     fn get_num_indices(&self) -> u32 {
         INDICES.len() as u32
     }
-    // functions to load the data, but where to store them?
+    fn get_instance_buffer(&mut self, render_context: &RenderContext) -> Option<Arc<wgpu::Buffer>> {
+        Some(self.instances.get_buffer(render_context, &self.transform.to_matrix()))
+    }
+    fn is_batched(&self) -> bool {
+        true
+    }
+    fn get_instances(&self) -> Option<Vec<InstanceRaw>> {
+        Some(self.instances.to_raw(&self.transform.to_matrix()))
+    }
+    fn get_num_instances(&self) -> u32 {
+        self.instances.len()
+    }
+    fn casts_shadows(&self) -> bool {
+        !self.transparent
+    }
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+    fn get_transform_mut(&mut self) -> Option<&mut Transform> {
+        Some(&mut self.transform)
+    }
 }
 
-lazy_static!{
-    #[rustfmt::skip]
-    static ref VERTICES: &'static [Vertex] = &[
-        // Front face (Z = 1)
-        Vertex {position: [-1.0, -1.0, 1.0],tex_coords: [1./4., 2./3.],},
-        Vertex {position: [1.0, -1.0, 1.0],tex_coords: [2./4., 2./3.],},
-        Vertex {position: [1.0, 1.0, 1.0],tex_coords: [2./4., 1./3.],},
-        Vertex {position: [-1.0, 1.0, 1.0],tex_coords: [1./4., 1./3.],},
-        // Back face (Z = -1)
-        Vertex {position: [-1.0, -1.0, -1.0],tex_coords: [3./4., 2./3.],},
-        Vertex {position: [1.0, -1.0, -1.0],tex_coords: [1.0, 2./3.],},
-        Vertex {position: [1.0, 1.0, -1.0],tex_coords: [1.0, 1./3.],},
-        Vertex {position: [-1.0, 1.0, -1.0],tex_coords: [3./4., 1./3.],},
-        // Left face (X = -1)
-        Vertex {position: [-1.0, -1.0, -1.0],tex_coords: [0.0, 2./3.],},
-        Vertex {position: [-1.0, -1.0, 1.0],tex_coords: [1./4., 2./3.],},
-        Vertex {position: [-1.0, 1.0, 1.0],tex_coords: [1./4., 1./3.],},
-        Vertex {position: [-1.0, 1.0, -1.0],tex_coords: [0.0, 1./3.],},
-        // Right face (X = 1)
-        Vertex {position: [1.0, -1.0, -1.0],tex_coords: [0.0, 0.0],},
-        Vertex {position: [1.0, 1.0, -1.0],tex_coords: [0.0, 1.0],},
-        Vertex {position: [1.0, 1.0, 1.0],tex_coords: [1.0, 1.0],},
-        Vertex {position: [1.0, -1.0, 1.0],tex_coords: [1.0, 0.0],},
-        // Top face (Y = 1)
-        Vertex {position: [-1.0, 1.0, -1.0],tex_coords: [0.0, 0.0],},
-        Vertex {position: [-1.0, 1.0, 1.0],tex_coords: [0.0, 1.0],},
-        Vertex {position: [1.0, 1.0, 1.0],tex_coords: [1.0, 1.0],},
-        Vertex {position: [1.0, 1.0, -1.0],tex_coords: [1.0, 0.0],},
-        // Bottom face (Y = -1)
-        Vertex {position: [-1.0, -1.0, -1.0],tex_coords: [0.0, 0.0],},
-        Vertex {position: [1.0, -1.0, -1.0],tex_coords: [1.0, 0.0],},
-        Vertex {position: [1.0, -1.0, 1.0],tex_coords: [1.0, 1.0],},
-        Vertex {position: [-1.0, -1.0, 1.0],tex_coords: [0.0, 1.0],},
-    ];
-    #[rustfmt::skip]
-    static ref INDICES: &'static [u16] = &[
-        0, 1, 2, 2, 3, 0, // Front
-        4, 6, 5, 6, 4, 7, // Back
-        8, 9, 10, 10, 11, 8, // Left
-        12, 13, 14, 14, 15, 12, // Right
-        16, 17, 18, 18, 19, 16, // Top
-        20, 21, 22, 22, 23, 20, // Bottom
-    ];
-    static ref VERTEX_BUFFER: Mutex<Option<Arc<wgpu::Buffer>>> = Mutex::new(None);
-    static ref INDEX_BUFFER: Mutex<Option<Arc<wgpu::Buffer>>> = Mutex::new(None);
-    static ref TEXTURES: Mutex<HashMap<String, Arc<MyTexture>>> = Mutex::new(HashMap::new());
-}
\ No newline at end of file
+#[rustfmt::skip]
+pub(crate) static VERTICES: &[Vertex] = &[
+    // Front face (Z = 1)
+    Vertex {position: [-1.0, -1.0, 1.0],tex_coords: [1./4., 2./3.],},
+    Vertex {position: [1.0, -1.0, 1.0],tex_coords: [2./4., 2./3.],},
+    Vertex {position: [1.0, 1.0, 1.0],tex_coords: [2./4., 1./3.],},
+    Vertex {position: [-1.0, 1.0, 1.0],tex_coords: [1./4., 1./3.],},
+    // Back face (Z = -1)
+    Vertex {position: [-1.0, -1.0, -1.0],tex_coords: [3./4., 2./3.],},
+    Vertex {position: [1.0, -1.0, -1.0],tex_coords: [1.0, 2./3.],},
+    Vertex {position: [1.0, 1.0, -1.0],tex_coords: [1.0, 1./3.],},
+    Vertex {position: [-1.0, 1.0, -1.0],tex_coords: [3./4., 1./3.],},
+    // Left face (X = -1)
+    Vertex {position: [-1.0, -1.0, -1.0],tex_coords: [0.0, 2./3.],},
+    Vertex {position: [-1.0, -1.0, 1.0],tex_coords: [1./4., 2./3.],},
+    Vertex {position: [-1.0, 1.0, 1.0],tex_coords: [1./4., 1./3.],},
+    Vertex {position: [-1.0, 1.0, -1.0],tex_coords: [0.0, 1./3.],},
+    // Right face (X = 1)
+    Vertex {position: [1.0, -1.0, -1.0],tex_coords: [0.0, 0.0],},
+    Vertex {position: [1.0, 1.0, -1.0],tex_coords: [0.0, 1.0],},
+    Vertex {position: [1.0, 1.0, 1.0],tex_coords: [1.0, 1.0],},
+    Vertex {position: [1.0, -1.0, 1.0],tex_coords: [1.0, 0.0],},
+    // Top face (Y = 1)
+    Vertex {position: [-1.0, 1.0, -1.0],tex_coords: [0.0, 0.0],},
+    Vertex {position: [-1.0, 1.0, 1.0],tex_coords: [0.0, 1.0],},
+    Vertex {position: [1.0, 1.0, 1.0],tex_coords: [1.0, 1.0],},
+    Vertex {position: [1.0, 1.0, -1.0],tex_coords: [1.0, 0.0],},
+    // Bottom face (Y = -1)
+    Vertex {position: [-1.0, -1.0, -1.0],tex_coords: [0.0, 0.0],},
+    Vertex {position: [1.0, -1.0, -1.0],tex_coords: [1.0, 0.0],},
+    Vertex {position: [1.0, -1.0, 1.0],tex_coords: [1.0, 1.0],},
+    Vertex {position: [-1.0, -1.0, 1.0],tex_coords: [0.0, 1.0],},
+];
+#[rustfmt::skip]
+pub(crate) static INDICES: &[u16] = &[
+    0, 1, 2, 2, 3, 0, // Front
+    4, 6, 5, 6, 4, 7, // Back
+    8, 9, 10, 10, 11, 8, // Left
+    12, 13, 14, 14, 15, 12, // Right
+    16, 17, 18, 18, 19, 16, // Top
+    20, 21, 22, 22, 23, 20, // Bottom
+];
@@ -0,0 +1,474 @@
+use std::{any::TypeId, collections::HashMap, path::Path, sync::{Arc, Mutex, OnceLock}};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    assets::{Asset, AssetError, AssetServer, Handle}, instance::{Instance, InstanceBuffer, InstanceRaw}, material::{AlphaMode, Material}, my_pipeline::PipelineBuilder, my_texture::{MyTexture, TextureOptions, TextureSource}, pipelines::{default_pipeline::DefaultPipeline, lit_pipeline::LitPipeline, pbr_pipeline::PbrPipeline, transparent_pipeline::TransparentPipeline}, render_context::RenderContext, renderable::{IndexFormat, Renderable, VertexLayoutMismatch}, renderables::{cube, skybox, ui}, transform::Transform, vertex::{MeshVertex, Vertex, VertexAttribute, VertexLayout}
+};
+
+pub enum MeshIndices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    // the vertices form the triangles in order
+    None,
+}
+
+impl MeshIndices {
+    // 16 bit indices are used whenever they can address every vertex
+    pub fn new(indices: Vec<u32>, num_vertices: usize) -> Self {
+        if num_vertices > u16::MAX as usize {
+            MeshIndices::U32(indices)
+        } else {
+            MeshIndices::U16(indices.into_iter().map(|index| index as u16).collect())
+        }
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            MeshIndices::U16(indices) => indices.len(),
+            MeshIndices::U32(indices) => indices.len(),
+            MeshIndices::None => 0,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn format(&self) -> IndexFormat {
+        match self {
+            MeshIndices::U16(_) => IndexFormat::Uint16,
+            MeshIndices::U32(_) => IndexFormat::Uint32,
+            MeshIndices::None => IndexFormat::None,
+        }
+    }
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            MeshIndices::U16(indices) => bytemuck::cast_slice(indices),
+            MeshIndices::U32(indices) => bytemuck::cast_slice(indices),
+            MeshIndices::None => &[],
+        }
+    }
+}
+
+// the geometry of a mesh, shared by every copy of it. The gpu buffers are created the first time it is drawn
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<MeshVertex>,
+    // the attributes the vertices were created with, the others keep their default value
+    pub attributes: VertexLayout,
+    pub indices: MeshIndices,
+    // one buffer for every layout the mesh is drawn with
+    vertex_buffers: Mutex<HashMap<VertexLayout, Arc<wgpu::Buffer>>>,
+    index_buffer: OnceLock<Arc<wgpu::Buffer>>,
+}
+
+impl MeshData {
+    pub fn new(name: String, vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        let vertices = vertices.into_iter().map(MeshVertex::from).collect();
+        Self::from_mesh_vertices(name, vertices, Vertex::LAYOUT, Some(indices))
+    }
+    pub fn new_non_indexed(name: String, vertices: Vec<Vertex>) -> Self {
+        let vertices = vertices.into_iter().map(MeshVertex::from).collect();
+        Self::from_mesh_vertices(name, vertices, Vertex::LAYOUT, None)
+    }
+    // without indices the vertices form the triangles in order
+    pub fn from_mesh_vertices(name: String, vertices: Vec<MeshVertex>, attributes: VertexLayout, indices: Option<Vec<u32>>) -> Self {
+        let indices = match indices {
+            Some(indices) => MeshIndices::new(indices, vertices.len()),
+            None => MeshIndices::None,
+        };
+        Self {
+            name,
+            vertices,
+            attributes,
+            indices,
+            vertex_buffers: Mutex::new(HashMap::new()),
+            index_buffer: OnceLock::new(),
+        }
+    }
+    // packed with the attributes of the layout, the buffer is made once for every layout
+    pub fn vertex_buffer(&self, render_context: &RenderContext, vertex_layout: VertexLayout) -> Arc<wgpu::Buffer> {
+        self.vertex_buffers.lock().unwrap().entry(vertex_layout).or_insert_with(|| {
+            let vertex_buffer = render_context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Vertex Buffer", self.name)),
+                contents: &vertex_layout.pack(&self.vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+            Arc::new(vertex_buffer)
+        }).clone()
+    }
+    // None without indices
+    pub fn index_buffer(&self, render_context: &RenderContext) -> Option<Arc<wgpu::Buffer>> {
+        if self.indices.format() == IndexFormat::None {
+            return None;
+        }
+        let index_buffer = self.index_buffer.get_or_init(|| {
+            let index_buffer = render_context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Index Buffer", self.name)),
+                contents: self.indices.as_bytes(),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+            Arc::new(index_buffer)
+        });
+        Some(index_buffer.clone())
+    }
+}
+
+// the geometry of the built in renderables
+#[derive(Hash, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum MeshSource {
+    // the textured cube of Cube
+    Cube,
+    // the cube around the camera of Skybox and ProceduralSky, seen from the inside
+    SkyCube,
+    // the quad of UI that covers the screen
+    Quad,
+}
+
+impl Asset for MeshData {
+    type Key = MeshSource;
+    type Data = Self;
+    fn read(source: &MeshSource, _assets: &AssetServer) -> Result<Self, AssetError> {
+        let (vertices, indices) = match source {
+            MeshSource::Cube => (cube::VERTICES, cube::INDICES),
+            MeshSource::SkyCube => (skybox::VERTICES, skybox::INDICES),
+            MeshSource::Quad => (ui::VERTICES, ui::INDICES),
+        };
+        let indices = indices.iter().map(|index| *index as u32).collect();
+        Ok(Self::new(format!("{:?}", source), vertices.to_vec(), indices))
+    }
+    fn create(_source: &MeshSource, mesh: Self, _render_context: &RenderContext) -> Result<Self, AssetError> {
+        Ok(mesh)
+    }
+    fn reads_files(_source: &MeshSource) -> bool {
+        false
+    }
+}
+
+// the handle of a built in mesh, loaded the first time it is asked for
+pub(crate) fn builtin_mesh(mesh: &OnceLock<Handle<MeshData>>, render_context: &RenderContext, source: MeshSource) -> Arc<MeshData> {
+    mesh.get_or_init(|| render_context.load_asset(source)).expect_loaded()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shading {
+    // the texture is drawn as it is
+    Unlit,
+    // Blinn-Phong shading of the texture by the lights of the state
+    Lit,
+    // metallic-roughness shading of the material
+    Pbr,
+}
+
+// why with_lighting or with_pbr could not light a mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightingError {
+    // the transparent pipeline is not lit
+    Transparent,
+    // the mesh lacks normals or another attribute the lit pipeline reads
+    MissingAttributes(VertexLayoutMismatch),
+}
+
+impl std::fmt::Display for LightingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LightingError::Transparent => write!(f, "Transparent meshes cannot be lit"),
+            LightingError::MissingAttributes(mismatch) => write!(f, "The mesh cannot be lit: {}", mismatch),
+        }
+    }
+}
+
+impl std::error::Error for LightingError {}
+
+#[derive(Clone)]
+pub struct Mesh {
+    data: Arc<MeshData>,
+    texture_source: TextureSource,
+    texture_options: TextureOptions,
+    // kept while the mesh is drawn with it, pbr meshes use the textures of their material
+    texture: Option<Handle<MyTexture>>,
+    // the material bind group for pbr meshes
+    texture_bind_group: Option<Arc<wgpu::BindGroup>>,
+    // the bind group holds placeholders until the textures are loaded
+    textures_loading: bool,
+    material: Option<Arc<Material>>,
+    vertex_layout: VertexLayout,
+    transparent: bool,
+    shading: Shading,
+    pub transform: Transform,
+    node_matrix: cgmath::Matrix4<f32>,
+    instances: InstanceBuffer,
+}
+
+impl Mesh {
+    pub fn new(name: String, vertices: Vec<Vertex>, indices: Vec<u32>, texture_source: TextureSource) -> Self {
+        Self::from_data(Arc::new(MeshData::new(name, vertices, indices)), texture_source)
+    }
+    pub fn new_non_indexed(name: String, vertices: Vec<Vertex>, texture_source: TextureSource) -> Self {
+        Self::from_data(Arc::new(MeshData::new_non_indexed(name, vertices)), texture_source)
+    }
+    pub fn from_data(data: Arc<MeshData>, texture_source: TextureSource) -> Self {
+        Self {
+            data,
+            texture_source,
+            texture_options: TextureOptions::default(),
+            texture: None,
+            texture_bind_group: None,
+            textures_loading: false,
+            material: None,
+            vertex_layout: Vertex::LAYOUT,
+            transparent: false,
+            shading: Shading::Unlit,
+            transform: Transform::default(),
+            node_matrix: cgmath::Matrix4::from_scale(1.0),
+            instances: InstanceBuffer::default(),
+        }
+    }
+    // loads every object of an .obj file as a separate mesh, textured with the diffuse map or color of its .mtl material.
+    // Faces are triangulated and vertices sharing the same position, texture coordinates and normal are merged
+    pub fn load_obj(file_path: &str) -> Result<Vec<Mesh>, tobj::LoadError> {
+        let load_options = tobj::LoadOptions {
+            triangulate: true,
+            ..Default::default()
+        };
+        let (models, materials) = tobj::load_obj(file_path, &load_options)?;
+        let materials = materials.unwrap_or_else(|e| {
+            log::warn!("Failed to load the materials of {}: {}", file_path, e);
+            Vec::new()
+        });
+        // textures in the .mtl file are relative to the .obj file
+        let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+        let meshes = models.into_iter().map(|model| {
+            let mesh = &model.mesh;
+            let mut vertices: Vec<MeshVertex> = Vec::new();
+            let mut indices: Vec<u32> = Vec::with_capacity(mesh.indices.len());
+            let mut unique_vertices: HashMap<(u32, Option<u32>, Option<u32>), u32> = HashMap::new();
+            for (i, &position_index) in mesh.indices.iter().enumerate() {
+                let tex_coord_index = mesh.texcoord_indices.get(i).copied();
+                let normal_index = mesh.normal_indices.get(i).copied();
+                let index = *unique_vertices.entry((position_index, tex_coord_index, normal_index)).or_insert_with(|| {
+                    let p = position_index as usize * 3;
+                    let tex_coords = match tex_coord_index {
+                        // obj texture coordinates start at the bottom left, wgpu's at the top left
+                        Some(t) => [mesh.texcoords[t as usize * 2], 1.0 - mesh.texcoords[t as usize * 2 + 1]],
+                        None => [0.0, 0.0],
+                    };
+                    let mut vertex = MeshVertex {
+                        position: [mesh.positions[p], mesh.positions[p + 1], mesh.positions[p + 2]],
+                        tex_coords,
+                        ..Default::default()
+                    };
+                    if let Some(n) = normal_index {
+                        let n = n as usize * 3;
+                        vertex.normal = [mesh.normals[n], mesh.normals[n + 1], mesh.normals[n + 2]];
+                    }
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                });
+                indices.push(index);
+            }
+            let material = mesh.material_id.and_then(|id| materials.get(id));
+            let texture_source = match material {
+                Some(tobj::Material { diffuse_texture: Some(texture), .. }) => {
+                    TextureSource::FilePath(directory.join(texture).to_string_lossy().into_owned())
+                }
+                Some(material) => {
+                    let [r, g, b] = material.diffuse.unwrap_or([1.0, 1.0, 1.0]);
+                    let a = material.dissolve.unwrap_or(1.0);
+                    TextureSource::SolidColor([r, g, b, a].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+                }
+                None => TextureSource::SolidColor([255, 255, 255, 255]),
+            };
+            let transparent = material.and_then(|material| material.dissolve).is_some_and(|d| d < 1.0);
+            let attributes = if mesh.normal_indices.is_empty() {
+                Vertex::LAYOUT
+            } else {
+                Vertex::LAYOUT.with(VertexAttribute::Normal)
+            };
+            let data = MeshData::from_mesh_vertices(model.name, vertices, attributes, Some(indices));
+            let mut result = Mesh::from_data(Arc::new(data), texture_source);
+            result.transparent = transparent;
+            result
+        }).collect();
+        Ok(meshes)
+    }
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+    // applied before the transform, like the world matrix of a scene node. Unlike a transform it can hold shear
+    pub fn with_node_matrix(mut self, node_matrix: cgmath::Matrix4<f32>) -> Self {
+        self.node_matrix = node_matrix;
+        self
+    }
+    pub fn node_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.node_matrix
+    }
+    // what the instances are placed relative to
+    fn object_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.transform.to_matrix() * self.node_matrix
+    }
+    // the base color texture or color is drawn, tinted by the base color factor
+    pub fn with_material(mut self, material: Arc<Material>) -> Self {
+        let tint = match &material.base_color_texture {
+            Some(texture) => {
+                self.texture_source = texture.source.clone();
+                self.texture_options = texture.options;
+                material.base_color_factor
+            }
+            None => {
+                self.texture_source = TextureSource::SolidColor(material.base_color_factor.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+                self.texture_options = TextureOptions::default();
+                [1.0, 1.0, 1.0, 1.0]
+            }
+        };
+        self.texture = None;
+        self.texture_bind_group = None;
+        self.transparent = material.alpha_mode == AlphaMode::Blend;
+        self.instances = InstanceBuffer::new(vec![Instance { tint, ..Default::default() }]);
+        self.material = Some(material);
+        self
+    }
+    // how the texture is sampled when the mesh is not drawn with pbr, Repeat makes it tile
+    pub fn with_texture_options(mut self, texture_options: TextureOptions) -> Self {
+        self.texture_options = texture_options;
+        self.texture = None;
+        self.texture_bind_group = None;
+        self
+    }
+    // the attributes uploaded to the gpu, they have to match the ones the pipeline of the mesh reads.
+    // Fails if the data of the mesh lacks some of them
+    pub fn with_vertex_layout(mut self, vertex_layout: VertexLayout) -> Result<Self, VertexLayoutMismatch> {
+        if !self.data.attributes.contains_layout(vertex_layout) {
+            return Err(VertexLayoutMismatch { expected: vertex_layout, actual: self.data.attributes });
+        }
+        self.vertex_layout = vertex_layout;
+        Ok(self)
+    }
+    // shaded by the lights of the state, the mesh needs normals for that. The transparent pipeline is not lit
+    pub fn with_lighting(mut self) -> Result<Self, LightingError> {
+        if self.transparent {
+            return Err(LightingError::Transparent);
+        }
+        self = self.with_vertex_layout(LitPipeline.vertex_layout()).map_err(LightingError::MissingAttributes)?;
+        self.shading = Shading::Lit;
+        self.texture = None;
+        self.texture_bind_group = None;
+        Ok(self)
+    }
+    // shaded with every map of the material, or the default material if the mesh has none. The mesh needs normals
+    pub fn with_pbr(mut self) -> Result<Self, LightingError> {
+        if self.transparent {
+            return Err(LightingError::Transparent);
+        }
+        self = self.with_vertex_layout(PbrPipeline.vertex_layout()).map_err(LightingError::MissingAttributes)?;
+        self.material.get_or_insert_with(|| Arc::new(Material::default()));
+        self.shading = Shading::Pbr;
+        self.texture = None;
+        self.texture_bind_group = None;
+        Ok(self)
+    }
+    pub fn with_instances(mut self, instances: Vec<Instance>) -> Self {
+        self.instances = InstanceBuffer::new(instances);
+        self
+    }
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+    pub fn texture_source(&self) -> &TextureSource {
+        &self.texture_source
+    }
+    pub fn texture_options(&self) -> &TextureOptions {
+        &self.texture_options
+    }
+    pub fn material(&self) -> Option<&Arc<Material>> {
+        self.material.as_ref()
+    }
+    pub fn shading(&self) -> Shading {
+        self.shading
+    }
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+    pub fn instances(&self) -> &[Instance] {
+        self.instances.instances()
+    }
+    pub fn instances_mut(&mut self) -> &mut Vec<Instance> {
+        self.instances.instances_mut()
+    }
+}
+
+impl Renderable for Mesh {
+    fn choose_pipeline(&self) -> TypeId {
+        if self.transparent {
+            TypeId::of::<TransparentPipeline>()
+        } else {
+            match self.shading {
+                Shading::Unlit => TypeId::of::<DefaultPipeline>(),
+                Shading::Lit => TypeId::of::<LitPipeline>(),
+                Shading::Pbr => TypeId::of::<PbrPipeline>(),
+            }
+        }
+    }
+    fn get_vertex_buffer(&self, render_context: &RenderContext) -> Arc<wgpu::Buffer> {
+        self.data.vertex_buffer(render_context, self.vertex_layout)
+    }
+    fn get_vertex_layout(&self) -> VertexLayout {
+        self.vertex_layout
+    }
+    fn get_index_buffer(&self, render_context: &RenderContext) -> Option<Arc<wgpu::Buffer>> {
+        self.data.index_buffer(render_context)
+    }
+    fn get_bind_groups<'a>(&'a mut self, render_context: &'a RenderContext) -> Vec<&'a wgpu::BindGroup> {
+        if self.texture_bind_group.is_none() || self.textures_loading {
+            let (bind_group, loading) = match (self.shading, &self.material) {
+                (Shading::Pbr, Some(material)) => PbrPipeline::get_material_bind_group(render_context, material),
+                _ => {
+                    let texture = self.texture.get_or_insert_with(|| {
+                        render_context.request_asset((self.texture_source.clone(), self.texture_options))
+                    });
+                    (DefaultPipeline::get_texture_bind_group(render_context, texture), texture.is_loading())
+                }
+            };
+            self.texture_bind_group = Some(bind_group);
+            self.textures_loading = loading;
+        }
+        let texture_bind_group = self.texture_bind_group.as_ref().unwrap();
+        match self.shading {
+            Shading::Unlit => DefaultPipeline::create_bind_groups(render_context, texture_bind_group),
+            Shading::Lit => LitPipeline::create_bind_groups(render_context, texture_bind_group),
+            Shading::Pbr => PbrPipeline::create_bind_groups(render_context, texture_bind_group),
+        }
+    }
+    fn get_num_indices(&self) -> u32 {
+        self.data.indices.len() as u32
+    }
+    fn get_index_format(&self) -> IndexFormat {
+        self.data.indices.format()
+    }
+    fn get_num_vertices(&self) -> u32 {
+        self.data.vertices.len() as u32
+    }
+    fn get_instance_buffer(&mut self, render_context: &RenderContext) -> Option<Arc<wgpu::Buffer>> {
+        Some(self.instances.get_buffer(render_context, &self.object_matrix()))
+    }
+    fn is_batched(&self) -> bool {
+        true
+    }
+    fn get_instances(&self) -> Option<Vec<InstanceRaw>> {
+        Some(self.instances.to_raw(&self.object_matrix()))
+    }
+    fn get_num_instances(&self) -> u32 {
+        self.instances.len()
+    }
+    fn casts_shadows(&self) -> bool {
+        !self.transparent
+    }
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+    fn get_position(&self) -> cgmath::Point3<f32> {
+        cgmath::Point3::from_homogeneous(self.object_matrix().w)
+    }
+    fn get_transform_mut(&mut self) -> Option<&mut Transform> {
+        Some(&mut self.transform)
+    }
+}
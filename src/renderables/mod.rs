@@ -1,4 +1,6 @@
 pub mod cube;
+pub mod mesh;
 pub mod polygon;
+pub mod procedural_sky;
 pub mod skybox;
 pub mod ui;
\ No newline at end of file
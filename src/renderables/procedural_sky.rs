@@ -0,0 +1,69 @@
+use std::{any::TypeId, sync::{Arc, OnceLock}};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    pipelines::procedural_sky_pipeline::{ProceduralSkyPipeline, SkyUniform}, render_context::RenderContext, renderable::Renderable, assets::Handle, renderables::{mesh::{builtin_mesh, MeshData, MeshSource}, skybox}, vertex::Vertex
+};
+
+// an atmosphere instead of the textures of Skybox. The sun is the one of State::sun_direction,
+// so the sky follows the time of day and the directional light
+pub struct ProceduralSky {
+    // scales the light of the sky and the sun
+    intensity: f32,
+    // the angular radius of the sun disk in degrees
+    sun_size: f32,
+    sky_bind_group: Option<wgpu::BindGroup>,
+    mesh: OnceLock<Handle<MeshData>>,
+}
+
+impl ProceduralSky {
+    pub fn new() -> Self {
+        Self {
+            intensity: 20.0,
+            sun_size: 1.0,
+            sky_bind_group: None,
+            mesh: OnceLock::new(),
+        }
+    }
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+    pub fn with_sun_size(mut self, sun_size: f32) -> Self {
+        self.sun_size = sun_size;
+        self
+    }
+}
+
+impl Default for ProceduralSky {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderable for ProceduralSky {
+    fn choose_pipeline(&self) -> TypeId {
+        TypeId::of::<ProceduralSkyPipeline>()
+    }
+    fn get_vertex_buffer(&self, render_context: &RenderContext) -> Arc<wgpu::Buffer> {
+        builtin_mesh(&self.mesh, render_context, MeshSource::SkyCube).vertex_buffer(render_context, Vertex::LAYOUT)
+    }
+    fn get_index_buffer(&self, render_context: &RenderContext) -> Option<Arc<wgpu::Buffer>> {
+        builtin_mesh(&self.mesh, render_context, MeshSource::SkyCube).index_buffer(render_context)
+    }
+    fn get_bind_groups<'a>(&'a mut self, render_context: &'a RenderContext) -> Vec<&'a wgpu::BindGroup> {
+        if self.sky_bind_group.is_none() {
+            let sky_buffer = render_context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Sky Buffer"),
+                contents: bytemuck::cast_slice(&[SkyUniform::new(self.intensity, self.sun_size)]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            self.sky_bind_group = Some(ProceduralSkyPipeline::create_sky_bind_group(render_context, &sky_buffer));
+        }
+        ProceduralSkyPipeline::create_bind_groups(render_context, self.sky_bind_group.as_ref().unwrap())
+    }
+    fn get_num_indices(&self) -> u32 {
+        skybox::INDICES.len() as u32
+    }
+}
@@ -0,0 +1,317 @@
+use std::{path::Path, sync::Arc};
+
+use cgmath::{EuclideanSpace, InnerSpace, Rotation};
+
+use crate::{
+    camera::Camera, material::{AlphaMode, Material, MaterialTexture}, my_texture::{TextureFiltering, TextureOptions, TextureSource}, renderables::mesh::{Mesh, MeshData}, transform::Transform, vertex::{MeshVertex, Vertex, VertexAttribute}
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // fovy in degrees. Without an aspect ratio the one of the window is used, without zfar the projection is infinite
+    Perspective { fovy: f32, aspect_ratio: Option<f32>, znear: f32, zfar: Option<f32> },
+    Orthographic { xmag: f32, ymag: f32, znear: f32, zfar: f32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct SceneCamera {
+    pub name: Option<String>,
+    pub node: usize,
+    pub projection: Projection,
+}
+
+#[derive(Debug, Clone)]
+pub struct SceneNode {
+    pub name: Option<String>,
+    // relative to the parent node
+    pub transform: Transform,
+    // the meshes of the node are placed with the world matrix, the world transform is split from it and loses its shear
+    pub world_matrix: cgmath::Matrix4<f32>,
+    pub world_transform: Transform,
+    pub children: Vec<usize>,
+    // one renderable per primitive of the mesh of the node, as indices into the renderables that were loaded with the scene
+    pub renderables: Vec<usize>,
+    pub camera: Option<usize>,
+}
+
+// the node hierarchy of a scene file. The renderables themselves live in State::renderables
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    pub nodes: Vec<SceneNode>,
+    pub roots: Vec<usize>,
+    pub cameras: Vec<SceneCamera>,
+    pub materials: Vec<Arc<Material>>,
+}
+
+impl Scene {
+    // loads the default scene of a .gltf or .glb file. Every primitive becomes a mesh placed with the world matrix of its node,
+    // shaded with the pbr pipeline when it has normals
+    pub fn load_gltf(file_path: &str) -> Result<(Scene, Vec<Mesh>), gltf::Error> {
+        let gltf::Gltf { document, blob } = gltf::Gltf::open(file_path)?;
+        // external buffers and images are relative to the gltf file
+        let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+        let buffers = gltf::import_buffers(&document, Some(directory), blob)?;
+        let images = document
+            .images()
+            .map(|image| Self::load_image(file_path, directory, image, &buffers))
+            .collect::<Result<Vec<_>, _>>()?;
+        let materials: Vec<Arc<Material>> = document
+            .materials()
+            .map(|material| Arc::new(Self::load_material(material, &images)))
+            .collect();
+        let default_material = Arc::new(Material::default());
+
+        let mut scene = Scene {
+            materials,
+            ..Default::default()
+        };
+        let mut meshes = Vec::new();
+        scene.nodes = document.nodes().map(|node| {
+            let (translation, rotation, scale) = node.transform().decomposed();
+            let transform = Transform::new(
+                translation.into(),
+                cgmath::Quaternion::new(rotation[3], rotation[0], rotation[1], rotation[2]),
+                scale.into(),
+            );
+            SceneNode {
+                name: node.name().map(str::to_string),
+                transform,
+                world_matrix: transform.to_matrix(),
+                world_transform: transform,
+                children: node.children().map(|child| child.index()).collect(),
+                renderables: Vec::new(),
+                camera: None,
+            }
+        }).collect();
+        let Some(gltf_scene) = document.default_scene().or_else(|| document.scenes().next()) else {
+            return Ok((scene, meshes));
+        };
+        scene.roots = gltf_scene.nodes().map(|node| node.index()).collect();
+
+        // parents are visited before their children, so the world transform of the parent is always known
+        let mut stack: Vec<(gltf::Node, cgmath::Matrix4<f32>)> = gltf_scene
+            .nodes()
+            .map(|node| (node, cgmath::Matrix4::from_scale(1.0)))
+            .collect();
+        while let Some((node, parent_matrix)) = stack.pop() {
+            let index = node.index();
+            let world_matrix = parent_matrix * scene.nodes[index].transform.to_matrix();
+            scene.nodes[index].world_matrix = world_matrix;
+            scene.nodes[index].world_transform = Transform::from_matrix(world_matrix);
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    if primitive.mode() != gltf::mesh::Mode::Triangles {
+                        log::warn!("Skipping a primitive of {} that is not made of triangles", file_path);
+                        continue;
+                    }
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let Some(positions) = reader.read_positions() else {
+                        continue;
+                    };
+                    let mut vertices: Vec<MeshVertex> = positions
+                        .map(|position| MeshVertex { position, ..Default::default() })
+                        .collect();
+                    // texture coordinates are always part of the layout, they stay at zero when missing
+                    let mut attributes = Vertex::LAYOUT;
+                    if let Some(tex_coords) = reader.read_tex_coords(0) {
+                        for (vertex, tex_coords) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+                            vertex.tex_coords = tex_coords;
+                        }
+                    }
+                    if let Some(normals) = reader.read_normals() {
+                        attributes = attributes.with(VertexAttribute::Normal);
+                        for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                            vertex.normal = normal;
+                        }
+                    }
+                    if let Some(tangents) = reader.read_tangents() {
+                        attributes = attributes.with(VertexAttribute::Tangent);
+                        for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+                            vertex.tangent = tangent;
+                        }
+                    }
+                    if let Some(colors) = reader.read_colors(0) {
+                        attributes = attributes.with(VertexAttribute::Color);
+                        for (vertex, color) in vertices.iter_mut().zip(colors.into_rgba_f32()) {
+                            vertex.color = color;
+                        }
+                    }
+                    if let Some(tex_coords) = reader.read_tex_coords(1) {
+                        attributes = attributes.with(VertexAttribute::TexCoords1);
+                        for (vertex, tex_coords) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+                            vertex.tex_coords1 = tex_coords;
+                        }
+                    }
+                    if let (Some(joints), Some(weights)) = (reader.read_joints(0), reader.read_weights(0)) {
+                        attributes = attributes.with(VertexAttribute::Joints).with(VertexAttribute::Weights);
+                        for (vertex, (joints, weights)) in vertices.iter_mut().zip(joints.into_u16().zip(weights.into_f32())) {
+                            vertex.joints = joints;
+                            vertex.weights = weights;
+                        }
+                    }
+                    let material = primitive
+                        .material()
+                        .index()
+                        .map_or(default_material.clone(), |i| scene.materials[i].clone());
+                    let name = format!("{}#{}", mesh.name().unwrap_or("mesh"), primitive.index());
+                    scene.nodes[index].renderables.push(meshes.len());
+                    let texture_source = TextureSource::SolidColor([255, 255, 255, 255]);
+                    let indices = reader.read_indices().map(|indices| indices.into_u32().collect());
+                    let data = MeshData::from_mesh_vertices(name, vertices, attributes, indices);
+                    let has_normals = attributes.contains(VertexAttribute::Normal);
+                    let mut mesh = Mesh::from_data(Arc::new(data), texture_source)
+                        .with_material(material)
+                        .with_node_matrix(world_matrix);
+                    // the materials are made for pbr shading, only blended ones and meshes without normals are drawn unlit
+                    if has_normals && !mesh.is_transparent() {
+                        mesh = mesh.with_pbr().unwrap();
+                    }
+                    meshes.push(mesh);
+                }
+            }
+            if let Some(camera) = node.camera() {
+                let projection = match camera.projection() {
+                    gltf::camera::Projection::Perspective(perspective) => Projection::Perspective {
+                        fovy: perspective.yfov().to_degrees(),
+                        aspect_ratio: perspective.aspect_ratio(),
+                        znear: perspective.znear(),
+                        zfar: perspective.zfar(),
+                    },
+                    gltf::camera::Projection::Orthographic(orthographic) => Projection::Orthographic {
+                        xmag: orthographic.xmag(),
+                        ymag: orthographic.ymag(),
+                        znear: orthographic.znear(),
+                        zfar: orthographic.zfar(),
+                    },
+                };
+                scene.nodes[index].camera = Some(scene.cameras.len());
+                scene.cameras.push(SceneCamera {
+                    name: camera.name().map(str::to_string),
+                    node: index,
+                    projection,
+                });
+            }
+            stack.extend(node.children().map(|child| (child, world_matrix)));
+        }
+        Ok((scene, meshes))
+    }
+    fn load_image(
+        file_path: &str,
+        directory: &Path,
+        image: gltf::Image,
+        buffers: &[gltf::buffer::Data],
+    ) -> Result<TextureSource, gltf::Error> {
+        match image.source() {
+            // external images go through the same cache as every other texture file
+            gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                Ok(TextureSource::FilePath(directory.join(uri).to_string_lossy().into_owned()))
+            }
+            source => {
+                let data = gltf::image::Data::from_source(source, Some(directory), buffers)?;
+                let rgba = match data.format {
+                    gltf::image::Format::R8G8B8A8 => data.pixels,
+                    gltf::image::Format::R8G8B8 => data
+                        .pixels
+                        .chunks_exact(3)
+                        .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                        .collect(),
+                    gltf::image::Format::R8 => data.pixels.iter().flat_map(|&r| [r, r, r, 255]).collect(),
+                    gltf::image::Format::R8G8 => data
+                        .pixels
+                        .chunks_exact(2)
+                        .flat_map(|rg| [rg[0], rg[1], 0, 255])
+                        .collect(),
+                    _ => return Err(gltf::Error::UnsupportedImageEncoding),
+                };
+                Ok(TextureSource::Pixels {
+                    name: format!("{}#image{}", file_path, image.index()),
+                    width: data.width,
+                    height: data.height,
+                    rgba: Arc::new(rgba),
+                })
+            }
+        }
+    }
+    fn load_material(material: gltf::Material, images: &[TextureSource]) -> Material {
+        let texture = |texture: gltf::Texture| MaterialTexture {
+            source: images[texture.source().index()].clone(),
+            options: Self::sampler_options(texture.sampler()),
+        };
+        let pbr = material.pbr_metallic_roughness();
+        Material {
+            name: material.name().map(str::to_string),
+            base_color_factor: pbr.base_color_factor(),
+            base_color_texture: pbr.base_color_texture().map(|info| texture(info.texture())),
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            metallic_roughness_texture: pbr.metallic_roughness_texture().map(|info| texture(info.texture())),
+            normal_texture: material.normal_texture().map(|info| texture(info.texture())),
+            normal_scale: material.normal_texture().map_or(1.0, |info| info.scale()),
+            occlusion_texture: material.occlusion_texture().map(|info| texture(info.texture())),
+            occlusion_strength: material.occlusion_texture().map_or(1.0, |info| info.strength()),
+            emissive_factor: material.emissive_factor(),
+            emissive_texture: material.emissive_texture().map(|info| texture(info.texture())),
+            alpha_mode: match material.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                gltf::material::AlphaMode::Mask => AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5)),
+                gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+            },
+            double_sided: material.double_sided(),
+        }
+    }
+    // gltf samplers repeat by default, filters that are not given are left to RenderContext::texture_filtering
+    fn sampler_options(sampler: gltf::texture::Sampler) -> TextureOptions {
+        use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+        use wgpu::FilterMode::{Linear, Nearest};
+        let address_mode = |mode: WrappingMode| match mode {
+            WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+            WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+        };
+        let filtering = match (sampler.mag_filter(), sampler.min_filter()) {
+            (None, None) => None,
+            (mag_filter, min_filter) => {
+                let (min_filter, mipmap_filter) = match min_filter {
+                    Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => (Nearest, Nearest),
+                    Some(MinFilter::Linear) | Some(MinFilter::LinearMipmapNearest) => (Linear, Nearest),
+                    Some(MinFilter::NearestMipmapLinear) => (Nearest, Linear),
+                    Some(MinFilter::LinearMipmapLinear) | None => (Linear, Linear),
+                };
+                Some(TextureFiltering::Custom {
+                    mag_filter: if mag_filter == Some(MagFilter::Nearest) { Nearest } else { Linear },
+                    min_filter,
+                    mipmap_filter,
+                    anisotropy_clamp: 1,
+                })
+            }
+        };
+        TextureOptions {
+            address_mode_u: address_mode(sampler.wrap_s()),
+            address_mode_v: address_mode(sampler.wrap_t()),
+            filtering,
+            ..Default::default()
+        }
+    }
+    // moves the camera to the camera node, orthographic projections are not supported by Camera and only the placement is copied
+    pub fn apply_camera(&self, index: usize, camera: &mut Camera) {
+        let scene_camera = &self.cameras[index];
+        let world_transform = self.nodes[scene_camera.node].world_transform;
+        // gltf cameras look down their local -z
+        let forward = world_transform.rotation.rotate_vector(-cgmath::Vector3::unit_z()).normalize();
+        camera.pos = cgmath::Point3::from_vec(world_transform.position);
+        camera.yaw = forward.z.atan2(forward.x).to_degrees();
+        camera.pitch = forward.y.asin().to_degrees();
+        match scene_camera.projection {
+            Projection::Perspective { fovy, znear, zfar, .. } => {
+                camera.fovy = fovy;
+                camera.znear = znear;
+                if let Some(zfar) = zfar {
+                    camera.zfar = zfar;
+                }
+            }
+            Projection::Orthographic { .. } => {
+                log::warn!("Orthographic cameras are not supported, only the position and direction are applied");
+            }
+        }
+    }
+}
@@ -0,0 +1,377 @@
+// shadow maps of the directional and spot lights that cast shadows. They are rendered into the layers of one
+// depth texture array by the shadow pass of the render graph, and sampled by the lit pipelines through the lights bind group
+
+use std::{collections::HashMap, sync::Mutex};
+
+use cgmath::{EuclideanSpace, InnerSpace, SquareMatrix};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::{Camera, OPENGL_TO_WGPU_MATRIX}, instance::InstanceRaw, light::{Light, MAX_LIGHTS}, my_texture::MyTexture, render_context::RenderContext, renderable::Renderable, vertex::{VertexAttribute, VertexLayout}
+};
+
+pub const SHADOW_MAP_SIZE: u32 = 1024;
+// layers of the shadow map, lights that do not fit anymore are drawn without shadows
+pub const MAX_SHADOW_LAYERS: usize = 8;
+pub const MAX_CASCADES: usize = 4;
+// casters this far behind a cascade, seen from the light, still throw their shadow into it
+const CASTER_MARGIN: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    // cascades of every directional light, clamped to between 1 and MAX_CASCADES
+    pub cascades: u32,
+    // directional shadows end this far from the camera
+    pub distance: f32,
+    // 0 splits the cascades evenly, 1 logarithmically so that near cascades are smaller
+    pub split_lambda: f32,
+    // subtracted from the depth of the lookup, against shadow acne
+    pub depth_bias: f32,
+    // world units the lookup is moved along the normal, against shadow acne on steep surfaces
+    pub normal_bias: f32,
+}
+
+impl ShadowSettings {
+    // the number of cascades that is rendered
+    pub fn cascade_count(&self) -> u32 {
+        self.cascades.clamp(1, MAX_CASCADES as u32)
+    }
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            cascades: 3,
+            distance: 50.0,
+            split_lambda: 0.7,
+            depth_bias: 0.0005,
+            normal_bias: 0.03,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniform {
+    view_projections: [[[f32; 4]; 4]; MAX_SHADOW_LAYERS],
+    // view space depths where the cascades end
+    cascade_splits: [f32; MAX_CASCADES],
+    // x is the size of a texel of the shadow map in uv, y the depth bias and z the normal bias
+    settings: [f32; 4],
+}
+
+// which layers of the shadow map belong to which light for one frame
+#[derive(Debug, Clone, Default)]
+pub struct ShadowLayout {
+    // one per layer of the shadow map
+    pub view_projections: Vec<cgmath::Matrix4<f32>>,
+    // first layer and number of layers of every light, None for lights without shadows
+    pub light_layers: Vec<Option<(u32, u32)>>,
+    pub cascade_splits: [f32; MAX_CASCADES],
+    // shadow casting lights that got no layers because the others took them all
+    pub lights_out_of_layers: usize,
+}
+
+impl ShadowLayout {
+    pub fn new(lights: &[Light], camera: &Camera, aspect: f32, settings: &ShadowSettings) -> Self {
+        let mut layout = Self {
+            cascade_splits: Self::compute_cascade_splits(camera, settings),
+            ..Default::default()
+        };
+        for light in lights.iter().take(MAX_LIGHTS) {
+            let view_projections = match *light {
+                _ if !light.casts_shadows() => Vec::new(),
+                Light::Directional { direction, .. } => {
+                    let mut near = camera.znear;
+                    layout.cascade_splits[..settings.cascade_count() as usize].iter().map(|&far| {
+                        let view_projection = Self::cascade_view_projection(direction, camera, aspect, near, far);
+                        near = far;
+                        view_projection
+                    }).collect()
+                }
+                Light::Spot { position, direction, range, outer_angle, .. } => {
+                    let view = cgmath::Matrix4::look_to_rh(cgmath::Point3::from_vec(position), direction.normalize(), Self::up_vector(direction));
+                    let fovy = (outer_angle * 2.0).min(170.0);
+                    let projection = cgmath::perspective(cgmath::Deg(fovy), 1.0, (range * 0.01).max(0.01), range);
+                    vec![OPENGL_TO_WGPU_MATRIX * projection * view]
+                }
+                Light::Point { .. } => Vec::new(),
+            };
+            if view_projections.is_empty() {
+                layout.light_layers.push(None);
+            } else if layout.view_projections.len() + view_projections.len() > MAX_SHADOW_LAYERS {
+                layout.lights_out_of_layers += 1;
+                layout.light_layers.push(None);
+            } else {
+                layout.light_layers.push(Some((layout.view_projections.len() as u32, view_projections.len() as u32)));
+                layout.view_projections.extend(view_projections);
+            }
+        }
+        layout
+    }
+    // a blend between logarithmic and even splits, the last cascade ends at the shadow distance
+    fn compute_cascade_splits(camera: &Camera, settings: &ShadowSettings) -> [f32; MAX_CASCADES] {
+        let near = camera.znear;
+        let far = settings.distance.min(camera.zfar);
+        let cascades = settings.cascade_count();
+        let mut splits = [far; MAX_CASCADES];
+        for (i, split) in splits.iter_mut().enumerate().take(cascades as usize) {
+            let t = (i + 1) as f32 / cascades as f32;
+            let logarithmic = near * (far / near).powf(t);
+            let even = near + (far - near) * t;
+            *split = settings.split_lambda * logarithmic + (1.0 - settings.split_lambda) * even;
+        }
+        splits
+    }
+    fn up_vector(direction: cgmath::Vector3<f32>) -> cgmath::Vector3<f32> {
+        if direction.normalize().y.abs() > 0.99 {
+            cgmath::Vector3::unit_z()
+        } else {
+            cgmath::Vector3::unit_y()
+        }
+    }
+    // an orthographic projection around the bounding sphere of the slice of the view frustum between near and far.
+    // The sphere does not change when the camera turns, and its center is snapped to texels, so the shadows do not shimmer
+    fn cascade_view_projection(direction: cgmath::Vector3<f32>, camera: &Camera, aspect: f32, near: f32, far: f32) -> cgmath::Matrix4<f32> {
+        let camera_view = camera.build_view_matrix();
+        let inverse_camera_view = camera_view.invert().unwrap();
+        let tan_half_fovy = (camera.fovy.to_radians() / 2.0).tan();
+        let mut corners = Vec::with_capacity(8);
+        for depth in [near, far] {
+            let half_height = depth * tan_half_fovy;
+            let half_width = half_height * aspect;
+            for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+                let corner = cgmath::Vector4::new(x * half_width, y * half_height, -depth, 1.0);
+                corners.push((inverse_camera_view * corner).truncate());
+            }
+        }
+        let center = corners.iter().fold(cgmath::Vector3::new(0.0, 0.0, 0.0), |sum, corner| sum + corner) / 8.0;
+        let radius = corners.iter().map(|corner| (corner - center).magnitude()).fold(0.0, f32::max);
+        let radius = (radius * 16.0).ceil() / 16.0;
+
+        let light_view = cgmath::Matrix4::look_to_rh(cgmath::Point3::origin(), direction.normalize(), Self::up_vector(direction));
+        let mut light_center = light_view * center.extend(1.0);
+        let texel = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+        light_center.x = (light_center.x / texel).floor() * texel;
+        light_center.y = (light_center.y / texel).floor() * texel;
+        // the light looks down -z, so the distance to the center is -z
+        let projection = cgmath::ortho(
+            light_center.x - radius,
+            light_center.x + radius,
+            light_center.y - radius,
+            light_center.y + radius,
+            -light_center.z - radius - CASTER_MARGIN,
+            -light_center.z + radius,
+        );
+        OPENGL_TO_WGPU_MATRIX * projection * light_view
+    }
+    pub fn to_uniform(&self, settings: &ShadowSettings) -> ShadowUniform {
+        let mut view_projections = [[[0.0; 4]; 4]; MAX_SHADOW_LAYERS];
+        for (raw, view_projection) in view_projections.iter_mut().zip(&self.view_projections) {
+            *raw = (*view_projection).into();
+        }
+        ShadowUniform {
+            view_projections,
+            cascade_splits: self.cascade_splits,
+            settings: [1.0 / SHADOW_MAP_SIZE as f32, settings.depth_bias, settings.normal_bias, 0.0],
+        }
+    }
+}
+
+pub struct ShadowMaps {
+    #[allow(unused)]
+    pub texture: wgpu::Texture,
+    // every layer, for sampling
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub uniform_buffer: wgpu::Buffer,
+    // one view, view projection and bind group per layer, for rendering
+    layer_views: Vec<wgpu::TextureView>,
+    layer_buffers: Vec<wgpu::Buffer>,
+    layer_bind_group_layout: wgpu::BindGroupLayout,
+    layer_bind_groups: Vec<wgpu::BindGroup>,
+    // casters only need their positions, but the stride depends on the vertex layout
+    pipelines: Mutex<HashMap<VertexLayout, wgpu::RenderPipeline>>,
+}
+
+impl ShadowMaps {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow map"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: MAX_SHADOW_LAYERS as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: MyTexture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..MAX_SHADOW_LAYERS as u32).map(|layer| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        }).collect();
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Buffer"),
+            contents: bytemuck::cast_slice(&[ShadowLayout::default().to_uniform(&ShadowSettings::default())]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let layer_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("shadow_layer_bind_group_layout"),
+        });
+        let layer_buffers: Vec<wgpu::Buffer> = (0..MAX_SHADOW_LAYERS).map(|_| {
+            let identity: [[f32; 4]; 4] = cgmath::Matrix4::identity().into();
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow Layer Buffer"),
+                contents: bytemuck::cast_slice(&[identity]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        }).collect();
+        let layer_bind_groups = layer_buffers.iter().map(|buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &layer_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+                label: Some("shadow_layer_bind_group"),
+            })
+        }).collect();
+        Self {
+            texture,
+            view,
+            sampler: MyTexture::create_comparison_sampler(device),
+            uniform_buffer,
+            layer_views,
+            layer_buffers,
+            layer_bind_group_layout,
+            layer_bind_groups,
+            pipelines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get_pipeline(&self, device: &wgpu::Device, vertex_layout: VertexLayout) -> wgpu::RenderPipeline {
+        self.pipelines.lock().unwrap().entry(vertex_layout).or_insert_with(|| {
+            let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[&self.layer_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Shadow Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("pipelines/shadow.wgsl").into()),
+            });
+            let position_location = VertexAttribute::Position.shader_location();
+            let vertex_attributes: Vec<wgpu::VertexAttribute> = vertex_layout
+                .attributes()
+                .into_iter()
+                .filter(|attribute| attribute.shader_location == position_location)
+                .collect();
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Shadow Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[vertex_layout.desc(&vertex_attributes), InstanceRaw::desc()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: None,
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    // single sided planes have to cast shadows as well
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: MyTexture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState {
+                        constant: 2,
+                        slope_scale: 2.0,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        }).clone()
+    }
+
+    /// Renders the depth of every shadow caster into the layers of the layout.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        render_context: &RenderContext,
+        shadow_layout: &ShadowLayout,
+        renderables: &mut [&mut dyn Renderable],
+    ) {
+        for (layer, view_projection) in shadow_layout.view_projections.iter().enumerate() {
+            let raw: [[f32; 4]; 4] = (*view_projection).into();
+            render_context.queue.write_buffer(&self.layer_buffers[layer], 0, bytemuck::cast_slice(&[raw]));
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.layer_views[layer],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_bind_group(0, &self.layer_bind_groups[layer], &[]);
+            for renderable in renderables.iter_mut() {
+                let num_instances = renderable.get_num_instances();
+                if !renderable.casts_shadows() || num_instances == 0 {
+                    continue;
+                }
+                let Some(instance_buffer) = renderable.get_instance_buffer(render_context) else {
+                    continue;
+                };
+                render_pass.set_pipeline(&self.get_pipeline(&render_context.device, renderable.get_vertex_layout()));
+                render_pass.set_vertex_buffer(0, renderable.get_vertex_buffer(render_context).slice(..));
+                render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                match renderable.get_index_format().to_wgpu() {
+                    Some(index_format) => {
+                        let index_buffer = renderable.get_index_buffer(render_context).expect("Indexed renderable has no index buffer");
+                        render_pass.set_index_buffer(index_buffer.slice(..), index_format);
+                        render_pass.draw_indexed(0..renderable.get_num_indices(), 0, 0..num_instances);
+                    }
+                    None => render_pass.draw(0..renderable.get_num_vertices(), 0..num_instances),
+                }
+            }
+        }
+    }
+}
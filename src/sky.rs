@@ -0,0 +1,108 @@
+// the sun over a day. It moves the procedural sky and the directional light of the sun together
+
+use cgmath::InnerSpace;
+
+use crate::light::Light;
+
+// the atmosphere of procedural_sky.wgsl, for the color of the sunlight that reaches the ground
+const RAYLEIGH_SCATTERING: [f32; 3] = [5.8e-6, 13.5e-6, 33.1e-6];
+const RAYLEIGH_HEIGHT: f32 = 8000.0;
+const MIE_EXTINCTION: f32 = 1.1 * 21e-6;
+const MIE_HEIGHT: f32 = 1200.0;
+const OZONE_ABSORPTION: [f32; 3] = [0.65e-6, 1.881e-6, 0.085e-6];
+// the thickness of the ozone layer if it were as dense as at its center everywhere
+const OZONE_THICKNESS: f32 = 15000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfDay {
+    // hours since midnight, it wraps around at 24
+    pub hours: f32,
+    // game hours per real second, 0 stops the clock
+    pub speed: f32,
+    // degrees above the horizon of the sun at noon. It rises in +x, is highest towards -z and sets in -x
+    pub noon_elevation: f32,
+    // of the sun light above the atmosphere, the light dims and reddens towards the horizon
+    pub sun_intensity: f32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            hours: 10.0,
+            speed: 0.0,
+            noon_elevation: 60.0,
+            sun_intensity: 3.0,
+        }
+    }
+}
+
+impl TimeOfDay {
+    pub fn new(hours: f32) -> Self {
+        Self {
+            hours,
+            ..Default::default()
+        }
+    }
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+    pub fn with_noon_elevation(mut self, noon_elevation: f32) -> Self {
+        self.noon_elevation = noon_elevation;
+        self
+    }
+
+    pub fn advance(&mut self, delta_time: f32) {
+        self.hours = (self.hours + self.speed * delta_time).rem_euclid(24.0);
+    }
+
+    // towards the sun, below the horizon between 18 and 6
+    pub fn sun_direction(&self) -> cgmath::Vector3<f32> {
+        let angle = (self.hours - 6.0) / 12.0 * std::f32::consts::PI;
+        let tilt = self.noon_elevation.to_radians();
+        cgmath::Vector3::new(angle.cos(), angle.sin() * tilt.sin(), -angle.sin() * tilt.cos())
+    }
+
+    // the part of the sunlight that is not scattered away on the way through the atmosphere, with the air mass of Kasten and Young
+    pub fn sun_transmittance(&self) -> [f32; 3] {
+        let direction = self.sun_direction();
+        let zenith = direction.y.clamp(-1.0, 1.0).acos().to_degrees().min(90.0);
+        let air_mass = 1.0 / (zenith.to_radians().cos() + 0.50572 * (96.07995 - zenith).powf(-1.6364));
+        std::array::from_fn(|i| {
+            let depth = RAYLEIGH_SCATTERING[i] * RAYLEIGH_HEIGHT + MIE_EXTINCTION * MIE_HEIGHT + OZONE_ABSORPTION[i] * OZONE_THICKNESS;
+            (-depth * air_mass).exp()
+        })
+    }
+
+    // the directional light of the sun, it fades out while the sun sets so nothing is lit from below the horizon
+    pub fn apply_to_light(&self, light: &mut Light) {
+        if let Light::Directional { direction, color, intensity, .. } = light {
+            let sun_direction = self.sun_direction();
+            *direction = -sun_direction;
+            *color = self.sun_transmittance();
+            let fade = ((sun_direction.y + 0.02) / 0.07).clamp(0.0, 1.0);
+            *intensity = self.sun_intensity * fade * fade * (3.0 - 2.0 * fade);
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SunUniform {
+    // towards the sun
+    pub direction: [f32; 4],
+}
+
+impl SunUniform {
+    pub fn new(direction: cgmath::Vector3<f32>) -> Self {
+        Self {
+            direction: direction.normalize().extend(0.0).into(),
+        }
+    }
+}
+
+impl Default for SunUniform {
+    fn default() -> Self {
+        Self::new(cgmath::Vector3::unit_y())
+    }
+}
@@ -1,9 +1,9 @@
 use std::{sync::Arc, time::Instant};
 
-use cgmath::{InnerSpace, Zero};
+use cgmath::InnerSpace;
 use winit::{keyboard::KeyCode, window::Window};
 
-use crate::{camera::Camera, input_context::InputContext, renderable::Renderable};
+use crate::{assets::AssetError, camera::Camera, environment_map::Environment, input_context::InputContext, light::Light, renderable::{validate_vertex_layout, Renderable, VertexLayoutMismatch}, pipelines::tonemap_pipeline::Tonemapping, post_process::PostProcessStack, scene::Scene, shadow::ShadowSettings, sky::TimeOfDay};
 
 pub struct State {
     // camera stuff
@@ -15,9 +15,86 @@ pub struct State {
     pub prev_device_mouse_delta: Option<(f64, f64)>,
     pub fps_timer: Instant,
     pub accumulated_frame_num: u32,
+    // add_renderable checks that they match their pipeline, pushing them directly does not
     pub renderables: Vec<Box<dyn Renderable + Send + Sync>>,
+    // the node hierarchy of the last loaded scene file, its renderables are in renderables
+    pub scene: Option<Scene>,
+    // only lit pipelines are affected by the lights
+    pub lights: Vec<Light>,
+    pub ambient_light: [f32; 3],
+    // a sky whose light replaces the ambient light, its maps are computed when it is first set
+    pub environment: Option<Environment>,
+    // moves the sun, the first directional light follows it and one is added if there is none.
+    // [ and ] turn the clock back and forward
+    pub time_of_day: Option<TimeOfDay>,
+    // for the lights that cast shadows
+    pub shadows: ShadowSettings,
+    // how the hdr scene color is mapped to the surface, T switches the operator and +/- change the exposure
+    pub tonemapping: Tonemapping,
+    pub exposure: f32,
+    // fullscreen effects applied to the hdr scene color before tonemapping
+    pub post_process: PostProcessStack,
 }
 impl State {
+    // added unless its vertex buffer lacks attributes its pipeline reads or has others
+    pub fn add_renderable(&mut self, renderable: impl Renderable + Send + Sync + 'static) -> Result<(), VertexLayoutMismatch> {
+        validate_vertex_layout(&renderable)?;
+        self.renderables.push(Box::new(renderable));
+        Ok(())
+    }
+    // adds the meshes of a gltf file to the renderables and looks through its first camera, if it has one.
+    // Its node hierarchy replaces the scene of an earlier load, the renderables of that one are kept.
+    // Nothing is added if a mesh does not match its pipeline
+    pub fn load_gltf(&mut self, file_path: &str) -> Result<(), AssetError> {
+        let (mut scene, meshes) = Scene::load_gltf(file_path)?;
+        for mesh in meshes.iter() {
+            validate_vertex_layout(mesh)?;
+        }
+        let offset = self.renderables.len();
+        for node in scene.nodes.iter_mut() {
+            for renderable in node.renderables.iter_mut() {
+                *renderable += offset;
+            }
+        }
+        for mesh in meshes {
+            self.renderables.push(Box::new(mesh));
+        }
+        if !scene.cameras.is_empty() {
+            scene.apply_camera(0, &mut self.camera);
+        }
+        self.scene = Some(scene);
+        Ok(())
+    }
+    // advances the clock and moves the sun light with it, update calls it every frame
+    pub fn update_time_of_day(&mut self, delta_time: f32) {
+        let Some(time_of_day) = &mut self.time_of_day else {
+            return;
+        };
+        time_of_day.advance(delta_time);
+        let sun = match self.lights.iter_mut().find(|light| matches!(light, Light::Directional { .. })) {
+            Some(sun) => sun,
+            None => {
+                self.lights.push(Light::Directional {
+                    direction: -cgmath::Vector3::unit_y(),
+                    color: [1.0, 1.0, 1.0],
+                    intensity: 1.0,
+                    cast_shadows: false,
+                });
+                self.lights.last_mut().unwrap()
+            }
+        };
+        time_of_day.apply_to_light(sun);
+    }
+    // towards the sun of the time of day, or against the first directional light without one
+    pub fn sun_direction(&self) -> Option<cgmath::Vector3<f32>> {
+        if let Some(time_of_day) = &self.time_of_day {
+            return Some(time_of_day.sun_direction());
+        }
+        self.lights.iter().find_map(|light| match light {
+            Light::Directional { direction, .. } => Some(-direction.normalize()),
+            _ => None,
+        })
+    }
     pub fn update(&mut self, input_context: &mut InputContext, window: Arc<Window>) {
         // accumulated time// calculate fps every 1 second
         let current_time = self.fps_timer.elapsed().as_secs_f32();
@@ -128,6 +205,27 @@ impl State {
             println!("Camera pos: {:?}", self.camera.pos);
             space_shift_pressed = true;
         }
+        if input_context.get_key_down(KeyCode::KeyT) {
+            self.tonemapping = self.tonemapping.next();
+            println!("Tonemapping: {:?}", self.tonemapping);
+        }
+        // a stop brighter or darker per second
+        if input_context.get_key(KeyCode::Equal) {
+            self.exposure *= 2.0f32.powf(delta_time);
+        }
+        if input_context.get_key(KeyCode::Minus) {
+            self.exposure /= 2.0f32.powf(delta_time);
+        }
+        // an hour per second
+        if let Some(time_of_day) = &mut self.time_of_day {
+            if input_context.get_key(KeyCode::BracketRight) {
+                time_of_day.hours += delta_time;
+            }
+            if input_context.get_key(KeyCode::BracketLeft) {
+                time_of_day.hours -= delta_time;
+            }
+        }
+        self.update_time_of_day(delta_time);
         fn damp_camera(curr_speed: &mut f32, damp_factor: f32) {
             assert!(damp_factor >= 0.0);
             let old_speed = *curr_speed;
@@ -160,8 +258,7 @@ impl State {
             let forward = cgmath::Vector3::new(yaw.to_radians().cos(), 0.0, yaw.to_radians().sin());
             let up = cgmath::Vector3::unit_y();
             let right = forward.cross(up).normalize();
-            let global_speed = forward * -curr_speed.z + right * curr_speed.x + up * curr_speed.y;
-            global_speed
+            forward * -curr_speed.z + right * curr_speed.x + up * curr_speed.y
         }
 
         let global_speed = local_to_global(self.camera.curr_local_speed, self.camera.yaw);
@@ -218,6 +315,15 @@ impl Default for State {
             fps_timer: Instant::now(),
             accumulated_frame_num: 0,
             renderables: Vec::new(),
+            scene: None,
+            lights: Vec::new(),
+            ambient_light: [0.1, 0.1, 0.1],
+            environment: None,
+            time_of_day: None,
+            shadows: ShadowSettings::default(),
+            tonemapping: Tonemapping::default(),
+            exposure: 1.0,
+            post_process: PostProcessStack::default(),
         }
     }
 }
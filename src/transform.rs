@@ -0,0 +1,66 @@
+use cgmath::{InnerSpace, One, SquareMatrix, Zero};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    pub scale: cgmath::Vector3<f32>,
+}
+
+impl Transform {
+    pub fn new(position: cgmath::Vector3<f32>, rotation: cgmath::Quaternion<f32>, scale: cgmath::Vector3<f32>) -> Self {
+        Self { position, rotation, scale }
+    }
+    pub fn from_position(position: cgmath::Vector3<f32>) -> Self {
+        Self {
+            position,
+            ..Default::default()
+        }
+    }
+    // splits a matrix made of a translation, a rotation and a scale, shear is lost.
+    // The rotation of an axis that is scaled to zero is taken from the other two, or left out if they are zero as well
+    pub fn from_matrix(matrix: cgmath::Matrix4<f32>) -> Self {
+        let position = matrix.w.truncate();
+        let columns = [matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate()];
+        let mut scale = cgmath::Vector3::new(columns[0].magnitude(), columns[1].magnitude(), columns[2].magnitude());
+        let axes = cgmath::Matrix3::from_scale(1.0);
+        let mut directions = [0, 1, 2].map(|i| if scale[i] > f32::EPSILON { Some(columns[i] / scale[i]) } else { None });
+        for i in 0..3 {
+            if directions[i].is_none() {
+                let (a, b) = (directions[(i + 1) % 3], directions[(i + 2) % 3]);
+                directions[i] = Some(match (a, b) {
+                    (Some(a), Some(b)) if a.cross(b).magnitude() > f32::EPSILON => a.cross(b).normalize(),
+                    _ => axes[i],
+                });
+            }
+        }
+        let [x, y, z] = directions.map(Option::unwrap);
+        let mut rotation = cgmath::Matrix3::from_cols(x, y, z);
+        // a mirrored matrix cannot be a rotation, flip one axis back into the scale
+        if rotation.determinant() < 0.0 {
+            scale.x = -scale.x;
+            rotation.x = -rotation.x;
+        }
+        Self {
+            position,
+            rotation: cgmath::Quaternion::from(rotation).normalize(),
+            scale,
+        }
+    }
+    // scale first, then rotate, then translate
+    pub fn to_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: cgmath::Vector3::zero(),
+            rotation: cgmath::Quaternion::one(),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
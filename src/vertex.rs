@@ -6,6 +6,7 @@ pub struct Vertex {
 }
 
 impl Vertex {
+    pub const LAYOUT: VertexLayout = VertexLayout::POSITION_TEX_COORDS;
     const ATTRIBS: [wgpu::VertexAttribute; 2] =
         wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
@@ -16,3 +17,165 @@ impl Vertex {
         }
     }
 }
+
+// the attributes a vertex can have. Each one is always read from the same shader location, in the order below
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VertexAttribute {
+    Position,
+    TexCoords,
+    Normal,
+    // xyz is the tangent, w the sign of the bitangent
+    Tangent,
+    Color,
+    // a second set of texture coordinates, like for light maps
+    TexCoords1,
+    Joints,
+    Weights,
+}
+
+impl VertexAttribute {
+    pub const ALL: [VertexAttribute; 8] = [
+        VertexAttribute::Position,
+        VertexAttribute::TexCoords,
+        VertexAttribute::Normal,
+        VertexAttribute::Tangent,
+        VertexAttribute::Color,
+        VertexAttribute::TexCoords1,
+        VertexAttribute::Joints,
+        VertexAttribute::Weights,
+    ];
+    pub fn shader_location(self) -> u32 {
+        self as u32
+    }
+    pub fn format(self) -> wgpu::VertexFormat {
+        match self {
+            VertexAttribute::Position => wgpu::VertexFormat::Float32x3,
+            VertexAttribute::TexCoords => wgpu::VertexFormat::Float32x2,
+            VertexAttribute::Normal => wgpu::VertexFormat::Float32x3,
+            VertexAttribute::Tangent => wgpu::VertexFormat::Float32x4,
+            VertexAttribute::Color => wgpu::VertexFormat::Float32x4,
+            VertexAttribute::TexCoords1 => wgpu::VertexFormat::Float32x2,
+            VertexAttribute::Joints => wgpu::VertexFormat::Uint16x4,
+            VertexAttribute::Weights => wgpu::VertexFormat::Float32x4,
+        }
+    }
+}
+
+// a set of vertex attributes, stored interleaved in one vertex buffer
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct VertexLayout {
+    bits: u8,
+}
+
+impl VertexLayout {
+    pub const POSITION_TEX_COORDS: VertexLayout = VertexLayout::new(&[VertexAttribute::Position, VertexAttribute::TexCoords]);
+
+    pub const fn new(attributes: &[VertexAttribute]) -> Self {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < attributes.len() {
+            bits |= 1 << attributes[i] as u8;
+            i += 1;
+        }
+        Self { bits }
+    }
+    pub fn with(self, attribute: VertexAttribute) -> Self {
+        Self { bits: self.bits | 1 << attribute as u8 }
+    }
+    pub fn contains(&self, attribute: VertexAttribute) -> bool {
+        self.bits & (1 << attribute as u8) != 0
+    }
+    pub fn contains_layout(&self, other: VertexLayout) -> bool {
+        self.bits & other.bits == other.bits
+    }
+    pub fn iter(&self) -> impl Iterator<Item = VertexAttribute> + '_ {
+        VertexAttribute::ALL.into_iter().filter(|attribute| self.contains(*attribute))
+    }
+    pub fn stride(&self) -> wgpu::BufferAddress {
+        self.iter().map(|attribute| attribute.format().size()).sum()
+    }
+    pub fn attributes(&self) -> Vec<wgpu::VertexAttribute> {
+        let mut offset = 0;
+        self.iter().map(|attribute| {
+            let result = wgpu::VertexAttribute {
+                format: attribute.format(),
+                offset,
+                shader_location: attribute.shader_location(),
+            };
+            offset += attribute.format().size();
+            result
+        }).collect()
+    }
+    // the attributes come from VertexLayout::attributes, the layout only borrows them
+    pub fn desc<'a>(&self, attributes: &'a [wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.stride(),
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes,
+        }
+    }
+    // interleaves the attributes of this layout, the others are left out
+    pub fn pack(&self, vertices: &[MeshVertex]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(vertices.len() * self.stride() as usize);
+        for vertex in vertices {
+            for attribute in self.iter() {
+                match attribute {
+                    VertexAttribute::Position => bytes.extend_from_slice(bytemuck::bytes_of(&vertex.position)),
+                    VertexAttribute::TexCoords => bytes.extend_from_slice(bytemuck::bytes_of(&vertex.tex_coords)),
+                    VertexAttribute::Normal => bytes.extend_from_slice(bytemuck::bytes_of(&vertex.normal)),
+                    VertexAttribute::Tangent => bytes.extend_from_slice(bytemuck::bytes_of(&vertex.tangent)),
+                    VertexAttribute::Color => bytes.extend_from_slice(bytemuck::bytes_of(&vertex.color)),
+                    VertexAttribute::TexCoords1 => bytes.extend_from_slice(bytemuck::bytes_of(&vertex.tex_coords1)),
+                    VertexAttribute::Joints => bytes.extend_from_slice(bytemuck::bytes_of(&vertex.joints)),
+                    VertexAttribute::Weights => bytes.extend_from_slice(bytemuck::bytes_of(&vertex.weights)),
+                }
+            }
+        }
+        bytes
+    }
+}
+
+impl std::fmt::Debug for VertexLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+// a vertex with every attribute, the cpu side copy of meshes. Only the ones in the layout of the mesh are uploaded
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 4],
+    pub color: [f32; 4],
+    pub tex_coords1: [f32; 2],
+    pub joints: [u16; 4],
+    pub weights: [f32; 4],
+}
+
+impl Default for MeshVertex {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0, 0.0],
+            tex_coords: [0.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+            tex_coords1: [0.0, 0.0],
+            // fully bound to the first joint
+            joints: [0, 0, 0, 0],
+            weights: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl From<Vertex> for MeshVertex {
+    fn from(vertex: Vertex) -> Self {
+        Self {
+            position: vertex.position,
+            tex_coords: vertex.tex_coords,
+            ..Default::default()
+        }
+    }
+}
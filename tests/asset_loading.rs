@@ -0,0 +1,110 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{assert_golden, lock_context, sphere, Tolerance};
+use learn_wgpu2::{
+    assets::LoadState,
+    environment_map::{Environment, EnvironmentMap},
+    material::Material,
+    my_texture::{MyTexture, TextureFiltering, TextureOptions, TextureSource},
+    pipelines::pbr_pipeline::PbrPipeline,
+    renderables::{cube::Cube, mesh::Mesh},
+    state::State,
+    transform::Transform,
+};
+
+// the headless context waits for its assets, these tests turn on the asynchronous loading of a windowed one.
+// Every key is only loaded in this file so that no other test has loaded it already
+fn load_asynchronously() {
+    if let Some(context) = lock_context().as_mut() {
+        context.asynchronous_loading = true;
+    }
+}
+
+#[test]
+fn requested_assets_are_read_on_workers() {
+    load_asynchronously();
+    let mut context = lock_context();
+    let Some(context) = context.as_mut() else {
+        return;
+    };
+    let key = (
+        TextureSource::MipChain(vec!["tests/fixtures/mips/level0.png".to_string(), "tests/fixtures/mips/level1.png".to_string()]),
+        TextureOptions::default().with_filtering(TextureFiltering::Nearest),
+    );
+    // the texture is only made once the render thread finishes the loads
+    let texture = context.request_asset::<MyTexture>(key.clone());
+    assert_eq!(texture.load_state(), LoadState::Loading);
+    assert_eq!(context.request_asset::<MyTexture>(key), texture);
+    assert!(context.assets.pending() > 0);
+    context.finish_loading();
+    assert_eq!(texture.load_state(), LoadState::Loaded);
+    assert_eq!(context.assets.pending(), 0);
+
+    // sources without files are made right away
+    let color = context.request_asset::<MyTexture>((TextureSource::SolidColor([1, 2, 3, 255]), TextureOptions::default()));
+    assert_eq!(color.load_state(), LoadState::Loaded);
+
+    // fonts are read on the worker that draws the character, a missing one fails the texture
+    let character = |font_file_path: &str| TextureSource::TextCharacter {
+        character: 'A',
+        font_file_path: font_file_path.to_string(),
+    };
+    let letter = context.request_asset::<MyTexture>((character("assets/times.ttf"), TextureOptions::default()));
+    let missing = context.request_asset::<MyTexture>((character("tests/fixtures/missing.ttf"), TextureOptions::default()));
+    context.finish_loading();
+    assert_eq!(letter.load_state(), LoadState::Loaded);
+    assert!(matches!(missing.load_state(), LoadState::Failed(_)), "{:?}", missing);
+}
+
+#[test]
+fn renderables_draw_a_placeholder_until_their_texture_is_loaded() {
+    load_asynchronously();
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 0.0, 9.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = 0.0;
+    let options = TextureOptions::default().with_address_mode(wgpu::AddressMode::MirrorRepeat);
+    for (x, file_path) in [(-1.5, "tests/fixtures/mips/level0.png"), (1.5, "tests/fixtures/missing.png")] {
+        let cube = Cube::new(file_path.to_string()).with_texture_options(options).with_transform(Transform {
+            position: cgmath::Vector3::new(x, 0.0, 0.0),
+            ..Default::default()
+        });
+        state.renderables.push(Box::new(cube));
+    }
+    // the files are read while the first frame is drawn, both cubes are gray
+    if assert_golden("assets_loading", &mut state, Tolerance::default()).is_none() {
+        return;
+    }
+    lock_context().as_ref().unwrap().finish_loading();
+    // the texture of the left cube is loaded, the right one failed and stays gray
+    assert_golden("assets_loaded", &mut state, Tolerance::default());
+}
+
+#[test]
+fn environments_light_the_scene_once_they_are_loaded() {
+    load_asynchronously();
+    let source = "tests/fixtures/hdr/uniform.hdr".to_string();
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 0.0, 4.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = 0.0;
+    state.environment = Some(Environment::new(source.clone()));
+    let material = Material {
+        roughness_factor: 1.0,
+        ..Default::default()
+    };
+    state.renderables.push(Box::new(Mesh::from_data(Arc::new(sphere(24, 48)), PbrPipeline::WHITE).with_material(Arc::new(material)).with_pbr().unwrap()));
+    // the panorama is decoded on a worker, until then the empty maps leave the sphere black
+    if assert_golden("environment_loading", &mut state, Tolerance::default()).is_none() {
+        return;
+    }
+    {
+        let context = lock_context();
+        let context = context.as_ref().unwrap();
+        context.finish_loading();
+        assert_eq!(context.assets.get_handle::<EnvironmentMap>(&source).unwrap().load_state(), LoadState::Loaded);
+    }
+    assert_golden("environment_loaded", &mut state, Tolerance::default());
+}
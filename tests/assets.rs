@@ -0,0 +1,148 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{assert_golden, lock_context, Tolerance};
+use learn_wgpu2::{
+    assets::LoadState,
+    cube_texture::CubeTexture,
+    material::{Material, MaterialTexture},
+    my_pipeline::ShaderSource,
+    my_texture::{MyTexture, TextureOptions, TextureSource},
+    pipelines::pbr_pipeline::PbrPipeline,
+    renderables::{
+        cube::Cube,
+        mesh::{MeshData, MeshSource},
+        skybox::Skybox,
+    },
+    state::State,
+    transform::Transform,
+};
+
+const LEVEL0: &str = "tests/fixtures/mips/level0.png";
+const LEVEL1: &str = "tests/fixtures/mips/level1.png";
+
+fn texture_key(file_path: &str) -> (TextureSource, TextureOptions) {
+    (TextureSource::FilePath(file_path.to_string()), TextureOptions::default())
+}
+
+#[test]
+fn loading_a_key_again_shares_the_asset() {
+    let mut context = lock_context();
+    let Some(context) = context.as_mut() else {
+        return;
+    };
+    let texture = context.load_asset::<MyTexture>(texture_key(LEVEL0));
+    assert_eq!(texture.load_state(), LoadState::Loaded);
+    let again = context.load_asset::<MyTexture>(texture_key(LEVEL0));
+    assert_eq!(texture, again);
+    assert!(Arc::ptr_eq(&texture.expect_loaded(), &again.expect_loaded()));
+    assert_eq!(texture.handle_count(), 2);
+
+    // other options are another texture
+    let repeated = context.load_asset::<MyTexture>((
+        TextureSource::FilePath(LEVEL0.to_string()),
+        TextureOptions::default().with_address_mode(wgpu::AddressMode::Repeat),
+    ));
+    assert_ne!(texture, repeated);
+    assert_eq!(context.assets.get_handle::<MyTexture>(&texture_key(LEVEL0)), Some(texture));
+}
+
+#[test]
+fn assets_are_dropped_with_their_last_handle() {
+    let mut context = lock_context();
+    let Some(context) = context.as_mut() else {
+        return;
+    };
+    let texture = context.load_asset::<MyTexture>(texture_key(LEVEL1));
+    let id = texture.id();
+    let weak = texture.downgrade();
+    let copy = texture.clone();
+    drop(texture);
+    assert!(weak.is_alive());
+    drop(copy);
+    assert!(!weak.is_alive());
+    assert!(context.assets.get_handle::<MyTexture>(&texture_key(LEVEL1)).is_none());
+
+    // loading it again makes a new asset
+    let texture = context.load_asset::<MyTexture>(texture_key(LEVEL1));
+    assert_ne!(texture.id(), id);
+    assert_eq!(texture.load_state(), LoadState::Loaded);
+}
+
+#[test]
+fn failed_loads_are_reported_on_the_handle() {
+    let mut context = lock_context();
+    let Some(context) = context.as_mut() else {
+        return;
+    };
+    let texture = context.load_asset::<MyTexture>(texture_key("tests/fixtures/missing.png"));
+    assert!(matches!(texture.load_state(), LoadState::Failed(_)), "{:?}", texture);
+    assert!(texture.get().is_none());
+    // the failure is kept while there is a handle
+    let again = context.load_asset::<MyTexture>(texture_key("tests/fixtures/missing.png"));
+    assert_eq!(texture, again);
+
+    let cube = context.load_asset::<CubeTexture>(("tests/fixtures/missing".to_string(), TextureOptions::default()));
+    assert!(matches!(cube.load_state(), LoadState::Failed(_)), "{:?}", cube);
+    let shader = context.load_asset::<wgpu::ShaderModule>(ShaderSource::File("tests/fixtures/missing.wgsl".to_string()));
+    assert!(matches!(shader.load_state(), LoadState::Failed(_)), "{:?}", shader);
+}
+
+#[test]
+fn renderables_hold_their_assets() {
+    // two cubes with the same texture in front of a skybox
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 0.0, 9.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = 0.0;
+    for x in [-1.5, 1.5] {
+        let cube = Cube::new(LEVEL0.to_string()).with_transform(Transform {
+            position: cgmath::Vector3::new(x, 0.0, 0.0),
+            ..Default::default()
+        });
+        state.renderables.push(Box::new(cube));
+    }
+    state.renderables.push(Box::new(Skybox::new("tests/fixtures/skybox".to_string())));
+    if assert_golden("assets_shared_by_cubes", &mut state, Tolerance::default()).is_none() {
+        return;
+    }
+
+    let context = lock_context();
+    let context = context.as_ref().unwrap();
+    // the cubes share their geometry and texture
+    let buffers: Vec<_> = state.renderables[..2].iter().map(|cube| cube.get_vertex_buffer(context)).collect();
+    assert!(Arc::ptr_eq(&buffers[0], &buffers[1]));
+    let mesh = context.assets.get_handle::<MeshData>(&MeshSource::Cube).unwrap();
+    assert_eq!(mesh.handle_count(), 3);
+    let texture = context.assets.get_handle::<MyTexture>(&texture_key(LEVEL0)).unwrap();
+    assert_eq!(texture.handle_count(), 3);
+    let skybox_key = ("tests/fixtures/skybox".to_string(), TextureOptions::default());
+    assert!(context.assets.get_handle::<CubeTexture>(&skybox_key).is_some());
+    drop((mesh, texture));
+
+    // and free them when they are gone
+    drop(state);
+    assert!(context.assets.get_handle::<MeshData>(&MeshSource::Cube).is_none());
+    assert!(context.assets.get_handle::<MeshData>(&MeshSource::SkyCube).is_none());
+    assert!(context.assets.get_handle::<CubeTexture>(&skybox_key).is_none());
+}
+
+#[test]
+fn materials_free_their_textures_once_they_are_dropped() {
+    let mut context = lock_context();
+    let Some(context) = context.as_mut() else {
+        return;
+    };
+    let file_path = "tests/fixtures/mips/level2.png";
+    let material = Arc::new(Material {
+        base_color_texture: Some(MaterialTexture::new(TextureSource::FilePath(file_path.to_string()))),
+        ..Default::default()
+    });
+    PbrPipeline::get_material_bind_group(context, &material);
+    assert!(context.assets.get_handle::<MyTexture>(&texture_key(file_path)).is_some());
+    drop(material);
+    // the bind groups of dropped materials are forgotten when the next one is asked for
+    PbrPipeline::get_material_bind_group(context, &Arc::new(Material::default()));
+    assert!(context.assets.get_handle::<MyTexture>(&texture_key(file_path)).is_none());
+}
@@ -0,0 +1,193 @@
+// golden image harness shared by the integration tests
+
+use std::{
+    path::PathBuf,
+    sync::{Mutex, MutexGuard, OnceLock},
+};
+
+use learn_wgpu2::{
+    batching::BatchStats,
+    renderables::mesh::MeshData,
+    render_context::RenderContext,
+    state::State,
+    vertex::{MeshVertex, Vertex, VertexAttribute},
+};
+
+pub const WIDTH: u32 = 128;
+pub const HEIGHT: u32 = 128;
+
+// Creating a context takes a while, so the tests of a binary share one. Renderables keep the assets of the context
+// that drew them first, so a state is only drawn with one context.
+static CONTEXT: OnceLock<Mutex<Option<RenderContext>>> = OnceLock::new();
+
+/// Panics if the machine has no adapter at all, unless SKIP_WITHOUT_ADAPTER=1 is set.
+/// Then it returns None and the test should be skipped.
+pub fn lock_context() -> MutexGuard<'static, Option<RenderContext>> {
+    CONTEXT
+        .get_or_init(|| {
+            let context = RenderContext::new_headless(WIDTH, HEIGHT, wgpu::TextureFormat::Rgba8UnormSrgb, false);
+            if context.is_none() {
+                assert!(
+                    std::env::var("SKIP_WITHOUT_ADAPTER").is_ok_and(|value| value == "1"),
+                    "No adapter available, set SKIP_WITHOUT_ADAPTER=1 to skip the tests that render"
+                );
+                eprintln!("No adapter available, the tests that render are skipped");
+            }
+            Mutex::new(context)
+        })
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub struct Tolerance {
+    // maximum difference of a single channel for a pixel to still match
+    pub per_channel: u8,
+    // fraction of pixels that may mismatch, absorbs rasterization differences at edges between adapters
+    pub max_mismatched_fraction: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            per_channel: 3,
+            max_mismatched_fraction: 0.005,
+        }
+    }
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name))
+}
+
+fn failure_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden-failures")
+}
+
+/// Renders one frame of the state and compares it to tests/golden/<name>.png.
+/// Set UPDATE_GOLDEN=1 to write the references instead.
+/// Returns the batching statistics of the frame, or None if the test was skipped.
+pub fn assert_golden(name: &str, state: &mut State, tolerance: Tolerance) -> Option<BatchStats> {
+    let mut context = lock_context();
+    let Some(context) = context.as_mut() else {
+        eprintln!("{} is skipped without an adapter", name);
+        return None;
+    };
+    Some(assert_golden_with(context, name, state, tolerance))
+}
+
+/// Like assert_golden, with a context of the test's own.
+pub fn assert_golden_with(context: &mut RenderContext, name: &str, state: &mut State, tolerance: Tolerance) -> BatchStats {
+    context.request_screenshot();
+    context.render(state).expect("Failed to render frame");
+    let actual = context.take_screenshot().expect("Failed to capture frame");
+
+    let golden_path = golden_path(name);
+    if std::env::var("UPDATE_GOLDEN").is_ok_and(|value| value == "1") {
+        actual.save(&golden_path).unwrap();
+        println!("Updated {}", golden_path.display());
+        return context.batch_stats;
+    }
+    let failure_dir = failure_dir();
+    std::fs::create_dir_all(&failure_dir).unwrap();
+    let actual_path = failure_dir.join(format!("{}_actual.png", name));
+    let expected = match image::open(&golden_path) {
+        Ok(expected) => expected.to_rgba8(),
+        Err(e) => {
+            actual.save(&actual_path).unwrap();
+            panic!(
+                "Failed to open {}: {}. Actual image written to {}, run with UPDATE_GOLDEN=1 to accept it",
+                golden_path.display(),
+                e,
+                actual_path.display()
+            );
+        }
+    };
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "Size of {} does not match the rendered frame",
+        golden_path.display()
+    );
+
+    let (diff, mismatched) = diff_images(&expected, &actual, tolerance.per_channel);
+    let total = (actual.width() * actual.height()) as f32;
+    if mismatched as f32 / total > tolerance.max_mismatched_fraction {
+        let diff_path = failure_dir.join(format!("{}_diff.png", name));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{} pixels of {} differ from {}. Actual: {}, diff: {}",
+            mismatched,
+            name,
+            golden_path.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+    context.batch_stats
+}
+
+// mismatched pixels are red, matching pixels are a dimmed gray version of the expected image
+fn diff_images(expected: &image::RgbaImage, actual: &image::RgbaImage, per_channel: u8) -> (image::RgbaImage, usize) {
+    let mut mismatched = 0;
+    let diff = image::RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let e = expected.get_pixel(x, y);
+        let a = actual.get_pixel(x, y);
+        let matches = e.0.iter().zip(a.0.iter()).all(|(e, a)| e.abs_diff(*a) <= per_channel);
+        if matches {
+            let gray = ((e[0] as u32 + e[1] as u32 + e[2] as u32) / 3 / 3) as u8;
+            image::Rgba([gray, gray, gray, 255])
+        } else {
+            mismatched += 1;
+            image::Rgba([255, 0, 0, 255])
+        }
+    });
+    (diff, mismatched)
+}
+
+/// Unit sphere around the origin, its normals double as positions.
+// not every test binary draws spheres or floors
+#[allow(dead_code)]
+pub fn sphere(rings: u32, segments: u32) -> MeshData {
+    let mut vertices = Vec::new();
+    for ring in 0..=rings {
+        let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+        for segment in 0..=segments {
+            let phi = 2.0 * std::f32::consts::PI * segment as f32 / segments as f32;
+            let normal = [theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()];
+            vertices.push(MeshVertex {
+                position: normal,
+                normal,
+                tex_coords: [segment as f32 / segments as f32, ring as f32 / rings as f32],
+                ..Default::default()
+            });
+        }
+    }
+    let mut indices = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let i = ring * (segments + 1) + segment;
+            let below = i + segments + 1;
+            indices.extend([i, i + 1, below, i + 1, below + 1, below]);
+        }
+    }
+    MeshData::from_mesh_vertices("sphere".to_string(), vertices, Vertex::LAYOUT.with(VertexAttribute::Normal), Some(indices))
+}
+
+/// Upward facing quad at y = -1 reaching half_width along x and half_depth along z, textured once.
+#[allow(dead_code)]
+pub fn floor(half_width: f32, half_depth: f32) -> MeshData {
+    let vertex = |x: f32, z: f32, u: f32, v: f32| MeshVertex {
+        position: [x, -1.0, z],
+        tex_coords: [u, v],
+        normal: [0.0, 1.0, 0.0],
+        ..Default::default()
+    };
+    let vertices = vec![
+        vertex(-half_width, half_depth, 0.0, 1.0),
+        vertex(half_width, half_depth, 1.0, 1.0),
+        vertex(half_width, -half_depth, 1.0, 0.0),
+        vertex(-half_width, -half_depth, 0.0, 0.0),
+    ];
+    MeshData::from_mesh_vertices("floor".to_string(), vertices, Vertex::LAYOUT.with(VertexAttribute::Normal), Some(vec![0, 1, 2, 2, 3, 0]))
+}
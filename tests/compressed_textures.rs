@@ -0,0 +1,213 @@
+mod common;
+
+use common::{assert_golden, lock_context, Tolerance};
+use learn_wgpu2::{
+    bc_decode::decode_bc,
+    compressed_texture::CompressedImage,
+    cube_texture::CubeTexture,
+    my_texture::{ColorSpace, TextureFiltering, TextureOptions, TextureSource},
+    renderables::{cube::Cube, mesh::Mesh, skybox::Skybox},
+    state::State,
+    vertex::Vertex,
+};
+
+const FIXTURES: &str = "tests/fixtures/compressed";
+
+// the texels of a 4x4 bc image, as rgba8
+fn decode_block(format: wgpu::TextureFormat, block: u128) -> Vec<[u8; 4]> {
+    let size = format.block_copy_size(None).unwrap() as usize;
+    decode_bc(format, 4, 4, &block.to_le_bytes()[..size])
+        .chunks_exact(4)
+        .map(|texel| texel.try_into().unwrap())
+        .collect()
+}
+
+#[test]
+fn bc_blocks_decode_to_known_texels() {
+    // red and blue with the two colors between them
+    let texels = decode_block(wgpu::TextureFormat::Bc1RgbaUnorm, 0xe4 << 32 | 0x001f << 16 | 0xf800);
+    assert_eq!(&texels[..4], &[[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255]]);
+    // the smaller color first switches to the half way color and transparent black
+    let texels = decode_block(wgpu::TextureFormat::Bc1RgbaUnorm, 0xe4 << 32 | 0xf800 << 16 | 0x001f);
+    assert_eq!(&texels[2..4], &[[127, 0, 127, 255], [0, 0, 0, 0]]);
+
+    // six values between the endpoints, rounded to the nearest. Indices 0, 1, 2 and 7
+    let texels = decode_block(wgpu::TextureFormat::Bc4RUnorm, 0o7210 << 16 | 0x00ff);
+    let red: Vec<u8> = texels[..4].iter().map(|texel| texel[0]).collect();
+    assert_eq!(red, vec![255, 0, 219, 36]);
+
+    // mode 6 with both endpoints at one color, the p-bit restores the lowest bit of it
+    let color = [201u128, 101, 51, 255];
+    let mut block = 1 << 6;
+    for (channel, value) in color.iter().enumerate() {
+        block |= (value >> 1) << (7 + channel * 14) | (value >> 1) << (14 + channel * 14);
+    }
+    block |= 1 << 63 | 1 << 64;
+    let texels = decode_block(wgpu::TextureFormat::Bc7RgbaUnorm, block);
+    assert!(texels.iter().all(|texel| *texel == [201, 101, 51, 255]), "{:?}", texels[0]);
+
+    // one region of ten bit endpoints from zero to the largest value, texel 0 at the first and texel 1 at the second
+    let block = 0b00011 | 0x3ff << 35 | 0x3ff << 45 | 0x3ff << 55 | 15 << 68;
+    let decoded = decode_bc(wgpu::TextureFormat::Bc6hRgbUfloat, 4, 4, &u128::to_le_bytes(block));
+    let halfs: Vec<f32> = decoded.chunks_exact(2).map(|bytes| half::f16::from_le_bytes([bytes[0], bytes[1]]).to_f32()).collect();
+    assert_eq!(&halfs[..8], &[0.0, 0.0, 0.0, 1.0, 65504.0, 65504.0, 65504.0, 1.0]);
+}
+
+#[test]
+fn containers_keep_their_mip_levels_and_faces() {
+    let checker = CompressedImage::open(&format!("{}/checker_mips.dds", FIXTURES)).unwrap();
+    assert_eq!(checker.format.remove_srgb_suffix(), wgpu::TextureFormat::Bc1RgbaUnorm);
+    assert_eq!((checker.size.width, checker.size.height, checker.size.depth_or_array_layers), (64, 64, 1));
+    assert_eq!(checker.levels.len(), 7);
+    // levels below the block size still take a whole block
+    assert_eq!(checker.levels[6].len(), 8);
+
+    let skybox = CompressedImage::open(&format!("{}/skybox_bc1.ktx2", FIXTURES)).unwrap();
+    assert_eq!(skybox.size.depth_or_array_layers, 6);
+    assert_eq!(skybox.levels[0].len(), 6 * 8 * 8 * 8);
+
+    let decoded = skybox.decompress();
+    assert_eq!(decoded.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+    assert_eq!(decoded.levels[0].len(), 6 * 32 * 32 * 4);
+
+    assert!(CompressedImage::open("tests/fixtures/mips/level0.png.dds").is_err());
+    // a single face is no cube
+    assert!(CubeTexture::read(&format!("{}/checker_mips.dds", FIXTURES)).is_err());
+}
+
+#[test]
+fn bc1_without_alpha_decodes_opaque() {
+    // the three color mode, every texel takes the fourth color
+    let block = 0xffff_ffff << 32 | 0xf800 << 16 | 0x001f;
+    let mut image = CompressedImage {
+        format: wgpu::TextureFormat::Bc1RgbaUnorm,
+        size: wgpu::Extent3d { width: 4, height: 4, depth_or_array_layers: 1 },
+        levels: vec![u64::to_le_bytes(block).to_vec()],
+        opaque: false,
+    };
+    assert_eq!(&image.decompress().levels[0][..4], &[0, 0, 0, 0]);
+    image.opaque = true;
+    assert_eq!(&image.decompress().levels[0][..4], &[0, 0, 0, 255]);
+}
+
+// a quad that fills the frame, texel for texel
+fn render_quad(file_path: &str, color_space: ColorSpace, exposure: f32) -> Option<image::RgbaImage> {
+    let mut state = State { exposure, ..Default::default() };
+    state.camera.pos = cgmath::Point3::new(0.0, 0.0, 2.414);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = 0.0;
+    let vertices = vec![
+        Vertex { position: [-1.0, -1.0, 0.0], tex_coords: [0.0, 1.0] },
+        Vertex { position: [1.0, -1.0, 0.0], tex_coords: [1.0, 1.0] },
+        Vertex { position: [1.0, 1.0, 0.0], tex_coords: [1.0, 0.0] },
+        Vertex { position: [-1.0, 1.0, 0.0], tex_coords: [0.0, 0.0] },
+    ];
+    let quad = Mesh::new("quad".to_string(), vertices, vec![0, 1, 2, 2, 3, 0], TextureSource::FilePath(file_path.to_string()))
+        .with_texture_options(
+            TextureOptions::default().with_filtering(TextureFiltering::Nearest).with_color_space(color_space),
+        );
+    state.renderables.push(Box::new(quad));
+    let mut context = lock_context();
+    let context = context.as_mut()?;
+    context.request_screenshot();
+    context.render(&mut state).unwrap();
+    context.take_screenshot()
+}
+
+// writes the image decoded on the cpu as an uncompressed dds file
+fn write_decoded(image: &CompressedImage, name: &str) -> String {
+    let decoded = image.decompress();
+    let format = match decoded.format {
+        wgpu::TextureFormat::Rgba8Unorm => ddsfile::DxgiFormat::R8G8B8A8_UNorm,
+        wgpu::TextureFormat::Rgba8UnormSrgb => ddsfile::DxgiFormat::R8G8B8A8_UNorm_sRGB,
+        wgpu::TextureFormat::Rgba8Snorm => ddsfile::DxgiFormat::R8G8B8A8_SNorm,
+        wgpu::TextureFormat::Rgba16Float => ddsfile::DxgiFormat::R16G16B16A16_Float,
+        format => panic!("unexpected format {:?}", format),
+    };
+    let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+        height: decoded.size.height,
+        width: decoded.size.width,
+        depth: None,
+        format,
+        mipmap_levels: Some(1),
+        array_layers: None,
+        caps2: None,
+        is_cubemap: false,
+        resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+        alpha_mode: ddsfile::AlphaMode::Unknown,
+    })
+    .unwrap();
+    dds.data = decoded.levels[0].clone();
+    let path = format!("{}/{}_decoded.dds", env!("CARGO_TARGET_TMPDIR"), name);
+    dds.write(&mut std::fs::File::create(&path).unwrap()).unwrap();
+    path
+}
+
+// decoders of devices interpolate with more or less than eight bits, so a step of the srgb frame
+// or two of the linear value it stands for are allowed
+fn texels_match(a: &image::Rgba<u8>, b: &image::Rgba<u8>) -> bool {
+    let linear = |value: u8| {
+        let value = value as f32 / 255.0;
+        255.0 * if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+    };
+    a.0.iter().zip(b.0.iter()).all(|(a, b)| a.abs_diff(*b) <= 1 || (linear(*a) - linear(*b)).abs() <= 2.0)
+}
+
+#[test]
+fn gpu_and_cpu_decoding_match() {
+    // random blocks use every mode and partition, so the decoder of the device checks the tables of the cpu one.
+    // Without the bc feature both sides are decoded on the cpu
+    for name in ["bc1", "bc2", "bc3", "bc4", "bc5", "bc6h", "bc6h_signed", "bc7"] {
+        let path = format!("{}/random_{}.ktx2", FIXTURES, name);
+        let image = CompressedImage::open(&path).unwrap();
+        let decoded_path = write_decoded(&image, name);
+        // srgb formats stay srgb, so the frame shows the texels as they are decoded
+        let color_space = if image.format.add_srgb_suffix() == image.format { ColorSpace::Linear } else { ColorSpace::Srgb };
+        // bc6h values reach up to 65504, lower exposures bring the larger ones into view
+        let exposures: &[f32] = if name.starts_with("bc6h") { &[1.0, 1.0 / 256.0, 1.0 / 65536.0] } else { &[1.0] };
+        for exposure in exposures {
+            let Some(compressed) = render_quad(&path, color_space, *exposure) else {
+                return;
+            };
+            let decoded = render_quad(&decoded_path, color_space, *exposure).unwrap();
+            let mismatched = compressed.pixels().zip(decoded.pixels()).filter(|(a, b)| !texels_match(a, b)).count();
+            assert_eq!(mismatched, 0, "{} pixels of {} differ between the gpu and the cpu at exposure {}", mismatched, name, exposure);
+        }
+    }
+}
+
+#[test]
+fn dds_mip_chain_floor() {
+    // like the precomputed mipmaps floor, the colors change with every level of the file
+    let vertices = vec![
+        Vertex { position: [-2.0, 0.0, 2.0], tex_coords: [0.0, 4.0] },
+        Vertex { position: [2.0, 0.0, 2.0], tex_coords: [4.0, 4.0] },
+        Vertex { position: [2.0, 0.0, -30.0], tex_coords: [4.0, 0.0] },
+        Vertex { position: [-2.0, 0.0, -30.0], tex_coords: [0.0, 0.0] },
+    ];
+    let floor = Mesh::new(
+        "floor".to_string(),
+        vertices,
+        vec![0, 1, 2, 2, 3, 0],
+        TextureSource::FilePath(format!("{}/checker_mips.dds", FIXTURES)),
+    )
+    .with_texture_options(TextureOptions::default().with_address_mode(wgpu::AddressMode::Repeat));
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 1.0, 3.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = -15.0;
+    state.renderables.push(Box::new(floor));
+    assert_golden("compressed_dds_mip_chain_floor", &mut state, Tolerance::default());
+}
+
+#[test]
+fn ktx2_cube_skybox_behind_cube() {
+    // the faces of the skybox fixture in one bc1 file, it should look like skybox_pipeline_behind_cube
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(3.0, 2.0, 5.0);
+    state.camera.yaw = -120.96;
+    state.camera.pitch = -18.93;
+    state.renderables.push(Box::new(Skybox::new(format!("{}/skybox_bc1.ktx2", FIXTURES))));
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+    assert_golden("compressed_ktx2_skybox_behind_cube", &mut state, Tolerance::default());
+}
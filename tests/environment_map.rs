@@ -0,0 +1,139 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{assert_golden, floor, lock_context, sphere, Tolerance};
+use learn_wgpu2::{
+    environment_map::Environment,
+    material::Material,
+    pipelines::{pbr_pipeline::PbrPipeline, tonemap_pipeline::Tonemapping},
+    renderables::{
+        mesh::{Mesh, MeshData},
+        skybox::Skybox,
+    },
+    state::State,
+    transform::Transform,
+};
+
+const SKY: &str = "tests/fixtures/hdr/sky.hdr";
+const UNIFORM: &str = "tests/fixtures/hdr/uniform.hdr";
+
+fn pbr_sphere(sphere: &Arc<MeshData>, x: f32, y: f32, base_color: [f32; 4], metallic: f32, roughness: f32) -> Mesh {
+    let material = Material {
+        base_color_factor: base_color,
+        metallic_factor: metallic,
+        roughness_factor: roughness,
+        ..Default::default()
+    };
+    Mesh::from_data(sphere.clone(), PbrPipeline::WHITE)
+        .with_material(Arc::new(material))
+        .with_pbr().unwrap()
+        .with_transform(Transform {
+            position: cgmath::Vector3::new(x, y, 0.0),
+            scale: cgmath::Vector3::new(0.7, 0.7, 0.7),
+            ..Default::default()
+        })
+}
+
+fn render(state: &mut State) -> Option<image::RgbaImage> {
+    let mut context = lock_context();
+    let context = context.as_mut()?;
+    context.request_screenshot();
+    context.render(state).unwrap();
+    context.take_screenshot()
+}
+
+#[test]
+fn uniform_sky_lights_every_material_alike() {
+    // in a sky of the same radiance everywhere, white surfaces reflect about that radiance whatever their material.
+    // Anything else points at the irradiance, the prefiltered levels or the brdf lookup table
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 0.0, 6.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = 0.0;
+    state.ambient_light = [1.0, 0.0, 0.0];
+    state.environment = Some(Environment::new(UNIFORM.to_string()));
+    let sphere = Arc::new(sphere(24, 48));
+    let materials = [(-1.5, 0.0, 1.0), (0.0, 0.0, 0.2), (1.5, 1.0, 0.0)];
+    for (x, metallic, roughness) in materials {
+        state.renderables.push(Box::new(pbr_sphere(&sphere, x, 0.0, [1.0, 1.0, 1.0, 1.0], metallic, roughness)));
+    }
+    let Some(frame) = render(&mut state) else {
+        return;
+    };
+    let (width, height) = frame.dimensions();
+    let half_width = 6.0 * (state.camera.fovy.to_radians() / 2.0).tan() * width as f32 / height as f32;
+    let center_of = |x: f32| (width as f32 * (0.5 + x / (2.0 * half_width))) as u32;
+    // 0.5 in linear is 188 in srgb, the centers of the spheres face the camera
+    let centers = [-1.5, 0.0, 1.5].map(|x| frame.get_pixel(center_of(x), height / 2).0);
+    for (center, (_, metallic, roughness)) in centers.iter().zip(materials) {
+        assert!(
+            center[..3].iter().all(|value| value.abs_diff(188) <= 8),
+            "a sphere with metallic {} and roughness {} is {:?}",
+            metallic,
+            roughness,
+            center
+        );
+    }
+
+    // rough metals lose some of it, the light that bounces between microfacets more than once is not counted
+    state.renderables.push(Box::new(pbr_sphere(&sphere, 0.0, 0.0, [1.0, 1.0, 1.0, 1.0], 1.0, 0.5)));
+    state.renderables.swap_remove(1);
+    let frame = render(&mut state).unwrap();
+    let center = frame.get_pixel(center_of(0.0), height / 2).0;
+    assert!((160..188).contains(&center[0]), "a rough metal sphere is {:?}", center);
+
+    // the intensity scales the light of the sky
+    state.environment = Some(Environment::new(UNIFORM.to_string()).with_intensity(0.0));
+    let dark = render(&mut state).unwrap();
+    assert_eq!(dark.get_pixel(width / 2, height / 2).0, [0, 0, 0, 255]);
+
+    // without an environment the ambient color is back
+    state.environment = None;
+    let ambient = render(&mut state).unwrap();
+    let center = ambient.get_pixel(center_of(-1.5), height / 2).0;
+    assert!(center[0] > 200 && center[1] == 0 && center[2] == 0, "{:?}", center);
+}
+
+#[test]
+fn exr_and_hdr_panoramas_match() {
+    // the same panorama written as exr makes the same skybox
+    let panorama = image::open(SKY).unwrap();
+    let exr_path = format!("{}/sky.exr", env!("CARGO_TARGET_TMPDIR"));
+    image::DynamicImage::ImageRgba32F(panorama.to_rgba32f()).save(&exr_path).unwrap();
+    let skybox_frame = |source: &str| {
+        let mut state = State::default();
+        state.camera.pos = cgmath::Point3::new(0.0, 0.0, 0.0);
+        state.camera.yaw = -45.0;
+        state.camera.pitch = 20.0;
+        state.renderables.push(Box::new(Skybox::new(source.to_string())));
+        render(&mut state)
+    };
+    let Some(hdr) = skybox_frame(SKY) else {
+        return;
+    };
+    let exr = skybox_frame(&exr_path).unwrap();
+    assert!(hdr == exr, "the skyboxes of the hdr and exr panoramas differ");
+}
+
+#[test]
+fn hdr_skybox_lights_spheres() {
+    // the green marker of the panorama is straight ahead and the sun is up to the right, the spheres reflect both
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 0.5, 5.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = 5.0;
+    state.tonemapping = Tonemapping::Aces;
+    state.environment = Some(Environment::new(SKY.to_string()));
+    state.renderables.push(Box::new(Skybox::new(SKY.to_string())));
+    let sphere = Arc::new(sphere(24, 48));
+    // dielectrics in the bottom row, metals in the top one, rougher to the right
+    for (i, roughness) in [0.05, 0.4, 0.8].into_iter().enumerate() {
+        let x = (i as f32 - 1.0) * 1.6;
+        state.renderables.push(Box::new(pbr_sphere(&sphere, x, -0.1, [0.8, 0.1, 0.1, 1.0], 0.0, roughness)));
+        state.renderables.push(Box::new(pbr_sphere(&sphere, x, 1.5, [1.0, 0.78, 0.34, 1.0], 1.0, roughness)));
+    }
+    // the lit pipeline takes the diffuse light of the sky
+    state.renderables.push(Box::new(Mesh::from_data(Arc::new(floor(4.0, 2.0)), PbrPipeline::WHITE).with_lighting().unwrap()));
+    assert_golden("environment_map_hdr_skybox_lights_spheres", &mut state, Tolerance::default());
+}
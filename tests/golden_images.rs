@@ -0,0 +1,126 @@
+mod common;
+
+use common::{assert_golden, assert_golden_with, lock_context, Tolerance, HEIGHT, WIDTH};
+use learn_wgpu2::{
+    instance::Instance,
+    my_texture::TextureSource,
+    render_context::RenderContext,
+    renderables::{cube::Cube, skybox::Skybox, ui::UI},
+    state::State,
+    transform::Transform,
+};
+
+// a camera looking at the origin from above and to the side, so three faces of the cube are visible
+fn create_state() -> State {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(3.0, 2.0, 5.0);
+    state.camera.yaw = -120.96;
+    state.camera.pitch = -18.93;
+    state
+}
+
+#[test]
+fn default_pipeline_cube() {
+    let mut state = create_state();
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+    assert_golden("default_pipeline_cube", &mut state, Tolerance::default());
+}
+
+#[test]
+fn default_pipeline_transformed_cubes() {
+    let mut state = create_state();
+    let left = Transform::new(
+        cgmath::Vector3::new(-1.5, 0.0, 0.0),
+        cgmath::Quaternion::from(cgmath::Euler::new(cgmath::Deg(0.0), cgmath::Deg(30.0), cgmath::Deg(0.0))),
+        cgmath::Vector3::new(0.5, 0.5, 0.5),
+    );
+    let right = Transform::from_position(cgmath::Vector3::new(1.5, 0.5, -1.0));
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string()).with_transform(left)));
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string()).with_transform(right)));
+    let stats = assert_golden("default_pipeline_transformed_cubes", &mut state, Tolerance::default());
+    // both cubes share the texture, so they are merged into one instanced draw
+    if let Some(stats) = stats {
+        assert_eq!(stats.renderables, 2);
+        assert_eq!(stats.draws, 1);
+    }
+}
+
+#[test]
+fn merged_cubes_follow_their_transforms_between_frames() {
+    // the instance buffer of the batch is kept and rewritten once the cubes moved
+    let mut state = create_state();
+    for _ in 0..2 {
+        state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+    }
+    {
+        let mut context = lock_context();
+        let Some(context) = context.as_mut() else {
+            return;
+        };
+        context.render(&mut state).expect("Failed to render frame");
+    }
+    *state.renderables[0].get_transform_mut().unwrap() = Transform::new(
+        cgmath::Vector3::new(-1.5, 0.0, 0.0),
+        cgmath::Quaternion::from(cgmath::Euler::new(cgmath::Deg(0.0), cgmath::Deg(30.0), cgmath::Deg(0.0))),
+        cgmath::Vector3::new(0.5, 0.5, 0.5),
+    );
+    *state.renderables[1].get_transform_mut().unwrap() = Transform::from_position(cgmath::Vector3::new(1.5, 0.5, -1.0));
+    assert_golden("default_pipeline_transformed_cubes", &mut state, Tolerance::default());
+}
+
+#[test]
+fn default_pipeline_instanced_cubes() {
+    let mut state = create_state();
+    let mut instances = Vec::new();
+    for x in -1..=1 {
+        for z in -1..=1 {
+            let mut instance = Instance::from_transform(Transform {
+                position: cgmath::Vector3::new(x as f32 * 1.2, 0.0, z as f32 * 1.2),
+                scale: cgmath::Vector3::new(0.4, 0.4, 0.4),
+                ..Default::default()
+            });
+            instance.tint = [1.0, (x + 2) as f32 / 3.0, (z + 2) as f32 / 3.0, 1.0];
+            instances.push(instance);
+        }
+    }
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string()).with_instances(instances)));
+    assert_golden("default_pipeline_instanced_cubes", &mut state, Tolerance::default());
+}
+
+#[test]
+fn skybox_pipeline_behind_cube() {
+    let mut state = create_state();
+    state.renderables.push(Box::new(Skybox::new("tests/fixtures/skybox".to_string())));
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+    assert_golden("skybox_pipeline_behind_cube", &mut state, Tolerance::default());
+}
+
+#[test]
+fn two_contexts_render_side_by_side() {
+    // every context makes its own pipelines and bind groups, none of them may be used with the device of the other
+    if lock_context().is_none() {
+        return;
+    }
+    let mut second = RenderContext::new_headless(WIDTH, HEIGHT, wgpu::TextureFormat::Rgba8UnormSrgb, false).unwrap();
+    let scene = || {
+        let mut state = create_state();
+        state.renderables.push(Box::new(Skybox::new("tests/fixtures/skybox".to_string())));
+        state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+        state
+    };
+    let mut second_state = scene();
+    assert_golden_with(&mut second, "skybox_pipeline_behind_cube", &mut second_state, Tolerance::default());
+    assert_golden("skybox_pipeline_behind_cube", &mut scene(), Tolerance::default());
+    assert_golden_with(&mut second, "skybox_pipeline_behind_cube", &mut second_state, Tolerance::default());
+}
+
+#[test]
+fn ui_pipeline_overlay() {
+    let mut state = create_state();
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+    state.renderables.push(Box::new(UI::new(TextureSource::TextCharacter {
+        character: 'A',
+        font_file_path: "assets/times.ttf".to_string(),
+    })));
+    assert_golden("ui_pipeline_overlay", &mut state, Tolerance::default());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{assert_golden, floor, sphere, Tolerance};
+use learn_wgpu2::{
+    light::{Light, LightRaw},
+    my_texture::TextureSource,
+    renderables::mesh::Mesh,
+    state::State,
+    transform::Transform,
+};
+
+#[test]
+fn lights_are_packed_for_the_shader() {
+    let spot = Light::Spot {
+        position: cgmath::Vector3::new(0.0, 2.0, 0.0),
+        direction: cgmath::Vector3::new(0.0, -2.0, 0.0),
+        color: [1.0, 0.5, 0.0],
+        intensity: 2.0,
+        range: 10.0,
+        inner_angle: 60.0,
+        outer_angle: 90.0,
+        cast_shadows: false,
+    };
+    let raw = spot.to_raw();
+    assert_eq!(raw.position, [0.0, 2.0, 0.0, LightRaw::SPOT]);
+    assert_eq!(raw.direction, [0.0, -1.0, 0.0, 0.0]);
+    assert_eq!(raw.color, [2.0, 1.0, 0.0, 10.0]);
+    assert!((raw.cone[0] - 0.5).abs() < 1e-6);
+    assert!(raw.cone[1].abs() < 1e-6);
+}
+
+#[test]
+fn lit_pipeline_with_every_light_kind() {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(3.0, 2.0, 5.0);
+    state.camera.yaw = -120.96;
+    state.camera.pitch = -18.93;
+    state.ambient_light = [0.05, 0.05, 0.05];
+    state.lights = vec![
+        Light::Directional {
+            direction: cgmath::Vector3::new(-1.0, -1.0, -0.5),
+            color: [1.0, 1.0, 0.9],
+            intensity: 0.6,
+            cast_shadows: false,
+        },
+        Light::Point {
+            position: cgmath::Vector3::new(1.5, 0.0, 1.5),
+            color: [1.0, 0.2, 0.2],
+            intensity: 4.0,
+            range: 4.0,
+        },
+        Light::Spot {
+            position: cgmath::Vector3::new(-1.5, 2.0, 1.0),
+            direction: cgmath::Vector3::new(0.0, -1.0, 0.0),
+            color: [0.2, 0.4, 1.0],
+            intensity: 6.0,
+            range: 6.0,
+            inner_angle: 15.0,
+            outer_angle: 25.0,
+            cast_shadows: false,
+        },
+    ];
+    let white = TextureSource::SolidColor([255, 255, 255, 255]);
+    let sphere = Mesh::from_data(Arc::new(sphere(24, 48)), white.clone())
+        .with_lighting().unwrap()
+        .with_transform(Transform {
+            scale: cgmath::Vector3::new(0.8, 0.8, 0.8),
+            ..Default::default()
+        });
+    state.renderables.push(Box::new(sphere));
+    state.renderables.push(Box::new(Mesh::from_data(Arc::new(floor(3.0, 3.0)), white).with_lighting().unwrap()));
+    assert_golden("lit_pipeline_lights", &mut state, Tolerance::default());
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::{assert_golden, Tolerance};
+use learn_wgpu2::{
+    my_texture::TextureSource,
+    renderable::{IndexFormat, Renderable},
+    renderables::mesh::{Mesh, MeshIndices},
+    state::State,
+    vertex::Vertex,
+};
+
+const SCENE: &str = "tests/fixtures/mesh/scene.obj";
+
+#[test]
+fn obj_faces_are_triangulated_and_vertices_merged() {
+    let meshes = Mesh::load_obj(SCENE).unwrap();
+    assert_eq!(meshes.len(), 2);
+
+    let floor = &meshes[0];
+    assert_eq!(floor.data().name, "floor");
+    // one quad becomes two triangles sharing two corners
+    assert_eq!(floor.data().vertices.len(), 4);
+    assert_eq!(floor.data().indices.len(), 6);
+    // the v coordinate is flipped, the first corner has vt 0 0
+    assert_eq!(floor.data().vertices[0].tex_coords, [0.0, 1.0]);
+    assert_eq!(
+        floor.texture_source(),
+        &TextureSource::FilePath("tests/fixtures/mesh/../skybox/px.png".to_string())
+    );
+
+    let pyramid = &meshes[1];
+    // every face refers to the same five corners
+    assert_eq!(pyramid.data().vertices.len(), 5);
+    assert_eq!(pyramid.data().indices.len(), 6 + 4 * 3);
+    assert_eq!(pyramid.texture_source(), &TextureSource::SolidColor([204, 26, 26, 255]));
+    assert!(!pyramid.is_transparent());
+}
+
+#[test]
+fn large_meshes_use_32_bit_indices() {
+    let small = MeshIndices::new(vec![0, 1, 2], u16::MAX as usize);
+    assert_eq!(small.format(), IndexFormat::Uint16);
+    let large = MeshIndices::new(vec![0, 1, 70_000], 70_001);
+    assert_eq!(large.format(), IndexFormat::Uint32);
+    assert_eq!(large.len(), 3);
+}
+
+#[test]
+fn default_pipeline_obj_scene() {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(3.0, 2.0, 5.0);
+    state.camera.yaw = -120.96;
+    state.camera.pitch = -18.93;
+    for mesh in Mesh::load_obj(SCENE).unwrap() {
+        state.renderables.push(Box::new(mesh));
+    }
+    assert_golden("default_pipeline_obj_scene", &mut state, Tolerance::default());
+}
+
+#[test]
+fn default_pipeline_large_and_non_indexed_meshes() {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(3.0, 2.0, 5.0);
+    state.camera.yaw = -120.96;
+    state.camera.pitch = -18.93;
+
+    // a 260x260 grid has more vertices than 16 bit indices can address
+    let size = 260;
+    let mut vertices = Vec::new();
+    for z in 0..size {
+        for x in 0..size {
+            let u = x as f32 / (size - 1) as f32;
+            let v = z as f32 / (size - 1) as f32;
+            vertices.push(Vertex {
+                position: [u * 4.0 - 2.0, -1.0, v * 4.0 - 2.0],
+                tex_coords: [u, v],
+            });
+        }
+    }
+    let mut indices = Vec::new();
+    for z in 0..size - 1 {
+        for x in 0..size - 1 {
+            let i = z * size + x;
+            indices.extend([i, i + size, i + 1, i + 1, i + size, i + size + 1]);
+        }
+    }
+    let grid = Mesh::new("grid".to_string(), vertices, indices, TextureSource::FilePath("assets/grass.jpg".to_string()));
+    assert_eq!(grid.get_index_format(), IndexFormat::Uint32);
+
+    // a triangle drawn without an index buffer
+    let triangle = Mesh::new_non_indexed(
+        "triangle".to_string(),
+        vec![
+            Vertex { position: [-1.0, -1.0, 0.0], tex_coords: [0.0, 0.0] },
+            Vertex { position: [1.0, -1.0, 0.0], tex_coords: [0.0, 0.0] },
+            Vertex { position: [0.0, 1.0, 0.0], tex_coords: [0.0, 0.0] },
+        ],
+        TextureSource::SolidColor([40, 160, 220, 255]),
+    );
+    assert_eq!(triangle.get_index_format(), IndexFormat::None);
+
+    state.renderables.push(Box::new(grid));
+    state.renderables.push(Box::new(triangle));
+    assert_golden("default_pipeline_large_and_non_indexed_meshes", &mut state, Tolerance::default());
+}
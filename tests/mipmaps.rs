@@ -0,0 +1,107 @@
+mod common;
+
+use common::{assert_golden, Tolerance};
+use learn_wgpu2::{
+    assets::AssetServer,
+    cube_texture::CubeTexture,
+    mipmaps::mip_level_count,
+    my_texture::{MyTexture, TextureSource},
+    renderables::mesh::Mesh,
+    state::State,
+    vertex::Vertex,
+};
+
+// a long floor that recedes from the camera, so the texture is minified more and more towards the horizon
+fn create_floor(texture_source: TextureSource) -> Mesh {
+    let vertices = vec![
+        Vertex { position: [-2.0, 0.0, 2.0], tex_coords: [0.0, 1.0] },
+        Vertex { position: [2.0, 0.0, 2.0], tex_coords: [1.0, 1.0] },
+        Vertex { position: [2.0, 0.0, -30.0], tex_coords: [1.0, 0.0] },
+        Vertex { position: [-2.0, 0.0, -30.0], tex_coords: [0.0, 0.0] },
+    ];
+    Mesh::new("floor".to_string(), vertices, vec![0, 1, 2, 2, 3, 0], texture_source)
+}
+
+fn create_state() -> State {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 1.0, 3.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = -15.0;
+    state
+}
+
+#[test]
+fn mip_chains_end_at_one_texel() {
+    assert_eq!(mip_level_count(1, 1), 1);
+    assert_eq!(mip_level_count(2, 1), 2);
+    assert_eq!(mip_level_count(256, 256), 9);
+    // the larger side decides, odd sizes are rounded down
+    assert_eq!(mip_level_count(1152, 1536), 11);
+    assert_eq!(mip_level_count(5, 300), 9);
+}
+
+#[test]
+fn broken_mip_chains_are_errors() {
+    let assets = AssetServer::default();
+    assert!(MyTexture::read(&TextureSource::MipChain(Vec::new()), &assets).is_err());
+    // the second level has to be half the size of the first one
+    let skipped = TextureSource::MipChain(vec!["tests/fixtures/mips/level0.png".to_string(), "tests/fixtures/mips/level2.png".to_string()]);
+    let error = MyTexture::read(&skipped, &assets).err().unwrap();
+    assert!(matches!(error, image::ImageError::Decoding(_)), "{:?}", error);
+}
+
+// writes the six faces of a cube into a directory of its own, with the given size for each face
+fn write_cube_faces(name: &str, sizes: [(u32, u32); 6]) -> String {
+    let directory = format!("{}/{}", env!("CARGO_TARGET_TMPDIR"), name);
+    std::fs::create_dir_all(&directory).unwrap();
+    for (face_name, (width, height)) in ["px", "nx", "py", "ny", "pz", "nz"].iter().zip(sizes) {
+        image::RgbaImage::new(width, height).save(format!("{}/{}.png", directory, face_name)).unwrap();
+    }
+    directory
+}
+
+#[test]
+fn broken_cube_faces_are_errors() {
+    assert!(CubeTexture::read(&write_cube_faces("square_cube", [(4, 4); 6])).is_ok());
+    let error = CubeTexture::read(&write_cube_faces("flat_cube", [(4, 2); 6])).err().unwrap();
+    assert!(matches!(error, image::ImageError::Decoding(_)), "{:?}", error);
+    let mut sizes = [(4, 4); 6];
+    sizes[3] = (8, 8);
+    let error = CubeTexture::read(&write_cube_faces("uneven_cube", sizes)).err().unwrap();
+    assert!(matches!(error, image::ImageError::Decoding(_)), "{:?}", error);
+    // a precomputed level has to be half the size of the one before
+    let directory = write_cube_faces("skipped_level_cube", [(4, 4); 6]);
+    write_cube_faces("skipped_level_cube/mip1", [(1, 1); 6]);
+    let error = CubeTexture::read(&directory).err().unwrap();
+    assert!(matches!(error, image::ImageError::Decoding(_)), "{:?}", error);
+}
+
+#[test]
+fn generated_mipmaps_checkerboard_floor() {
+    // checks of sixteen texels, the distance turns them into an even gray instead of moire
+    let size = 512;
+    let mut rgba = Vec::new();
+    for y in 0..size {
+        for x in 0..size {
+            let value = if (x / 16 + y / 16) % 2 == 0 { 255 } else { 0 };
+            rgba.extend_from_slice(&[value, value, value, 255]);
+        }
+    }
+    let mut state = create_state();
+    state.renderables.push(Box::new(create_floor(TextureSource::Pixels {
+        name: "checkerboard".to_string(),
+        width: size,
+        height: size,
+        rgba: std::sync::Arc::new(rgba),
+    })));
+    assert_golden("mipmaps_generated_checkerboard_floor", &mut state, Tolerance::default());
+}
+
+#[test]
+fn precomputed_mipmaps_floor() {
+    // every level has its own color, so the bands show which level is sampled at which distance
+    let levels = (0..9).map(|level| format!("tests/fixtures/mips/level{}.png", level)).collect();
+    let mut state = create_state();
+    state.renderables.push(Box::new(create_floor(TextureSource::MipChain(levels))));
+    assert_golden("mipmaps_precomputed_floor", &mut state, Tolerance::default());
+}
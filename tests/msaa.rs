@@ -0,0 +1,88 @@
+mod common;
+
+use common::{assert_golden, lock_context, Tolerance};
+use learn_wgpu2::{
+    renderables::{cube::Cube, skybox::Skybox},
+    state::State,
+    transform::Transform,
+};
+
+fn create_state() -> State {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(3.0, 2.0, 5.0);
+    state.camera.yaw = -120.96;
+    state.camera.pitch = -18.93;
+    state
+}
+
+// sets the shared context back to a single sample when it is dropped, also when an assertion of the test failed
+struct ResetSampleCount;
+
+impl Drop for ResetSampleCount {
+    fn drop(&mut self) {
+        if let Some(context) = lock_context().as_mut() {
+            context.set_sample_count(1).unwrap();
+        }
+    }
+}
+
+#[test]
+fn supported_sample_counts_come_from_the_adapter() {
+    let context = lock_context();
+    let Some(context) = context.as_ref() else {
+        return;
+    };
+    assert_eq!(context.supported_sample_counts()[0], 1);
+    assert!(context.supported_sample_counts().iter().all(|count| [1, 2, 4, 8].contains(count)));
+}
+
+#[test]
+fn every_supported_sample_count_renders() {
+    // dropped after the lock of the context
+    let _reset = ResetSampleCount;
+    let mut context = lock_context();
+    let Some(context) = context.as_mut() else {
+        return;
+    };
+    let mut state = create_state();
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+    let glass = Transform::from_position(cgmath::Vector3::new(0.5, 0.5, 1.5));
+    state.renderables.push(Box::new(Cube::new_transparent("assets/grass.jpg".to_string()).with_transform(glass)));
+    // creating the targets and pipelines of a count the device does not allow fails validation
+    for sample_count in context.supported_sample_counts().to_vec() {
+        context.set_sample_count(sample_count).unwrap();
+        context.request_screenshot();
+        context.render(&mut state).expect("Failed to render frame");
+        assert!(context.take_screenshot().is_some(), "Nothing was rendered with {} samples", sample_count);
+    }
+    assert!(context.set_sample_count(3).is_err());
+}
+
+#[test]
+fn msaa_4x_and_back() {
+    let reset = ResetSampleCount;
+    {
+        let mut context = lock_context();
+        let Some(context) = context.as_mut() else {
+            return;
+        };
+        if !context.supported_sample_counts().contains(&4) {
+            eprintln!("The adapter does not support 4x msaa, the test is skipped");
+            return;
+        }
+        context.set_sample_count(4).unwrap();
+    }
+    // the opaque and the transparent pass both render through the multisampled target
+    let mut state = create_state();
+    state.renderables.push(Box::new(Skybox::new("tests/fixtures/skybox".to_string())));
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+    let glass = Transform::from_position(cgmath::Vector3::new(0.5, 0.5, 1.5));
+    state.renderables.push(Box::new(Cube::new_transparent("assets/grass.jpg".to_string()).with_transform(glass)));
+    assert_golden("msaa_4x_cubes", &mut state, Tolerance::default());
+
+    // the pipelines are rebuilt for a single sample again
+    drop(reset);
+    let mut state = create_state();
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+    assert_golden("default_pipeline_cube", &mut state, Tolerance::default());
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use std::{any::TypeId, sync::Arc};
+
+use common::{assert_golden, floor, sphere, Tolerance};
+use learn_wgpu2::{
+    light::Light,
+    material::{AlphaMode, Material, MaterialTexture},
+    my_texture::TextureSource,
+    pipelines::pbr_pipeline::{MaterialUniform, PbrPipeline},
+    renderable::Renderable,
+    renderables::mesh::{LightingError, Mesh, MeshData, Shading},
+    state::State,
+    transform::Transform,
+    vertex::Vertex,
+};
+
+// stripes that tilt the normal left and right, the floor only looks ridged if the normal map is applied
+fn ridged_normal_map() -> TextureSource {
+    let (width, height) = (16, 16);
+    let rgba = (0..width * height)
+        .flat_map(|i| if (i % width) / 2 % 2 == 0 { [200, 128, 200, 255] } else { [56, 128, 200, 255] })
+        .collect();
+    TextureSource::Pixels {
+        name: "ridged normal map".to_string(),
+        width,
+        height,
+        rgba: Arc::new(rgba),
+    }
+}
+
+#[test]
+fn material_uniform_follows_the_material() {
+    let material = Material {
+        base_color_factor: [1.0, 0.5, 0.25, 1.0],
+        metallic_factor: 0.3,
+        roughness_factor: 0.7,
+        normal_scale: 0.5,
+        occlusion_strength: 0.8,
+        emissive_factor: [0.1, 0.2, 0.3],
+        alpha_mode: AlphaMode::Mask(0.4),
+        double_sided: true,
+        ..Default::default()
+    };
+    let uniform = MaterialUniform::new(&material);
+    assert_eq!(uniform.base_color_factor, [1.0, 0.5, 0.25, 1.0]);
+    assert_eq!(uniform.emissive_factor, [0.1, 0.2, 0.3, 0.0]);
+    assert_eq!(uniform.factors, [0.3, 0.7, 0.5, 0.8]);
+    assert_eq!(uniform.alpha, [0.4, 1.0, 0.0, 0.0]);
+    let opaque = MaterialUniform::new(&Material::default());
+    assert!(opaque.alpha[0] < 0.0);
+    assert_eq!(opaque.alpha[1], 0.0);
+}
+
+#[test]
+fn pbr_meshes_use_the_default_material() {
+    let mesh = Mesh::from_data(Arc::new(sphere(4, 8)), PbrPipeline::WHITE).with_pbr().unwrap();
+    assert_eq!(mesh.shading(), Shading::Pbr);
+    assert_eq!(mesh.material().map(|material| material.as_ref()), Some(&Material::default()));
+    assert_eq!(mesh.choose_pipeline(), TypeId::of::<PbrPipeline>());
+}
+
+#[test]
+fn pbr_needs_normals_and_an_opaque_mesh() {
+    let data = Arc::new(MeshData::new_non_indexed("triangle".to_string(), vec![Vertex { position: [0.0, 0.0, 0.0], tex_coords: [0.0, 0.0] }; 3]));
+    let error = Mesh::from_data(data.clone(), PbrPipeline::WHITE).with_pbr().err().unwrap();
+    assert!(matches!(error, LightingError::MissingAttributes(_)), "{:?}", error);
+    let error = Mesh::from_data(data, PbrPipeline::WHITE).with_lighting().err().unwrap();
+    assert!(matches!(error, LightingError::MissingAttributes(_)), "{:?}", error);
+
+    let blended = Material { alpha_mode: AlphaMode::Blend, ..Default::default() };
+    let glass = Mesh::from_data(Arc::new(sphere(4, 8)), PbrPipeline::WHITE).with_material(Arc::new(blended));
+    assert_eq!(glass.clone().with_pbr().err(), Some(LightingError::Transparent));
+    assert_eq!(glass.with_lighting().err(), Some(LightingError::Transparent));
+}
+
+#[test]
+fn pbr_pipeline_metallic_roughness() {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 1.0, 6.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = -10.0;
+    state.ambient_light = [0.03, 0.03, 0.03];
+    state.lights = vec![
+        Light::Directional {
+            direction: cgmath::Vector3::new(-0.5, -1.0, -1.0),
+            color: [1.0, 1.0, 1.0],
+            intensity: 3.0,
+            cast_shadows: false,
+        },
+        Light::Point {
+            position: cgmath::Vector3::new(0.0, 1.5, 2.0),
+            color: [1.0, 0.9, 0.8],
+            intensity: 6.0,
+            range: 8.0,
+        },
+    ];
+    let sphere = Arc::new(sphere(24, 48));
+    // dielectrics on the left, metals on the right, rougher towards the outside
+    let materials = [
+        (-2.4, 0.0, 0.9),
+        (-0.8, 0.0, 0.3),
+        (0.8, 1.0, 0.3),
+        (2.4, 1.0, 0.9),
+    ];
+    for (x, metallic, roughness) in materials {
+        let material = Material {
+            base_color_factor: if metallic > 0.5 { [1.0, 0.78, 0.34, 1.0] } else { [0.8, 0.1, 0.1, 1.0] },
+            metallic_factor: metallic,
+            roughness_factor: roughness,
+            ..Default::default()
+        };
+        let mesh = Mesh::from_data(sphere.clone(), PbrPipeline::WHITE)
+            .with_material(Arc::new(material))
+            .with_pbr().unwrap()
+            .with_transform(Transform {
+                position: cgmath::Vector3::new(x, 0.0, 0.0),
+                scale: cgmath::Vector3::new(0.7, 0.7, 0.7),
+                ..Default::default()
+            });
+        state.renderables.push(Box::new(mesh));
+    }
+    let floor_material = Material {
+        base_color_factor: [0.6, 0.6, 0.6, 1.0],
+        metallic_factor: 0.0,
+        roughness_factor: 0.6,
+        normal_texture: Some(MaterialTexture::new(ridged_normal_map())),
+        emissive_factor: [0.0, 0.02, 0.05],
+        ..Default::default()
+    };
+    let floor = Mesh::from_data(Arc::new(floor(4.0, 2.0)), PbrPipeline::WHITE).with_material(Arc::new(floor_material)).with_pbr().unwrap();
+    state.renderables.push(Box::new(floor));
+    assert_golden("pbr_pipeline_metallic_roughness", &mut state, Tolerance::default());
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{assert_golden, lock_context, sphere, Tolerance};
+use learn_wgpu2::{
+    light::Light,
+    my_texture::TextureSource,
+    pipelines::tonemap_pipeline::Tonemapping,
+    post_effects::{bloom::Bloom, color_grading::{ColorGrading, Lut3d}, fxaa::Fxaa, vignette::Vignette},
+    post_process::PostProcessStack,
+    renderables::mesh::Mesh,
+    state::State,
+    transform::Transform,
+};
+
+// three spheres under a light that is bright enough to bloom
+fn bright_scene() -> State {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 0.0, 5.0);
+    state.camera.yaw = -90.0;
+    state.ambient_light = [0.05, 0.05, 0.05];
+    state.lights = vec![Light::Directional {
+        direction: cgmath::Vector3::new(-1.0, -0.5, -1.0),
+        color: [1.0, 0.9, 0.7],
+        intensity: 6.0,
+        cast_shadows: false,
+    }];
+    state.tonemapping = Tonemapping::Aces;
+    let sphere = Arc::new(sphere(24, 48));
+    let colors = [[230, 60, 60, 255], [60, 230, 60, 255], [60, 60, 230, 255]];
+    for (i, color) in colors.into_iter().enumerate() {
+        let mesh = Mesh::from_data(sphere.clone(), TextureSource::SolidColor(color))
+            .with_lighting().unwrap()
+            .with_transform(Transform {
+                position: cgmath::Vector3::new(i as f32 * 2.0 - 2.0, 0.0, 0.0),
+                scale: cgmath::Vector3::new(0.8, 0.8, 0.8),
+                ..Default::default()
+            });
+        state.renderables.push(Box::new(mesh));
+    }
+    state
+}
+
+#[test]
+fn effects_can_be_found_and_edited_by_type() {
+    let mut stack = PostProcessStack::default();
+    stack.push(Bloom::default());
+    stack.push(Vignette::default());
+    stack.get_mut::<Vignette>().unwrap().intensity = 0.8;
+    assert_eq!(stack.get::<Vignette>().unwrap().intensity, 0.8);
+    assert!(stack.get::<Fxaa>().is_none());
+    stack.remove::<Bloom>();
+    assert_eq!(stack.effects.len(), 1);
+    assert!(stack.get::<Bloom>().is_none());
+}
+
+#[test]
+fn lut_encoding_covers_the_hdr_range() {
+    let color = [0.0, 0.18, 20.0];
+    let decoded = Lut3d::decode(Lut3d::encode(color));
+    assert!(decoded[0] < 1e-4);
+    assert!((decoded[1] - 0.18).abs() < 1e-5);
+    assert!((decoded[2] - 20.0).abs() < 1e-3);
+    assert_eq!(Lut3d::encode([1e6, 0.0, 0.0]), [1.0, 0.0, 0.0]);
+
+    // the lattice points of the identity lut store their own coordinates
+    let size = 5;
+    let lut = Lut3d::identity(size);
+    assert_eq!(lut.values.len(), 125);
+    let step = 1.0 / (size - 1) as f32;
+    for (i, value) in lut.values.iter().enumerate() {
+        let i = i as u32;
+        let expected = [(i % size) as f32 * step, (i / size % size) as f32 * step, (i / size / size) as f32 * step];
+        for (value, expected) in value.iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-5, "{:?} != {:?}", value, expected);
+        }
+    }
+}
+
+#[test]
+fn identity_grading_keeps_the_image() {
+    let mut context = lock_context();
+    let Some(context) = context.as_mut() else {
+        return;
+    };
+    let mut render = |state: &mut State| {
+        context.request_screenshot();
+        context.render(state).unwrap();
+        context.take_screenshot().unwrap()
+    };
+    let mut state = bright_scene();
+    let expected = render(&mut state);
+    // a single effect ends in the second target and is copied back
+    state.post_process.push(ColorGrading::new(Lut3d::identity(33)));
+    let actual = render(&mut state);
+    let max_difference = expected
+        .pixels()
+        .zip(actual.pixels())
+        .flat_map(|(e, a)| e.0.into_iter().zip(a.0).map(|(e, a)| e.abs_diff(a)))
+        .max()
+        .unwrap();
+    assert!(max_difference <= 2, "identity grading changed a channel by {}", max_difference);
+}
+
+#[test]
+fn post_process_bloom_fxaa_vignette_and_grading() {
+    let mut state = bright_scene();
+    state.post_process.push(Bloom::default());
+    state.post_process.push(Fxaa::default());
+    state.post_process.push(Vignette {
+        intensity: 0.7,
+        ..Default::default()
+    });
+    // warm the image up and push the shadows towards teal
+    let grade = |[r, g, b]: [f32; 3]| {
+        let shadows = 1.0 / (1.0 + 10.0 * (r + g + b));
+        [r * 1.15, g * (1.0 + 0.1 * shadows), b * (0.85 + 0.4 * shadows)]
+    };
+    state.post_process.push(ColorGrading::new(Lut3d::from_fn(17, grade)));
+    assert_golden("post_process_bloom_fxaa_vignette_and_grading", &mut state, Tolerance::default());
+}
@@ -0,0 +1,138 @@
+use std::{any::TypeId, collections::HashSet};
+
+use learn_wgpu2::{
+    my_render_pass::RenderPassBuilder,
+    render_graph::{RenderGraph, RenderGraphAttachments, SURFACE_COLOR},
+    render_passes::{
+        opauqe3d_render_pass::Opaque3DRenderPass, post_process_render_pass::PostProcessRenderPass, shadow_render_pass::ShadowRenderPass,
+        tonemap_render_pass::TonemapRenderPass,
+        transparent3d_render_pass::Transparent3DRenderPass, ui_render_pass::UiRenderPass,
+    },
+};
+
+// passes in these tests are only resolved, never executed
+macro_rules! test_pass {
+    ($name:ident, reads: [$($read:expr),*], writes: [$($write:expr),*], dependencies: [$($dependency:ty),*]) => {
+        struct $name;
+        impl RenderPassBuilder for $name {
+            fn reads(&self) -> Vec<&'static str> {
+                vec![$($read),*]
+            }
+            fn writes(&self) -> Vec<&'static str> {
+                vec![$($write),*]
+            }
+            fn dependencies(&self) -> Vec<TypeId> {
+                vec![$(TypeId::of::<$dependency>()),*]
+            }
+            fn create_render_pass<'a>(
+                &self,
+                _encoder: &'a mut wgpu::CommandEncoder,
+                _attachments: &RenderGraphAttachments<'a>,
+            ) -> wgpu::RenderPass<'a> {
+                unreachable!()
+            }
+        }
+    };
+}
+
+test_pass!(CompositePass, reads: [SURFACE_COLOR, "mask"], writes: [SURFACE_COLOR], dependencies: []);
+test_pass!(MaskPass, reads: [], writes: ["mask"], dependencies: []);
+test_pass!(UnusedOutputPass, reads: [], writes: ["unused"], dependencies: []);
+test_pass!(CyclePassA, reads: [], writes: ["a"], dependencies: [CyclePassB]);
+test_pass!(CyclePassB, reads: [], writes: ["b"], dependencies: [CyclePassA]);
+
+fn active(passes: &[TypeId]) -> HashSet<TypeId> {
+    passes.iter().copied().collect()
+}
+
+#[test]
+fn ui_runs_after_opaque() {
+    let mut render_graph = RenderGraph::default();
+    let shadow = TypeId::of::<ShadowRenderPass>();
+    let opaque = TypeId::of::<Opaque3DRenderPass>();
+    let post_process = TypeId::of::<PostProcessRenderPass>();
+    let tonemap = TypeId::of::<TonemapRenderPass>();
+    let ui = TypeId::of::<UiRenderPass>();
+    assert_eq!(render_graph.resolve(&active(&[ui, opaque])), vec![shadow, opaque, post_process, tonemap, ui]);
+}
+
+#[test]
+fn post_processing_and_tonemap_only_run_with_3d_passes() {
+    let mut render_graph = RenderGraph::default();
+    let transparent = TypeId::of::<Transparent3DRenderPass>();
+    let post_process = TypeId::of::<PostProcessRenderPass>();
+    let tonemap = TypeId::of::<TonemapRenderPass>();
+    let ui = TypeId::of::<UiRenderPass>();
+    assert_eq!(render_graph.resolve(&active(&[ui])), vec![ui]);
+    assert_eq!(render_graph.resolve(&active(&[transparent])), vec![transparent, post_process, tonemap]);
+}
+
+#[test]
+fn transparent_runs_between_opaque_and_ui() {
+    let mut render_graph = RenderGraph::default();
+    let shadow = TypeId::of::<ShadowRenderPass>();
+    let opaque = TypeId::of::<Opaque3DRenderPass>();
+    let transparent = TypeId::of::<Transparent3DRenderPass>();
+    let post_process = TypeId::of::<PostProcessRenderPass>();
+    let tonemap = TypeId::of::<TonemapRenderPass>();
+    let ui = TypeId::of::<UiRenderPass>();
+    assert_eq!(
+        render_graph.resolve(&active(&[ui, transparent, opaque])),
+        vec![shadow, opaque, transparent, post_process, tonemap, ui]
+    );
+}
+
+#[test]
+fn reads_order_passes_regardless_of_registration() {
+    let mut render_graph = RenderGraph::default();
+    render_graph.add_pass(CompositePass);
+    render_graph.add_pass(MaskPass);
+    let shadow = TypeId::of::<ShadowRenderPass>();
+    let opaque = TypeId::of::<Opaque3DRenderPass>();
+    let post_process = TypeId::of::<PostProcessRenderPass>();
+    let tonemap = TypeId::of::<TonemapRenderPass>();
+    let composite = TypeId::of::<CompositePass>();
+    let mask = TypeId::of::<MaskPass>();
+    let order = render_graph.resolve(&active(&[composite, mask, opaque]));
+    assert_eq!(order, vec![shadow, opaque, post_process, tonemap, mask, composite]);
+}
+
+#[test]
+fn unused_passes_are_dropped() {
+    let mut render_graph = RenderGraph::default();
+    render_graph.add_pass(CompositePass);
+    render_graph.add_pass(MaskPass);
+    render_graph.add_pass(UnusedOutputPass);
+    let shadow = TypeId::of::<ShadowRenderPass>();
+    let opaque = TypeId::of::<Opaque3DRenderPass>();
+    let post_process = TypeId::of::<PostProcessRenderPass>();
+    let tonemap = TypeId::of::<TonemapRenderPass>();
+    let mask = TypeId::of::<MaskPass>();
+    let unused = TypeId::of::<UnusedOutputPass>();
+    // mask is only read by the composite pass, which has nothing to draw
+    assert_eq!(render_graph.resolve(&active(&[opaque, mask, unused])), vec![shadow, opaque, post_process, tonemap]);
+}
+
+#[test]
+fn shadows_are_only_rendered_for_the_opaque_pass() {
+    let mut render_graph = RenderGraph::default();
+    let shadow = TypeId::of::<ShadowRenderPass>();
+    let opaque = TypeId::of::<Opaque3DRenderPass>();
+    let transparent = TypeId::of::<Transparent3DRenderPass>();
+    let post_process = TypeId::of::<PostProcessRenderPass>();
+    let tonemap = TypeId::of::<TonemapRenderPass>();
+    // the shadow pass draws the renderables of the other passes, it has none of its own
+    assert_eq!(render_graph.resolve(&active(&[opaque])), vec![shadow, opaque, post_process, tonemap]);
+    // nothing samples the shadow map without the opaque pass
+    assert_eq!(render_graph.resolve(&active(&[transparent])), vec![transparent, post_process, tonemap]);
+    assert!(render_graph.resolve(&HashSet::new()).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "cycle")]
+fn cycles_are_rejected() {
+    let mut render_graph = RenderGraph::default();
+    render_graph.add_pass(CyclePassA);
+    render_graph.add_pass(CyclePassB);
+    render_graph.resolve(&HashSet::new());
+}
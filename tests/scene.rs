@@ -0,0 +1,114 @@
+mod common;
+
+use cgmath::InnerSpace;
+use common::{assert_golden, Tolerance};
+use learn_wgpu2::{
+    material::AlphaMode,
+    my_texture::TextureSource,
+    renderable::{IndexFormat, Renderable},
+    renderables::mesh::Mesh,
+    scene::{Projection, Scene},
+    state::State,
+    transform::Transform,
+    vertex::Vertex,
+};
+
+#[test]
+fn gltf_hierarchy_and_materials() {
+    let (scene, meshes) = Scene::load_gltf("tests/fixtures/gltf/scene.gltf").unwrap();
+    assert_eq!(scene.roots, vec![0, 3]);
+    assert_eq!(scene.nodes[0].children, vec![1, 2]);
+    // the floor inherits the translation of the root node
+    let floor = &scene.nodes[1];
+    assert_eq!(floor.renderables.len(), 1);
+    assert!((floor.world_transform.position - cgmath::Vector3::new(0.0, 0.0, -0.5)).magnitude() < 1e-5);
+    assert_eq!(meshes[floor.renderables[0]].node_matrix(), floor.world_matrix);
+
+    // one mesh per primitive, the base is not indexed
+    let pyramid = &scene.nodes[2];
+    assert_eq!(pyramid.renderables.len(), 2);
+    let sides = &meshes[pyramid.renderables[0]];
+    let base = &meshes[pyramid.renderables[1]];
+    assert_eq!(sides.data().indices.len(), 12);
+    assert_eq!(base.data().vertices.len(), 6);
+    assert_eq!(base.get_index_format(), IndexFormat::None);
+    assert_eq!(base.get_num_vertices(), 6);
+    assert!((pyramid.world_transform.scale - cgmath::Vector3::new(1.0, 1.2, 1.0)).magnitude() < 1e-5);
+
+    assert_eq!(scene.materials.len(), 3);
+    let ground = &scene.materials[0];
+    assert_eq!(ground.metallic_factor, 0.0);
+    let ground_texture = ground.base_color_texture.as_ref().unwrap();
+    assert_eq!(
+        ground_texture.source,
+        TextureSource::FilePath("tests/fixtures/gltf/../skybox/px.png".to_string())
+    );
+    // the texture has no sampler, gltf wraps by default and leaves the filters to the renderer
+    assert_eq!(ground_texture.options.address_mode_u, wgpu::AddressMode::Repeat);
+    assert_eq!(ground_texture.options.address_mode_v, wgpu::AddressMode::Repeat);
+    assert_eq!(ground_texture.options.filtering, None);
+    let checker = &scene.materials[1];
+    assert!(matches!(
+        checker.base_color_texture.as_ref().map(|texture| &texture.source),
+        Some(TextureSource::Pixels { width: 2, height: 2, .. })
+    ));
+    assert_eq!(checker.roughness_factor, 0.5);
+    let blue = &scene.materials[2];
+    assert_eq!(blue.base_color_texture, None);
+    assert_eq!(blue.emissive_factor, [0.0, 0.0, 0.2]);
+    assert_eq!(blue.alpha_mode, AlphaMode::Opaque);
+    assert!(blue.double_sided);
+    assert_eq!(base.texture_source(), &TextureSource::SolidColor([26, 51, 230, 255]));
+
+    assert_eq!(scene.cameras.len(), 1);
+    assert_eq!(scene.cameras[0].node, 3);
+    assert!(matches!(scene.cameras[0].projection, Projection::Perspective { zfar: Some(100.0), .. }));
+}
+
+#[test]
+fn node_matrices_keep_shear_and_zero_scale() {
+    // a child rotated under a parent that is scaled along x only is sheared, no transform can place it
+    let parent = cgmath::Matrix4::from_translation(cgmath::Vector3::new(1.0, 2.0, 3.0)) * cgmath::Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0);
+    let child = cgmath::Matrix4::from_angle_z(cgmath::Deg(45.0));
+    let world_matrix = parent * child;
+    let vertices = vec![Vertex { position: [0.0, 0.0, 0.0], tex_coords: [0.0, 0.0] }; 3];
+    let mesh = Mesh::new_non_indexed("triangle".to_string(), vertices, TextureSource::SolidColor([255, 255, 255, 255]))
+        .with_node_matrix(world_matrix);
+    assert_eq!(mesh.node_matrix(), world_matrix);
+    assert!((mesh.get_position() - cgmath::Point3::new(1.0, 2.0, 3.0)).magnitude() < 1e-5);
+
+    // an axis scaled to zero keeps a valid rotation instead of dividing by zero
+    let flat = Transform::from_matrix(cgmath::Matrix4::from_angle_y(cgmath::Deg(30.0)) * cgmath::Matrix4::from_nonuniform_scale(1.0, 0.0, 1.0));
+    assert_eq!(flat.scale.y, 0.0);
+    let expected = cgmath::Quaternion::from(cgmath::Euler::new(cgmath::Deg(0.0), cgmath::Deg(30.0), cgmath::Deg(0.0)));
+    assert!((flat.rotation - expected).magnitude() < 1e-5 || (flat.rotation + expected).magnitude() < 1e-5, "{:?}", flat.rotation);
+    let nothing = Transform::from_matrix(cgmath::Matrix4::from_scale(0.0));
+    assert_eq!(nothing.rotation, cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0));
+}
+
+#[test]
+fn glb_matches_gltf() {
+    let (gltf_scene, gltf_meshes) = Scene::load_gltf("tests/fixtures/gltf/scene.gltf").unwrap();
+    let (glb_scene, glb_meshes) = Scene::load_gltf("tests/fixtures/gltf/scene.glb").unwrap();
+    assert_eq!(gltf_scene.nodes.len(), glb_scene.nodes.len());
+    assert_eq!(gltf_meshes.len(), glb_meshes.len());
+    // the embedded image is decoded the same way from a data uri and from the binary chunk
+    let pixels = |scene: &Scene| match scene.materials[1].base_color_texture.as_ref().map(|texture| &texture.source) {
+        Some(TextureSource::Pixels { rgba, .. }) => rgba.clone(),
+        _ => panic!("expected an embedded image"),
+    };
+    assert_eq!(pixels(&gltf_scene), pixels(&glb_scene));
+}
+
+#[test]
+fn gltf_camera_is_applied() {
+    let mut state = State::default();
+    state.load_gltf("tests/fixtures/gltf/scene.gltf").unwrap();
+    assert_eq!(state.renderables.len(), 3);
+    // the camera node sits where the other golden image tests put the camera
+    assert!((state.camera.pos - cgmath::Point3::new(3.0, 2.0, 5.0)).magnitude() < 1e-4);
+    assert!((state.camera.yaw - -120.96).abs() < 0.01);
+    assert!((state.camera.pitch - -18.93).abs() < 0.01);
+    assert!((state.camera.fovy - 45.0).abs() < 1e-4);
+    assert_golden("default_pipeline_gltf_scene", &mut state, Tolerance::default());
+}
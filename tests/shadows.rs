@@ -0,0 +1,186 @@
+mod common;
+
+use std::sync::Arc;
+
+use cgmath::SquareMatrix;
+use common::{assert_golden, Tolerance};
+use learn_wgpu2::{
+    camera::Camera,
+    light::Light,
+    material::Material,
+    my_texture::TextureSource,
+    renderables::mesh::{Mesh, MeshData},
+    shadow::{ShadowLayout, ShadowSettings, MAX_CASCADES, MAX_SHADOW_LAYERS},
+    state::State,
+    transform::Transform,
+    vertex::{MeshVertex, Vertex, VertexAttribute},
+};
+
+fn sun(cast_shadows: bool) -> Light {
+    Light::Directional {
+        direction: cgmath::Vector3::new(-1.0, -2.0, -0.5),
+        color: [1.0, 1.0, 0.95],
+        intensity: 0.5,
+        cast_shadows,
+    }
+}
+
+fn spot(cast_shadows: bool) -> Light {
+    Light::Spot {
+        position: cgmath::Vector3::new(2.0, 3.0, 2.0),
+        direction: cgmath::Vector3::new(-1.0, -1.5, -1.0),
+        color: [0.3, 0.5, 1.0],
+        intensity: 8.0,
+        range: 10.0,
+        inner_angle: 20.0,
+        outer_angle: 30.0,
+        cast_shadows,
+    }
+}
+
+fn quad(name: &str, size: f32, y: f32) -> MeshData {
+    let vertex = |x: f32, z: f32| MeshVertex {
+        position: [x, y, z],
+        normal: [0.0, 1.0, 0.0],
+        ..Default::default()
+    };
+    let vertices = vec![vertex(-size, size), vertex(size, size), vertex(size, -size), vertex(-size, -size)];
+    MeshData::from_mesh_vertices(name.to_string(), vertices, Vertex::LAYOUT.with(VertexAttribute::Normal), Some(vec![0, 1, 2, 2, 3, 0]))
+}
+
+// a box with flat normals, from -0.5 to 0.5
+fn cuboid() -> MeshData {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let axes = [
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+    ];
+    for (normal, tangent) in axes {
+        for sign in [1.0f32, -1.0] {
+            let n = cgmath::Vector3::<f32>::from(normal) * sign;
+            let u = cgmath::Vector3::<f32>::from(tangent);
+            let v = n.cross(u);
+            let first = vertices.len() as u32;
+            for (a, b) in [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)] {
+                let position = n * 0.5 + u * a + v * b;
+                vertices.push(MeshVertex {
+                    position: position.into(),
+                    normal: n.into(),
+                    ..Default::default()
+                });
+            }
+            indices.extend([first, first + 1, first + 2, first + 2, first + 3, first]);
+        }
+    }
+    MeshData::from_mesh_vertices("cuboid".to_string(), vertices, Vertex::LAYOUT.with(VertexAttribute::Normal), Some(indices))
+}
+
+#[test]
+fn shadow_layers_are_assigned_in_light_order() {
+    let camera = Camera::default();
+    let settings = ShadowSettings::default();
+    let point = Light::Point {
+        position: cgmath::Vector3::new(0.0, 1.0, 0.0),
+        color: [1.0, 1.0, 1.0],
+        intensity: 1.0,
+        range: 5.0,
+    };
+    let lights = [sun(true), point, spot(false), spot(true)];
+    let layout = ShadowLayout::new(&lights, &camera, 1.0, &settings);
+    assert_eq!(layout.light_layers, vec![Some((0, settings.cascades)), None, None, Some((settings.cascades, 1))]);
+    assert_eq!(layout.view_projections.len(), settings.cascades as usize + 1);
+}
+
+#[test]
+fn lights_without_free_layers_have_no_shadows() {
+    let lights = vec![spot(true); MAX_SHADOW_LAYERS + 2];
+    let layout = ShadowLayout::new(&lights, &Camera::default(), 1.0, &ShadowSettings::default());
+    assert_eq!(layout.view_projections.len(), MAX_SHADOW_LAYERS);
+    assert!(layout.light_layers[..MAX_SHADOW_LAYERS].iter().all(Option::is_some));
+    assert!(layout.light_layers[MAX_SHADOW_LAYERS..].iter().all(Option::is_none));
+    assert_eq!(layout.lights_out_of_layers, 2);
+}
+
+#[test]
+fn cascade_counts_are_clamped() {
+    for (cascades, expected) in [(0, 1), (2, 2), (MAX_CASCADES as u32 + 3, MAX_CASCADES as u32)] {
+        let settings = ShadowSettings { cascades, ..Default::default() };
+        let layout = ShadowLayout::new(&[sun(true)], &Camera::default(), 1.0, &settings);
+        assert_eq!(layout.light_layers, vec![Some((0, expected))]);
+    }
+}
+
+#[test]
+fn cascades_cover_the_view_up_to_the_shadow_distance() {
+    let camera = Camera::default();
+    let settings = ShadowSettings {
+        cascades: 4,
+        distance: 40.0,
+        ..Default::default()
+    };
+    let layout = ShadowLayout::new(&[sun(true)], &camera, 1.5, &settings);
+    let splits = layout.cascade_splits;
+    assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!((splits[3] - 40.0).abs() < 1e-3);
+
+    // points along the view direction land inside the cascade that covers their depth
+    let inverse_view = camera.build_view_matrix().invert().unwrap();
+    let mut near = camera.znear;
+    for (cascade, &far) in splits.iter().enumerate() {
+        for t in [0.05, 0.5, 0.95] {
+            let depth = near + (far - near) * t;
+            let world = inverse_view * cgmath::Vector4::new(0.0, 0.0, -depth, 1.0);
+            let clip = layout.view_projections[cascade] * world;
+            let ndc = clip.truncate() / clip.w;
+            assert!(ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0, "cascade {} misses depth {}", cascade, depth);
+            assert!((0.0..=1.0).contains(&ndc.z), "cascade {} clips depth {}", cascade, depth);
+        }
+        near = far;
+    }
+}
+
+#[test]
+fn lit_pipelines_with_shadows() {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 4.0, 7.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = -30.0;
+    state.ambient_light = [0.1, 0.1, 0.1];
+    state.lights = vec![sun(true), spot(true)];
+    let white = TextureSource::SolidColor([255, 255, 255, 255]);
+    let floor = Mesh::from_data(Arc::new(quad("floor", 5.0, 0.0)), white.clone()).with_lighting().unwrap();
+    state.renderables.push(Box::new(floor));
+    // boxes standing on the floor and a floating plate in front of them
+    let cuboid = Arc::new(cuboid());
+    let lit_box = Mesh::from_data(cuboid.clone(), white.clone())
+        .with_lighting().unwrap()
+        .with_transform(Transform {
+            position: cgmath::Vector3::new(-1.5, 0.5, 0.0),
+            ..Default::default()
+        });
+    state.renderables.push(Box::new(lit_box));
+    let pbr_box = Mesh::from_data(cuboid, white.clone())
+        .with_material(Arc::new(Material {
+            base_color_factor: [0.9, 0.6, 0.3, 1.0],
+            metallic_factor: 0.0,
+            roughness_factor: 0.5,
+            ..Default::default()
+        }))
+        .with_pbr().unwrap()
+        .with_transform(Transform {
+            position: cgmath::Vector3::new(1.0, 0.75, 0.5),
+            scale: cgmath::Vector3::new(1.0, 1.5, 1.0),
+            ..Default::default()
+        });
+    state.renderables.push(Box::new(pbr_box));
+    let plate = Mesh::from_data(Arc::new(quad("plate", 0.5, 0.0)), white)
+        .with_lighting().unwrap()
+        .with_transform(Transform {
+            position: cgmath::Vector3::new(0.5, 1.2, 2.5),
+            ..Default::default()
+        });
+    state.renderables.push(Box::new(plate));
+    assert_golden("lit_pipelines_with_shadows", &mut state, Tolerance::default());
+}
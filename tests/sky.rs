@@ -0,0 +1,122 @@
+mod common;
+
+use std::sync::Arc;
+
+use cgmath::InnerSpace;
+use common::{assert_golden, sphere, Tolerance};
+use learn_wgpu2::{
+    light::Light,
+    pipelines::tonemap_pipeline::Tonemapping,
+    renderables::{
+        mesh::Mesh,
+        procedural_sky::ProceduralSky,
+    },
+    sky::TimeOfDay,
+    state::State,
+    transform::Transform,
+};
+
+fn assert_direction(actual: cgmath::Vector3<f32>, expected: cgmath::Vector3<f32>) {
+    assert!((actual - expected).magnitude() < 1e-5, "{:?} is not {:?}", actual, expected);
+}
+
+#[test]
+fn the_sun_crosses_the_sky_in_a_day() {
+    let elevation = 60.0f32.to_radians();
+    assert_direction(TimeOfDay::new(6.0).sun_direction(), cgmath::Vector3::unit_x());
+    assert_direction(TimeOfDay::new(12.0).sun_direction(), cgmath::Vector3::new(0.0, elevation.sin(), -elevation.cos()));
+    assert_direction(TimeOfDay::new(18.0).sun_direction(), -cgmath::Vector3::unit_x());
+    assert!(TimeOfDay::new(0.0).sun_direction().y < -0.8);
+
+    let mut time_of_day = TimeOfDay::new(23.0).with_speed(0.5);
+    time_of_day.advance(4.0);
+    assert!((time_of_day.hours - 1.0).abs() < 1e-5);
+    time_of_day.advance(-4.0);
+    assert!((time_of_day.hours - 23.0).abs() < 1e-5);
+
+    // the light of the setting sun lost more blue than red on its way through the air
+    let noon = TimeOfDay::new(12.0).sun_transmittance();
+    let evening = TimeOfDay::new(17.5).sun_transmittance();
+    assert!(noon.iter().all(|channel| *channel > 0.7), "{:?}", noon);
+    assert!(evening[0] < noon[0] && evening[2] / evening[0] < noon[2] / noon[0], "{:?}", evening);
+}
+
+#[test]
+fn the_first_directional_light_follows_the_sun() {
+    let mut state = State {
+        time_of_day: Some(TimeOfDay::new(9.0).with_speed(1.0)),
+        ..Default::default()
+    };
+    state.lights.push(Light::Point {
+        position: cgmath::Vector3::new(0.0, 1.0, 0.0),
+        color: [1.0, 1.0, 1.0],
+        intensity: 1.0,
+        range: 5.0,
+    });
+    // the sun is added when there is no directional light
+    state.update_time_of_day(1.0);
+    assert_eq!(state.lights.len(), 2);
+    let sun_direction = TimeOfDay::new(10.0).sun_direction();
+    assert_direction(state.sun_direction().unwrap(), sun_direction);
+    let Light::Directional { direction, intensity, .. } = state.lights[1] else {
+        panic!("{:?} is not the sun", state.lights[1]);
+    };
+    assert_direction(direction, -sun_direction);
+    assert!((intensity - 3.0).abs() < 1e-5);
+
+    // at night it is dark
+    state.update_time_of_day(12.0);
+    assert_eq!(state.lights.len(), 2);
+    let Light::Directional { intensity, .. } = state.lights[1] else {
+        unreachable!();
+    };
+    assert_eq!(intensity, 0.0);
+
+    // without a time of day the sky takes the sun from the light
+    state.time_of_day = None;
+    state.lights[1] = Light::Directional {
+        direction: cgmath::Vector3::new(0.0, -2.0, 0.0),
+        color: [1.0, 1.0, 1.0],
+        intensity: 1.0,
+        cast_shadows: false,
+    };
+    assert_direction(state.sun_direction().unwrap(), cgmath::Vector3::unit_y());
+}
+
+fn sky_scene(hours: f32) -> State {
+    let mut state = State {
+        time_of_day: Some(TimeOfDay::new(hours)),
+        tonemapping: Tonemapping::Aces,
+        ambient_light: [0.02, 0.02, 0.03],
+        ..Default::default()
+    };
+    state.update_time_of_day(0.0);
+    state.renderables.push(Box::new(ProceduralSky::new()));
+    let white = learn_wgpu2::my_texture::TextureSource::SolidColor([255, 255, 255, 255]);
+    let sphere = Mesh::from_data(Arc::new(sphere(24, 48)), white).with_lighting().unwrap().with_transform(Transform {
+        position: cgmath::Vector3::new(0.0, 0.0, -4.0),
+        ..Default::default()
+    });
+    state.renderables.push(Box::new(sphere));
+    state
+}
+
+#[test]
+fn procedural_sky_at_noon() {
+    // blue above, paler towards the horizon and the ground below it. The sphere is lit from above
+    let mut state = sky_scene(12.0);
+    state.camera.pos = cgmath::Point3::new(0.0, 0.0, 0.0);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = 10.0;
+    assert_golden("procedural_sky_at_noon", &mut state, Tolerance::default());
+}
+
+#[test]
+fn procedural_sky_at_sunset() {
+    // looking west to the setting sun, the sky glows around it and the ground and the sphere in front of it are dark
+    let mut state = sky_scene(17.8);
+    state.camera.pos = cgmath::Point3::new(4.0, 0.0, -2.5);
+    state.camera.yaw = 195.0;
+    state.camera.pitch = 8.0;
+    assert_golden("procedural_sky_at_sunset", &mut state, Tolerance::default());
+}
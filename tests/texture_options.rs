@@ -0,0 +1,129 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{assert_golden, lock_context, Tolerance};
+use learn_wgpu2::{
+    assets::LoadState,
+    my_texture::{ColorSpace, MyTexture, TextureFiltering, TextureOptions, TextureSource},
+    renderables::mesh::Mesh,
+    state::State,
+    transform::Transform,
+    vertex::Vertex,
+};
+
+#[test]
+fn color_space_picks_the_format_variant() {
+    let options = TextureOptions::default();
+    assert_eq!(options.texture_format(), wgpu::TextureFormat::Rgba8UnormSrgb);
+    assert_eq!(options.with_color_space(ColorSpace::Linear).texture_format(), wgpu::TextureFormat::Rgba8Unorm);
+    let bgra = options.with_format(wgpu::TextureFormat::Bgra8UnormSrgb).with_color_space(ColorSpace::Linear);
+    assert_eq!(bgra.texture_format(), wgpu::TextureFormat::Bgra8Unorm);
+    // float formats have no srgb variant
+    let float = options.with_format(wgpu::TextureFormat::Rgba16Float);
+    assert_eq!(float.texture_format(), wgpu::TextureFormat::Rgba16Float);
+}
+
+#[test]
+fn pixels_are_converted_to_the_format() {
+    let rgba = [255, 128, 0, 64];
+    let options = TextureOptions::default().with_color_space(ColorSpace::Linear);
+    assert_eq!(options.convert_pixels(&rgba).unwrap(), vec![255, 128, 0, 64]);
+    assert_eq!(options.with_format(wgpu::TextureFormat::Bgra8Unorm).convert_pixels(&rgba).unwrap(), vec![0, 128, 255, 64]);
+    assert_eq!(options.with_format(wgpu::TextureFormat::R8Unorm).convert_pixels(&rgba).unwrap(), vec![255]);
+    assert_eq!(options.with_format(wgpu::TextureFormat::Rg8Unorm).convert_pixels(&rgba).unwrap(), vec![255, 128]);
+
+    // srgb values are decoded on the cpu for float formats, alpha is kept as it is
+    let float = TextureOptions::default().with_format(wgpu::TextureFormat::Rgba16Float).convert_pixels(&rgba).unwrap();
+    let values: Vec<f32> = float
+        .chunks_exact(2)
+        .map(|bytes| half::f16::from_le_bytes([bytes[0], bytes[1]]).to_f32())
+        .collect();
+    assert!((values[0] - 1.0).abs() < 1e-3);
+    assert!((values[1] - 0.2158).abs() < 1e-3);
+    assert_eq!(values[2], 0.0);
+    assert!((values[3] - 64.0 / 255.0).abs() < 1e-3);
+}
+
+#[test]
+fn single_channel_textures_need_a_linear_color_space() {
+    let error = TextureOptions::default().with_format(wgpu::TextureFormat::R8Unorm).convert_pixels(&[0, 0, 0, 0]).unwrap_err();
+    assert!(error.to_string().contains("has no srgb variant"), "{}", error);
+    let depth = TextureOptions::default().with_format(wgpu::TextureFormat::Depth32Float).convert_pixels(&[0, 0, 0, 0]);
+    assert!(depth.is_err());
+}
+
+#[test]
+fn custom_filtering_is_clamped_and_validated() {
+    use wgpu::FilterMode::{Linear, Nearest};
+    let custom = |filter, anisotropy_clamp| TextureFiltering::Custom {
+        mag_filter: filter,
+        min_filter: Linear,
+        mipmap_filter: Linear,
+        anisotropy_clamp,
+    };
+    assert_eq!(custom(Nearest, 0).validate().unwrap(), (Nearest, Linear, Linear, 1));
+    assert_eq!(custom(Linear, 64).validate().unwrap(), (Linear, Linear, Linear, 16));
+    assert!(custom(Nearest, 4).validate().is_err());
+}
+
+#[test]
+fn textures_with_invalid_options_fail_to_load() {
+    let mut context = lock_context();
+    let Some(context) = context.as_mut() else {
+        return;
+    };
+    let source = TextureSource::SolidColor([10, 20, 30, 255]);
+    let srgb_r8 = TextureOptions::default().with_format(wgpu::TextureFormat::R8Unorm);
+    let nearest_anisotropic = TextureOptions::default().with_filtering(TextureFiltering::Custom {
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        anisotropy_clamp: 8,
+    });
+    for options in [srgb_r8, nearest_anisotropic] {
+        let texture = context.load_asset::<MyTexture>((source.clone(), options));
+        assert!(matches!(texture.load_state(), LoadState::Failed(_)), "{:?}", options);
+    }
+}
+
+#[test]
+fn default_pipeline_clamped_and_repeated_quads() {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 0.0, 5.5);
+    state.camera.yaw = -90.0;
+    state.camera.pitch = 0.0;
+    // a 4x4 checker that is stretched over three times its size
+    let mut rgba = Vec::new();
+    for y in 0..4 {
+        for x in 0..4 {
+            let color = if (x + y) % 2 == 0 { [230, 60, 40, 255] } else { [240, 240, 240, 255] };
+            rgba.extend_from_slice(&color);
+        }
+    }
+    let source = TextureSource::Pixels {
+        name: "checker".to_string(),
+        width: 4,
+        height: 4,
+        rgba: Arc::new(rgba),
+    };
+    let quad = |x: f32, options: TextureOptions| {
+        let vertices = vec![
+            Vertex { position: [-0.9, -0.9, 0.0], tex_coords: [0.0, 3.0] },
+            Vertex { position: [0.9, -0.9, 0.0], tex_coords: [3.0, 3.0] },
+            Vertex { position: [0.9, 0.9, 0.0], tex_coords: [3.0, 0.0] },
+            Vertex { position: [-0.9, 0.9, 0.0], tex_coords: [0.0, 0.0] },
+        ];
+        Mesh::new("quad".to_string(), vertices, vec![0, 1, 2, 2, 3, 0], source.clone())
+            .with_texture_options(options)
+            .with_transform(Transform::from_position(cgmath::Vector3::new(x, 0.0, 0.0)))
+    };
+    // the same source is loaded once for every set of options
+    let nearest = TextureOptions::default().with_filtering(TextureFiltering::Nearest);
+    state.renderables.push(Box::new(quad(-1.0, nearest)));
+    state.renderables.push(Box::new(quad(1.0, nearest.with_address_mode(wgpu::AddressMode::Repeat))));
+    let stats = assert_golden("default_pipeline_clamped_and_repeated_quads", &mut state, Tolerance::default());
+    if let Some(stats) = stats {
+        assert_eq!(stats.draws, 2);
+    }
+}
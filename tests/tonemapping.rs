@@ -0,0 +1,65 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::{assert_golden, sphere, Tolerance};
+use learn_wgpu2::{
+    light::Light,
+    my_texture::TextureSource,
+    pipelines::tonemap_pipeline::{TonemapUniform, Tonemapping},
+    renderables::mesh::Mesh,
+    state::State,
+    transform::Transform,
+};
+
+#[test]
+fn tonemapping_cycles_through_every_operator() {
+    let mut tonemapping = Tonemapping::default();
+    assert_eq!(tonemapping, Tonemapping::None);
+    let mut seen = Vec::new();
+    for _ in 0..Tonemapping::ALL.len() {
+        seen.push(tonemapping);
+        tonemapping = tonemapping.next();
+    }
+    assert_eq!(seen, Tonemapping::ALL);
+    assert_eq!(tonemapping, Tonemapping::None);
+}
+
+#[test]
+fn tonemap_uniform_packs_exposure_and_operator() {
+    // the indices are the constants in tonemap.wgsl
+    assert_eq!(TonemapUniform::new(Tonemapping::None, 1.0).settings, [1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(TonemapUniform::new(Tonemapping::Reinhard, 2.0).settings, [2.0, 1.0, 0.0, 0.0]);
+    assert_eq!(TonemapUniform::new(Tonemapping::Aces, 0.5).settings, [0.5, 2.0, 0.0, 0.0]);
+    assert_eq!(TonemapUniform::new(Tonemapping::AgX, 1.0).settings, [1.0, 3.0, 0.0, 0.0]);
+}
+
+#[test]
+fn tonemap_pipeline_aces_overexposed_lights() {
+    let mut state = State::default();
+    state.camera.pos = cgmath::Point3::new(0.0, 0.0, 5.0);
+    state.camera.yaw = -90.0;
+    state.ambient_light = [0.05, 0.05, 0.05];
+    // bright enough that clamping would burn the lit sides of the spheres to flat colors
+    state.lights = vec![Light::Directional {
+        direction: cgmath::Vector3::new(-1.0, -0.5, -1.0),
+        color: [1.0, 0.9, 0.7],
+        intensity: 6.0,
+        cast_shadows: false,
+    }];
+    state.tonemapping = Tonemapping::Aces;
+    state.exposure = 0.8;
+    let sphere = Arc::new(sphere(24, 48));
+    let colors = [[230, 60, 60, 255], [60, 230, 60, 255], [60, 60, 230, 255]];
+    for (i, color) in colors.into_iter().enumerate() {
+        let mesh = Mesh::from_data(sphere.clone(), TextureSource::SolidColor(color))
+            .with_lighting().unwrap()
+            .with_transform(Transform {
+                position: cgmath::Vector3::new(i as f32 * 2.0 - 2.0, 0.0, 0.0),
+                scale: cgmath::Vector3::new(0.8, 0.8, 0.8),
+                ..Default::default()
+            });
+        state.renderables.push(Box::new(mesh));
+    }
+    assert_golden("tonemap_pipeline_aces_overexposed_lights", &mut state, Tolerance::default());
+}
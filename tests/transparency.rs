@@ -0,0 +1,76 @@
+mod common;
+
+use std::{any::TypeId, sync::Arc};
+
+use common::{assert_golden, Tolerance};
+use learn_wgpu2::{
+    camera::Camera,
+    instance::Instance,
+    render_context::RenderContext,
+    renderable::{sort_back_to_front, Renderable},
+    renderables::cube::Cube,
+    state::State,
+    transform::Transform,
+};
+
+// only the position matters for sorting
+struct Marker(cgmath::Point3<f32>);
+
+impl Renderable for Marker {
+    fn choose_pipeline(&self) -> TypeId {
+        unreachable!()
+    }
+    fn get_vertex_buffer(&self, _render_context: &RenderContext) -> Arc<wgpu::Buffer> {
+        unreachable!()
+    }
+    fn get_index_buffer(&self, _render_context: &RenderContext) -> Option<Arc<wgpu::Buffer>> {
+        unreachable!()
+    }
+    fn get_bind_groups<'a>(&'a mut self, _render_context: &'a RenderContext) -> Vec<&'a wgpu::BindGroup> {
+        unreachable!()
+    }
+    fn get_num_indices(&self) -> u32 {
+        unreachable!()
+    }
+    fn get_position(&self) -> cgmath::Point3<f32> {
+        self.0
+    }
+}
+
+#[test]
+fn sorts_farthest_first() {
+    // the default camera is at z = 2 looking down -z
+    let camera = Camera::default();
+    let mut near = Marker(cgmath::Point3::new(0.0, 0.0, 0.0));
+    let mut far = Marker(cgmath::Point3::new(0.0, 0.0, -10.0));
+    // far to the side, but closer along the view direction than the far marker
+    let mut side = Marker(cgmath::Point3::new(8.0, 0.0, -5.0));
+    let mut behind = Marker(cgmath::Point3::new(0.0, 0.0, 5.0));
+    let mut renderables: Vec<&mut dyn Renderable> = vec![&mut near, &mut behind, &mut far, &mut side];
+    sort_back_to_front(&mut renderables, &camera);
+    let z: Vec<f32> = renderables.iter().map(|renderable| renderable.get_position().z).collect();
+    assert_eq!(z, vec![-10.0, -5.0, 0.0, 5.0]);
+}
+
+// a tinted glass cube at the position
+fn glass(x: f32, y: f32, z: f32, tint: [f32; 4]) -> Cube {
+    Cube::new_transparent("assets/grass.jpg".to_string())
+        .with_transform(Transform::from_position(cgmath::Vector3::new(x, y, z)))
+        .with_instances(vec![Instance { tint, ..Default::default() }])
+}
+
+#[test]
+fn transparent_cubes_blend_back_to_front() {
+    let mut state = State::default();
+    // farther from the cubes than the other golden image tests, so all of them are in view
+    state.camera.pos = cgmath::Point3::new(4.5, 3.0, 7.5);
+    state.camera.yaw = -120.96;
+    state.camera.pitch = -18.93;
+    state.renderables.push(Box::new(Cube::new("assets/grass.jpg".to_string())));
+    // added nearest first, the red cube only shows through the blue one if they are drawn farthest first
+    state.renderables.push(Box::new(glass(2.2, 0.7, 1.1, [0.2, 0.2, 1.0, 0.5])));
+    state.renderables.push(Box::new(glass(1.3, 0.4, 0.6, [1.0, 0.2, 0.2, 0.5])));
+    // partly hidden behind the opaque cube, the transparent pass still tests against its depth
+    state.renderables.push(Box::new(glass(-1.0, 0.5, -1.2, [0.2, 1.0, 0.2, 0.5])));
+    assert_golden("transparent_cubes_back_to_front", &mut state, Tolerance::default());
+}
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use learn_wgpu2::{
+    my_texture::TextureSource,
+    renderable::validate_vertex_layout,
+    renderables::mesh::{Mesh, MeshData},
+    state::State,
+    vertex::{MeshVertex, Vertex, VertexAttribute, VertexLayout},
+};
+
+#[test]
+fn attributes_are_interleaved_in_order() {
+    let layout = VertexLayout::new(&[VertexAttribute::Weights, VertexAttribute::Position, VertexAttribute::Normal, VertexAttribute::Joints]);
+    assert_eq!(layout.stride(), 12 + 12 + 8 + 16);
+    let attributes = layout.attributes();
+    let locations: Vec<(u32, u64)> = attributes.iter().map(|a| (a.shader_location, a.offset)).collect();
+    assert_eq!(locations, vec![(0, 0), (2, 12), (6, 24), (7, 32)]);
+}
+
+#[test]
+fn position_and_tex_coords_match_vertex() {
+    let attributes = Vertex::LAYOUT.attributes();
+    let desc = Vertex::LAYOUT.desc(&attributes);
+    let expected = Vertex::desc();
+    assert_eq!(desc.array_stride, expected.array_stride);
+    assert_eq!(desc.attributes, expected.attributes);
+
+    let vertex = Vertex { position: [1.0, 2.0, 3.0], tex_coords: [4.0, 5.0] };
+    let packed = Vertex::LAYOUT.pack(&[MeshVertex::from(vertex)]);
+    assert_eq!(packed, bytemuck::bytes_of(&vertex));
+}
+
+#[test]
+fn pack_leaves_out_other_attributes() {
+    let vertex = MeshVertex {
+        position: [1.0, 2.0, 3.0],
+        color: [0.5, 0.5, 0.5, 1.0],
+        ..Default::default()
+    };
+    let layout = VertexLayout::new(&[VertexAttribute::Position, VertexAttribute::Color]);
+    let packed = layout.pack(&[vertex, vertex]);
+    assert_eq!(packed.len(), 2 * 28);
+    let floats: &[f32] = bytemuck::cast_slice(&packed[..28]);
+    assert_eq!(floats, &[1.0, 2.0, 3.0, 0.5, 0.5, 0.5, 1.0]);
+}
+
+fn lit_triangle() -> MeshData {
+    let vertices = vec![MeshVertex { normal: [0.0, 1.0, 0.0], ..Default::default() }; 3];
+    MeshData::from_mesh_vertices("triangle".to_string(), vertices, Vertex::LAYOUT.with(VertexAttribute::Normal), None)
+}
+
+#[test]
+fn renderables_are_validated_against_their_pipeline() {
+    let data = Arc::new(lit_triangle());
+    let mesh = Mesh::from_data(data.clone(), TextureSource::SolidColor([255, 255, 255, 255]));
+    assert!(validate_vertex_layout(&mesh).is_ok());
+
+    // the default pipeline does not read normals
+    let lit = Mesh::from_data(data, TextureSource::SolidColor([255, 255, 255, 255]))
+        .with_vertex_layout(Vertex::LAYOUT.with(VertexAttribute::Normal))
+        .unwrap();
+    let mismatch = validate_vertex_layout(&lit).unwrap_err();
+    assert_eq!(mismatch.expected, Vertex::LAYOUT);
+    assert_eq!(mismatch.actual, Vertex::LAYOUT.with(VertexAttribute::Normal));
+}
+
+#[test]
+fn mismatched_renderables_are_not_added() {
+    let data = Arc::new(lit_triangle());
+    let mut state = State::default();
+    let lit = Mesh::from_data(data.clone(), TextureSource::SolidColor([255, 255, 255, 255])).with_lighting().unwrap();
+    assert!(state.add_renderable(lit).is_ok());
+    let unlit = Mesh::from_data(data, TextureSource::SolidColor([255, 255, 255, 255]))
+        .with_vertex_layout(Vertex::LAYOUT.with(VertexAttribute::Normal))
+        .unwrap();
+    let mismatch = state.add_renderable(unlit).unwrap_err();
+    assert_eq!(mismatch.expected, Vertex::LAYOUT);
+    assert_eq!(state.renderables.len(), 1);
+}
+
+#[test]
+fn missing_attributes_are_rejected() {
+    let mesh = Mesh::from_data(Arc::new(lit_triangle()), TextureSource::SolidColor([255, 255, 255, 255]));
+    let mismatch = mesh.with_vertex_layout(Vertex::LAYOUT.with(VertexAttribute::Tangent)).err().unwrap();
+    assert_eq!(mismatch.expected, Vertex::LAYOUT.with(VertexAttribute::Tangent));
+    assert_eq!(mismatch.actual, Vertex::LAYOUT.with(VertexAttribute::Normal));
+}